@@ -1,12 +1,23 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use url::Url;
 
+use crate::body::Payload;
+use crate::clock::{ArcClock, StdClock};
+use crate::error::{Error, ErrorKind};
+use crate::header::{self, Header};
+use crate::hsts::HstsStore;
 use crate::pool::ConnectionPool;
 use crate::proxy::Proxy;
 use crate::request::Request;
 use crate::resolve::{ArcResolver, StdResolver};
-use std::time::Duration;
+use crate::stream::IoCounters;
+use crate::unit::{self, Unit};
+#[cfg(feature = "tls")]
+use crate::verify::{CertificateInfo, VerifyCallback};
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "cookies")]
 use {
@@ -20,11 +31,15 @@ pub struct AgentBuilder {
     config: AgentConfig,
     max_idle_connections: usize,
     max_idle_connections_per_host: usize,
+    max_idle_time: Option<Duration>,
     /// Cookies saved between requests.
     /// Invariant: All cookies must have a nonempty domain and path.
     #[cfg(feature = "cookies")]
     cookie_store: Option<CookieStore>,
     resolver: ArcResolver,
+    clock: ArcClock,
+    #[cfg(feature = "tls")]
+    verify_callback: Option<VerifyCallback>,
 }
 
 /// Config as built by AgentBuilder and then static for the lifetime of the Agent.
@@ -36,10 +51,169 @@ pub(crate) struct AgentConfig {
     pub timeout_write: Option<Duration>,
     pub timeout: Option<Duration>,
     pub redirects: u32,
+    pub request_quota: Option<u64>,
+    pub max_status_line_length: usize,
+    pub max_requests_per_connection: Option<usize>,
+    pub continue_timeout: Duration,
+    pub accept_language: Option<String>,
+    pub idempotency_keys: bool,
+    pub max_error_body_size: Option<usize>,
+    pub timing: bool,
+    pub url_rewrite: Option<UrlRewrite>,
+    pub upgrade_to_https: bool,
+    pub hsts: bool,
+    pub merge_trailers: Vec<String>,
+    pub host_configs: Vec<(String, HostConfig)>,
+    #[cfg(feature = "cookies")]
+    pub max_cookies_per_response: Option<usize>,
+    #[cfg(feature = "cookies")]
+    pub max_cookies_total: Option<usize>,
+    #[cfg(feature = "gzip")]
+    pub should_decompress: Option<DecompressFilter>,
+    #[cfg(feature = "gzip")]
+    pub sniff_encoding: bool,
     #[cfg(feature = "tls")]
     pub tls_config: Option<TLSClientConfig>,
 }
 
+impl AgentConfig {
+    /// The settings for the host of `url`, if any.
+    pub(crate) fn host_config(&self, url: &Url) -> Option<&HostConfig> {
+        let host = url.host_str()?;
+        self.host_configs
+            .iter()
+            .find(|(h, _)| h.eq_ignore_ascii_case(host))
+            .map(|(_, config)| config)
+    }
+}
+
+/// Settings for the requests to one host, see [`AgentBuilder::host_config`].
+///
+/// They take precedence over the agent's settings, but not over the ones of
+/// a request.
+#[derive(Debug, Clone, Default)]
+pub struct HostConfig {
+    pub(crate) headers: Vec<Header>,
+    pub(crate) timeout: Option<Duration>,
+}
+
+impl HostConfig {
+    /// Set a header on requests to the host, unless the request sets it
+    /// itself.
+    pub fn set(&mut self, header: &str, value: &str) -> &mut Self {
+        header::add_header(&mut self.headers, Header::new(header, value));
+        self
+    }
+
+    /// Timeout for the overall request to the host, like
+    /// [`AgentBuilder::timeout`].
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// Bytes read and written by an agent, see [`Agent::io_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoStats {
+    bytes_read: u64,
+    bytes_written: u64,
+}
+
+impl IoStats {
+    /// Bytes received from servers.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Bytes sent to servers.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+}
+
+/// A connection opened ahead of time by [`Agent::connect_only`].
+///
+/// The connection itself is in the agent's pool, ready for the next request
+/// to the same scheme, host and port. This only describes how it was set up.
+#[derive(Debug, Clone)]
+pub struct ConnectionHandle {
+    url: Url,
+    remote_addr: Option<SocketAddr>,
+    connect_time: Duration,
+}
+
+impl ConnectionHandle {
+    /// The url the connection was opened for.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The address of the server, or of the proxy when connecting through
+    /// one. `None` for connections that aren't over TCP.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// How long it took to set up the connection: the DNS lookup, the TCP
+    /// connect, any proxy handshake and, for `https`, the TLS handshake.
+    pub fn connect_time(&self) -> Duration {
+        self.connect_time
+    }
+}
+
+/// Rewrites the URL of each request before it's sent.
+#[derive(Clone)]
+pub(crate) struct UrlRewrite(Arc<dyn Fn(&Url) -> Url + Send + Sync>);
+
+impl UrlRewrite {
+    pub(crate) fn rewrite(&self, url: &Url) -> Url {
+        (self.0)(url)
+    }
+}
+
+impl std::fmt::Debug for UrlRewrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "UrlRewrite(...)")
+    }
+}
+
+/// Decides from a response's media type whether its `Content-Encoding`
+/// should be decoded.
+#[cfg(feature = "gzip")]
+#[derive(Clone)]
+pub(crate) struct DecompressFilter(pub(crate) Arc<dyn Fn(&str) -> bool + Send + Sync>);
+
+#[cfg(feature = "gzip")]
+impl DecompressFilter {
+    pub(crate) fn allows(&self, content_type: &str) -> bool {
+        (self.0)(content_type)
+    }
+}
+
+// Whether `content_type` matches a pattern like `text/html`, `text/*` or `*/*`.
+#[cfg(feature = "gzip")]
+fn media_type_matches(pattern: &str, content_type: &str) -> bool {
+    if pattern == "*/*" {
+        return true;
+    }
+    match pattern.strip_suffix("/*") {
+        Some(top) => content_type
+            .split('/')
+            .next()
+            .filter(|t| t.eq_ignore_ascii_case(top))
+            .is_some(),
+        None => pattern.eq_ignore_ascii_case(content_type),
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl std::fmt::Debug for DecompressFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DecompressFilter(...)")
+    }
+}
+
 /// Agents keep state between requests.
 ///
 /// By default, no state, such as cookies, is kept between requests.
@@ -86,6 +260,13 @@ pub(crate) struct AgentState {
     #[cfg(feature = "cookies")]
     pub(crate) cookie_tin: CookieTin,
     pub(crate) resolver: ArcResolver,
+    pub(crate) clock: ArcClock,
+    /// Hosts that sent a Strict-Transport-Security header.
+    pub(crate) hsts: HstsStore,
+    /// Bytes read and written on all connections.
+    pub(crate) io_counters: Arc<IoCounters>,
+    /// Requests sent so far, checked against the request quota.
+    pub(crate) requests_sent: AtomicU64,
 }
 
 impl Agent {
@@ -168,6 +349,156 @@ impl Agent {
         self.request("DELETE", path)
     }
 
+    /// Make an OPTIONS request from this agent.
+    ///
+    /// Together with the `Response::cors_*` accessors, this can be used to
+    /// check how a server answers a CORS preflight request.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let agent = ureq::agent();
+    /// let resp = agent.options("http://example.com/api")
+    ///     .set("Origin", "https://app.example.com")
+    ///     .set("Access-Control-Request-Method", "PUT")
+    ///     .call()?;
+    ///
+    /// let allowed = resp.cors_allow_methods().contains(&"PUT");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn options(&self, path: &str) -> Request {
+        self.request("OPTIONS", path)
+    }
+
+    /// Make a TRACE request from this agent.
+    ///
+    /// The server echoes the request it received as the response body, with
+    /// content type `message/http`. Since that exposes the request headers,
+    /// credentials (`Authorization`, `Proxy-Authorization` and `Cookie`) are
+    /// never sent with a TRACE.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let echo = ureq::agent()
+    ///     .trace("http://example.com/")
+    ///     .call()?
+    ///     .into_string()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn trace(&self, path: &str) -> Request {
+        self.request("TRACE", path)
+    }
+
+    /// Open a connection to the host of `url`, and keep it in the pool for a
+    /// later request to the same scheme, host and port.
+    ///
+    /// This does the DNS lookup, connect and TLS handshake ahead of time, so
+    /// that the first request doesn't wait for them. No request is sent. Like
+    /// any pooled connection, it's dropped once idle for longer than
+    /// [`max_idle_time`](AgentBuilder::max_idle_time), and it's not kept if
+    /// the pool is disabled.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let agent = ureq::agent();
+    /// agent.preconnect("http://example.com/")?;
+    ///
+    /// // Later.
+    /// let resp = agent.get("http://example.com/page").call()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn preconnect(&self, url: &str) -> Result<(), Error> {
+        self.connect_only(url).map(|_| ())
+    }
+
+    /// Like [`preconnect`](Agent::preconnect), but describes the connection
+    /// that was opened, including how long it took to set up.
+    ///
+    /// This separates the cost of a new connection from the cost of the
+    /// request itself, for instance in benchmarks. The connection is pooled,
+    /// and used by the next request to the same scheme, host and port.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let agent = ureq::agent();
+    /// let conn = agent.connect_only("http://example.com/")?;
+    /// println!("connected to {:?} in {:?}", conn.remote_addr(), conn.connect_time());
+    ///
+    /// let resp = agent.get("http://example.com/page").call()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect_only(&self, url: &str) -> Result<ConnectionHandle, Error> {
+        let url: Url = url.parse().map_err(|e: url::ParseError| {
+            ErrorKind::InvalidUrl
+                .msg(&format!("failed to parse URL '{}'", url))
+                .src(e)
+        })?;
+        let unit = Unit::new(self, "GET", &url, &vec![], &Payload::Empty.into_read());
+        let started = Instant::now();
+        let remote_addr = unit::preconnect(&unit).map_err(|e| e.url(url.clone()))?;
+        Ok(ConnectionHandle {
+            url,
+            remote_addr,
+            connect_time: started.elapsed(),
+        })
+    }
+
+    /// Close the idle connections in the pool, and stop pooling connections.
+    ///
+    /// The sockets are closed right away, rather than when the last clone of
+    /// the agent is dropped, which helps applications that need to release
+    /// them before exiting. Requests in flight aren't interrupted. Later
+    /// requests, from this agent or its clones, still work, but each opens a
+    /// new connection that's closed once its response is read.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let agent = ureq::agent();
+    /// agent.get("http://example.com/").call()?.into_string()?;
+    ///
+    /// agent.shutdown();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn shutdown(&self) {
+        self.state.pool.close();
+    }
+
+    /// The number of bytes read and written on all connections of this agent
+    /// and its clones, since it was built.
+    ///
+    /// This counts everything that went over the connections, like status
+    /// lines, headers and bodies as they were sent, including responses
+    /// whose body was only partly read. For https, it's the bytes before
+    /// encryption, so TLS overhead isn't counted.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let agent = ureq::agent();
+    /// agent.get("http://example.com/").call()?.into_string()?;
+    ///
+    /// let stats = agent.io_stats();
+    /// println!("{} bytes in, {} bytes out", stats.bytes_read(), stats.bytes_written());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn io_stats(&self) -> IoStats {
+        let counters = &self.state.io_counters;
+        IoStats {
+            bytes_read: counters.read.load(Ordering::Relaxed),
+            bytes_written: counters.written.load(Ordering::Relaxed),
+        }
+    }
+
     /// Read access to the cookie store.
     ///
     /// Used to persist the cookies to an external writer.
@@ -197,6 +528,7 @@ impl Agent {
 
 const DEFAULT_MAX_IDLE_CONNECTIONS: usize = 100;
 const DEFAULT_MAX_IDLE_CONNECTIONS_PER_HOST: usize = 1;
+pub(crate) const DEFAULT_MAX_STATUS_LINE_LENGTH: usize = 8 * 1024;
 
 impl AgentBuilder {
     pub fn new() -> Self {
@@ -208,14 +540,39 @@ impl AgentBuilder {
                 timeout_write: None,
                 timeout: None,
                 redirects: 5,
+                request_quota: None,
+                max_status_line_length: DEFAULT_MAX_STATUS_LINE_LENGTH,
+                max_requests_per_connection: None,
+                continue_timeout: Duration::from_secs(1),
+                accept_language: None,
+                idempotency_keys: false,
+                max_error_body_size: None,
+                timing: false,
+                url_rewrite: None,
+                upgrade_to_https: false,
+                hsts: false,
+                merge_trailers: vec![],
+                host_configs: vec![],
+                #[cfg(feature = "cookies")]
+                max_cookies_per_response: None,
+                #[cfg(feature = "cookies")]
+                max_cookies_total: None,
+                #[cfg(feature = "gzip")]
+                should_decompress: None,
+                #[cfg(feature = "gzip")]
+                sniff_encoding: false,
                 #[cfg(feature = "tls")]
                 tls_config: None,
             },
             max_idle_connections: DEFAULT_MAX_IDLE_CONNECTIONS,
             max_idle_connections_per_host: DEFAULT_MAX_IDLE_CONNECTIONS_PER_HOST,
+            max_idle_time: None,
             resolver: StdResolver.into(),
+            clock: StdClock.into(),
             #[cfg(feature = "cookies")]
             cookie_store: None,
+            #[cfg(feature = "tls")]
+            verify_callback: None,
         }
     }
 
@@ -225,18 +582,37 @@ impl AgentBuilder {
     // not implement clone, so we have to give ownership to the newly
     // built Agent.
     pub fn build(self) -> Agent {
+        #[allow(unused_mut)]
+        let mut config = self.config;
+        // Make the verifying TLS config once, instead of on every connect.
+        #[cfg(feature = "tls")]
+        {
+            if let Some(callback) = &self.verify_callback {
+                let tls_config = match &config.tls_config {
+                    Some(c) => &c.0,
+                    None => &*crate::stream::TLS_CONF,
+                };
+                let tls_config = crate::verify::with_callback(tls_config, callback);
+                config.tls_config = Some(TLSClientConfig(tls_config));
+            }
+        }
         Agent {
-            config: Arc::new(self.config),
+            config: Arc::new(config),
             state: Arc::new(AgentState {
                 pool: ConnectionPool::new_with_limits(
                     self.max_idle_connections,
                     self.max_idle_connections_per_host,
+                    self.max_idle_time,
                 ),
                 #[cfg(feature = "cookies")]
                 cookie_tin: CookieTin::new(
                     self.cookie_store.unwrap_or_else(|| CookieStore::default()),
                 ),
                 resolver: self.resolver,
+                clock: self.clock,
+                hsts: HstsStore::default(),
+                io_counters: Arc::default(),
+                requests_sent: AtomicU64::new(0),
             }),
         }
     }
@@ -287,6 +663,59 @@ impl AgentBuilder {
         self
     }
 
+    /// Disables connection pooling.
+    ///
+    /// Every request opens a fresh connection, which is closed once the
+    /// response has been read. This is the same as setting both
+    /// `max_idle_connections` and `max_idle_connections_per_host` to zero.
+    ///
+    /// ```
+    /// let agent = ureq::AgentBuilder::new()
+    ///     .no_pool()
+    ///     .build();
+    /// ```
+    pub fn no_pool(mut self) -> Self {
+        self.max_idle_connections = 0;
+        self.max_idle_connections_per_host = 0;
+        self
+    }
+
+    /// Sets how long a connection may sit idle in the pool and still be reused.
+    ///
+    /// When a request would reuse a pooled connection that has been idle for
+    /// longer, that connection is closed and a fresh one is opened instead.
+    /// Servers and middleboxes often drop idle connections silently, so this
+    /// avoids sending a request into a connection that is likely dead. By
+    /// default idle connections are reused no matter how old they are.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// let agent = ureq::AgentBuilder::new()
+    ///     .max_idle_time(Duration::from_secs(30))
+    ///     .build();
+    /// ```
+    pub fn max_idle_time(mut self, max: Duration) -> Self {
+        self.max_idle_time = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of requests a single connection may serve.
+    ///
+    /// Once a connection has been used for this many requests, it is closed
+    /// after its last response is read instead of going back into the pool.
+    /// This helps spread load when a load balancer pins each connection to one
+    /// backend. By default there is no limit.
+    ///
+    /// ```
+    /// let agent = ureq::AgentBuilder::new()
+    ///     .max_requests_per_connection(100)
+    ///     .build();
+    /// ```
+    pub fn max_requests_per_connection(mut self, max: usize) -> Self {
+        self.config.max_requests_per_connection = Some(max);
+        self
+    }
+
     /// Configures a custom resolver to be used by this agent. By default,
     /// address-resolution is done by std::net::ToSocketAddrs. This allows you
     /// to override that resolution with your own alternative. Useful for
@@ -311,10 +740,40 @@ impl AgentBuilder {
         self
     }
 
+    /// Configures the clock that timeouts are measured against. By default,
+    /// this is [Instant::now](std::time::Instant::now).
+    ///
+    /// This is meant for testing: with a clock that is advanced by hand,
+    /// timeouts can be hit without waiting for them. A
+    /// `Fn() -> Instant` is a valid clock.
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let now = Arc::new(Mutex::new(Instant::now()));
+    /// let clock = now.clone();
+    /// let agent = ureq::builder()
+    ///     .timeout(Duration::from_secs(10))
+    ///     .clock(move || *clock.lock().unwrap())
+    ///     .build();
+    ///
+    /// // Later, to go past the timeout:
+    /// *now.lock().unwrap() += Duration::from_secs(11);
+    /// ```
+    pub fn clock(mut self, clock: impl crate::Clock + 'static) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
     /// Timeout for the socket connection to be successful.
     /// If both this and `.timeout()` are both set, `.timeout_connect()`
     /// takes precedence.
     ///
+    /// For https, the TLS handshake must also be done within this time. Running
+    /// out of it fails the request with [`ErrorKind::ConnectionFailed`](crate::ErrorKind),
+    /// and a source [`io::Error`](std::io::Error) of kind `TimedOut`.
+    ///
     /// The default is 30 seconds.
     ///
     /// ```
@@ -401,6 +860,292 @@ impl AgentBuilder {
         self
     }
 
+    /// Sets an `Accept-Language` header to send with every request.
+    ///
+    /// Requests that set their own `Accept-Language`, for instance with
+    /// [`Request::accept_language`](crate::Request::accept_language), send
+    /// that instead.
+    ///
+    /// ```
+    /// let agent = ureq::builder()
+    ///     .accept_language("sv-SE, sv;q=0.9, en;q=0.5")
+    ///     .build();
+    /// ```
+    pub fn accept_language(mut self, languages: &str) -> Self {
+        self.config.accept_language = Some(languages.to_string());
+        self
+    }
+
+    /// Send a generated `Idempotency-Key` header with every POST and PATCH
+    /// request that doesn't set one.
+    ///
+    /// Some APIs use this header to recognize a request that is sent again,
+    /// and then don't process it twice. The key is a random UUID, generated
+    /// once per request, so a retry of the request sends the same key. Like
+    /// [`Request::with_idempotency_key`](crate::Request::with_idempotency_key),
+    /// this makes the requests retryable. The default is false.
+    ///
+    /// ```
+    /// let agent = ureq::builder()
+    ///     .idempotency_keys(true)
+    ///     .build();
+    /// ```
+    pub fn idempotency_keys(mut self, enabled: bool) -> Self {
+        self.config.idempotency_keys = enabled;
+        self
+    }
+
+    /// Only decode a `Content-Encoding` when this returns true for the
+    /// response's media type, such as `"text/html"` (see
+    /// [`Response::content_type`](crate::Response::content_type)).
+    ///
+    /// By default, bodies sent with `Content-Encoding: gzip` or `deflate` are
+    /// always decoded. Some misconfigured servers label files that are
+    /// already compressed, like images or zip archives, as gzip; use this to
+    /// read those as they are.
+    ///
+    /// ```
+    /// let agent = ureq::builder()
+    ///     .should_decompress(|content_type| {
+    ///         !content_type.starts_with("image/") && content_type != "application/zip"
+    ///     })
+    ///     .build();
+    /// ```
+    #[cfg(feature = "gzip")]
+    pub fn should_decompress(
+        mut self,
+        filter: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.config.should_decompress = Some(DecompressFilter(Arc::new(filter)));
+        self
+    }
+
+    /// Only decode a `Content-Encoding` for these media types.
+    ///
+    /// A shorthand for [`should_decompress`](#method.should_decompress) with
+    /// an allowlist. Types are compared ignoring case, and a pattern like
+    /// `text/*` matches every subtype. Responses of other types are read as
+    /// they were received, still compressed.
+    ///
+    /// ```
+    /// let agent = ureq::builder()
+    ///     .decompress_content_types(&["text/*", "application/json"])
+    ///     .build();
+    /// ```
+    #[cfg(feature = "gzip")]
+    pub fn decompress_content_types(self, types: &[&str]) -> Self {
+        let types: Vec<String> = types.iter().map(|t| t.trim().to_string()).collect();
+        self.should_decompress(move |content_type| {
+            types.iter().any(|t| media_type_matches(t, content_type))
+        })
+    }
+
+    /// Decompress bodies that look like gzip or zlib data, even without a
+    /// `Content-Encoding` header.
+    ///
+    /// Some servers compress responses but don't say so. With this on, the
+    /// first bytes of a body without `Content-Encoding` are checked for the
+    /// gzip or zlib signature, and the body is decoded if one is found. Other
+    /// bodies are read as they are. Only the zlib headers that compressors
+    /// actually write are recognized, but a text body that happens to start
+    /// with one, like `x^`, fails to decode. The default is false.
+    ///
+    /// Like `Content-Encoding`, this is subject to
+    /// [`should_decompress`](#method.should_decompress).
+    ///
+    /// ```
+    /// let agent = ureq::builder()
+    ///     .sniff_encoding(true)
+    ///     .build();
+    /// ```
+    #[cfg(feature = "gzip")]
+    pub fn sniff_encoding(mut self, enabled: bool) -> Self {
+        self.config.sniff_encoding = enabled;
+        self
+    }
+
+    /// Read the body of error responses eagerly, keeping at most this many bytes.
+    ///
+    /// By default the [Response](crate::Response) in an
+    /// [`Error::Status`](crate::Error::Status) still holds the connection,
+    /// and its body is read from the server on demand. With this set, the body
+    /// is read when the error is created: up to `max` bytes are kept in memory
+    /// and the rest is discarded. The error then holds a bounded buffer and no
+    /// connection, which is handy when errors are stored or sent elsewhere.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let agent = ureq::builder()
+    ///     .max_error_body_size(64 * 1024)
+    ///     .build();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn max_error_body_size(mut self, max: usize) -> Self {
+        self.config.max_error_body_size = Some(max);
+        self
+    }
+
+    /// Make the named trailers of a chunked response available as headers.
+    ///
+    /// Some protocols, like gRPC, send a status in the trailer section after
+    /// the body. Trailers with these names are merged into the headers, so
+    /// [`ResponseHead::header`](crate::ResponseHead::header) and friends find
+    /// them. Trailers only exist once the body has been read to the end,
+    /// which makes this mostly useful with
+    /// [`Response::split`](crate::Response::split). Other trailers are
+    /// discarded, except for requests made with
+    /// [`Request::te_trailers`](crate::Request::te_trailers).
+    ///
+    /// ```
+    /// let agent = ureq::builder()
+    ///     .merge_trailers(&["grpc-status", "grpc-message"])
+    ///     .build();
+    /// ```
+    pub fn merge_trailers(mut self, names: &[&str]) -> Self {
+        self.config.merge_trailers = names.iter().map(|n| n.to_string()).collect();
+        self
+    }
+
+    /// Settings for the requests to one host, like default headers, that take
+    /// precedence over the agent's own.
+    ///
+    /// The host is compared ignoring case, and without the port, to the host
+    /// of the request url, after any [`url_rewrite`](#method.url_rewrite).
+    /// Calling this again for the same host changes the same settings.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// let agent = ureq::builder()
+    ///     .host_config("api.example.com", |host| {
+    ///         host.set("X-Api-Key", "secret")
+    ///             .timeout(Duration::from_secs(5));
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn host_config(mut self, host: &str, configure: impl FnOnce(&mut HostConfig)) -> Self {
+        let configs = &mut self.config.host_configs;
+        let index = match configs
+            .iter()
+            .position(|(h, _)| h.eq_ignore_ascii_case(host))
+        {
+            Some(index) => index,
+            None => {
+                configs.push((host.to_string(), HostConfig::default()));
+                configs.len() - 1
+            }
+        };
+        configure(&mut configs[index].1);
+        self
+    }
+
+    /// Rewrite the URL of every request from this agent before it's sent.
+    ///
+    /// This can send traffic to a mirror, add a query parameter to every
+    /// request, or upgrade `http` to `https`. The rewrite also applies to each
+    /// redirect, after the `Location` has been resolved against the URL that
+    /// was redirected from. The [Response](crate::Response) reports the
+    /// rewritten URL.
+    ///
+    /// ```
+    /// let agent = ureq::builder()
+    ///     .url_rewrite(|url| {
+    ///         let mut url = url.clone();
+    ///         if url.host_str() == Some("example.com") {
+    ///             url.set_host(Some("mirror.example.com")).unwrap();
+    ///         }
+    ///         url
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn url_rewrite(mut self, rewrite: impl Fn(&Url) -> Url + Send + Sync + 'static) -> Self {
+        self.config.url_rewrite = Some(UrlRewrite(Arc::new(rewrite)));
+        self
+    }
+
+    /// Send every `http` request as `https` instead.
+    ///
+    /// This guards against accidentally sending anything in plaintext. A
+    /// non-default port in the URL is kept. Off by default.
+    ///
+    /// ```
+    /// let agent = ureq::builder()
+    ///     .upgrade_to_https(true)
+    ///     .build();
+    /// ```
+    pub fn upgrade_to_https(mut self, upgrade: bool) -> Self {
+        self.config.upgrade_to_https = upgrade;
+        self
+    }
+
+    /// Honor `Strict-Transport-Security` headers.
+    ///
+    /// When an `https` response carries this header, later `http` requests
+    /// from this agent to the same host are sent as `https` until the
+    /// header's `max-age` runs out. With `includeSubDomains`, subdomains of
+    /// the host are upgraded too. Like browsers, the header is ignored on
+    /// `http` responses and for IP addresses. Off by default.
+    ///
+    /// ```
+    /// let agent = ureq::builder()
+    ///     .hsts(true)
+    ///     .build();
+    /// ```
+    pub fn hsts(mut self, enabled: bool) -> Self {
+        self.config.hsts = enabled;
+        self
+    }
+
+    /// Record timings of each request, like
+    /// [`Response::time_to_first_byte`](crate::Response::time_to_first_byte).
+    ///
+    /// This is off by default.
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let agent = ureq::builder()
+    ///     .timing(true)
+    ///     .build();
+    /// let resp = agent.get("http://example.com/").call()?;
+    /// println!("first byte after {:?}", resp.time_to_first_byte());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn timing(mut self, enabled: bool) -> Self {
+        self.config.timing = enabled;
+        self
+    }
+
+    /// How long to wait for a `100 Continue` before sending the request body.
+    ///
+    /// When a request has the header `Expect: 100-continue`, ureq sends the
+    /// headers first and waits for the server to confirm it wants the body.
+    /// Servers that don't support this never answer, so after this timeout
+    /// the body is sent anyway. If the server answers with a final status
+    /// instead, like `401` or `417`, that response is returned and the body is
+    /// never sent.
+    ///
+    /// Defaults to 1 second.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let agent = ureq::builder()
+    ///     .continue_timeout(std::time::Duration::from_millis(500))
+    ///     .build();
+    /// let result = agent.put("http://httpbin.org/put")
+    ///     .set("Expect", "100-continue")
+    ///     .send_bytes(&[0; 100_000]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn continue_timeout(mut self, timeout: Duration) -> Self {
+        self.config.continue_timeout = timeout;
+        self
+    }
+
     /// How many redirects to follow.
     ///
     /// Defaults to `5`. Set to `0` to avoid redirects and instead
@@ -434,8 +1179,62 @@ impl AgentBuilder {
         self
     }
 
+    /// The most requests the agent, and all its clones, may send.
+    ///
+    /// Once `n` requests have been sent, every further request fails with
+    /// [`ErrorKind::QuotaExceeded`](crate::ErrorKind::QuotaExceeded) without
+    /// being sent. This catches runaway request loops in tests, and enforces
+    /// hard budgets. Redirects and retries count as requests. The default is
+    /// no limit.
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let agent = ureq::builder()
+    ///     .request_quota(1000)
+    ///     .build();
+    ///
+    /// match agent.get("http://example.com/").call() {
+    ///     Err(e) if e.kind() == ureq::ErrorKind::QuotaExceeded => println!("out of requests"),
+    ///     result => println!("{}", result?.status()),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn request_quota(mut self, n: u64) -> Self {
+        self.config.request_quota = Some(n);
+        self
+    }
+
+    /// The longest status line (`HTTP/1.1 200 OK`) we accept from a server,
+    /// not counting the final CRLF. A longer status line, typically due to an
+    /// excessive reason phrase, fails the request with a `BadStatus` error
+    /// instead of being read into memory.
+    ///
+    /// Defaults to 8 KiB.
+    ///
+    /// ```
+    /// let agent = ureq::builder()
+    ///     .max_status_line_length(64 * 1024)
+    ///     .build();
+    /// ```
+    pub fn max_status_line_length(mut self, max: usize) -> Self {
+        self.config.max_status_line_length = max;
+        self
+    }
+
     /// Set the TLS client config to use for the connection. See [`ClientConfig`](https://docs.rs/rustls/latest/rustls/struct.ClientConfig.html).
     ///
+    /// rustls never negotiates anything older than TLS 1.2. To require TLS 1.3,
+    /// set `versions` to only `rustls::ProtocolVersion::TLSv1_3`; restrict the
+    /// cipher suites with `ciphersuites`.
+    ///
+    /// The default config, shared by all agents, caches TLS sessions in memory,
+    /// so new connections to a host resume an earlier session rather than
+    /// doing a full handshake. A config set here uses its own
+    /// `session_persistence` instead, which `ClientConfig::new()` also sets to
+    /// an in-memory cache.
+    ///
     /// Example:
     /// ```
     /// # fn main() -> Result<(), ureq::Error> {
@@ -454,6 +1253,45 @@ impl AgentBuilder {
         self
     }
 
+    /// Decide whether to trust the certificate of a server yourself.
+    ///
+    /// During each TLS handshake, `callback` is given the certificate chain
+    /// presented by the server and the host name. The connection goes ahead
+    /// if it returns true, and fails if it returns false. This allows trust
+    /// decisions the normal verification can't make, like trusting a
+    /// self-signed certificate the first time it's seen, or checking a
+    /// revocation list.
+    ///
+    /// **The callback replaces the normal verification.** Whether the chain
+    /// passed it is available from
+    /// [`CertificateInfo::is_trusted`](crate::CertificateInfo::is_trusted),
+    /// but accepting a chain that didn't means the connection is only as
+    /// secure as the checks the callback makes. Accepting every certificate
+    /// lets anyone on the network read and change the traffic.
+    ///
+    /// The callback applies on top of [`tls_config`](#method.tls_config), if
+    /// one is set, whose root certificates decide `is_trusted`.
+    ///
+    /// Example:
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let pinned: Vec<u8> = vec![/* DER of the expected certificate */];
+    /// let agent = ureq::builder()
+    ///     .verify_callback(move |cert| cert.is_trusted() || cert.end_entity() == &pinned[..])
+    ///     .build();
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn verify_callback(
+        mut self,
+        callback: impl Fn(&CertificateInfo) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.verify_callback = Some(VerifyCallback(Arc::new(callback)));
+        self
+    }
+
     /// Provide the cookie store to be used for all requests using this agent.
     ///
     /// This is useful in two cases. First when there is a need to persist cookies
@@ -485,6 +1323,35 @@ impl AgentBuilder {
         self.cookie_store = Some(cookie_store);
         self
     }
+
+    /// Store at most `max` cookies from the `Set-Cookie` headers of one
+    /// response. The rest are discarded.
+    ///
+    /// Without a limit, a server can fill the cookie store with as many
+    /// cookies as it likes, and have all of them sent back on every request.
+    ///
+    /// ```
+    /// let agent = ureq::builder()
+    ///     .max_cookies_per_response(50)
+    ///     .max_cookies_total(3000)
+    ///     .build();
+    /// ```
+    #[cfg(feature = "cookies")]
+    pub fn max_cookies_per_response(mut self, max: usize) -> Self {
+        self.config.max_cookies_per_response = Some(max);
+        self
+    }
+
+    /// Store at most `max` cookies in total. Once that many are stored, new
+    /// cookies are discarded, while updates to stored cookies are still
+    /// made. Cookies that were in the store given to
+    /// [`cookie_store()`](#method.cookie_store) count too. Expired cookies
+    /// don't count.
+    #[cfg(feature = "cookies")]
+    pub fn max_cookies_total(mut self, max: usize) -> Self {
+        self.config.max_cookies_total = Some(max);
+        self
+    }
 }
 
 #[cfg(feature = "tls")]
@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use percent_encoding::percent_decode_str;
+
+use crate::header::{is_tchar, is_ws, ValueParser};
+
+// How long an alternative is valid without an `ma` parameter.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// An alternative service from an `Alt-Svc` header.
+/// https://tools.ietf.org/html/rfc7838#section-3
+///
+/// A server uses this to advertise that it can also be reached at another
+/// endpoint, possibly over another protocol, such as HTTP/3.
+///
+/// ```
+/// let s = "HTTP/1.1 200 OK\r\n\
+///     Alt-Svc: h3=\":443\"; ma=2592000, h2=\"alt.example.com:8443\"\r\n\
+///     \r\n";
+/// let resp = s.parse::<ureq::Response>().unwrap();
+/// let alt = resp.alt_svc();
+///
+/// assert_eq!(alt[0].protocol(), "h3");
+/// assert_eq!(alt[0].host(), None);
+/// assert_eq!(alt[0].port(), 443);
+/// assert_eq!(alt[0].max_age().as_secs(), 2592000);
+/// assert_eq!(alt[1].host(), Some("alt.example.com"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AltService {
+    protocol: String,
+    host: Option<String>,
+    port: u16,
+    max_age: Duration,
+}
+
+impl AltService {
+    /// The ALPN protocol id of the alternative, like `h2` or `h3`.
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// The host of the alternative, or None if it's the same host as the
+    /// request's.
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    /// The port of the alternative.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// How long the alternative may be used, from when the response was
+    /// received. 24 hours unless the server says otherwise.
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+}
+
+/// Parse the value of an `Alt-Svc` header. Alternatives that can't be
+/// parsed are skipped, and `clear` gives none.
+pub(crate) fn parse_alt_svc(value: &str) -> Vec<AltService> {
+    let mut p = ValueParser::new(value);
+    let mut services = vec![];
+    loop {
+        p.skip(|c| c == b',' || is_ws(c));
+        if p.done() {
+            break;
+        }
+        let service = parse_alternative(&mut p);
+        services.extend(service);
+        // Skip anything unparsed up to the next alternative.
+        p.skip(|c| c != b',');
+    }
+    services
+}
+
+// alt-value = alternative *( OWS ";" OWS parameter )
+// alternative = protocol-id "=" alt-authority
+fn parse_alternative(p: &mut ValueParser) -> Option<AltService> {
+    let protocol = p.take(is_tchar);
+    if protocol.is_empty() || p.peek() != Some(b'=') {
+        return None;
+    }
+    p.pos += 1;
+    if p.peek() != Some(b'"') {
+        return None;
+    }
+    let authority = p.quoted_string();
+    let colon = authority.rfind(':')?;
+    let port = authority[colon + 1..].parse().ok()?;
+    let host = &authority[..colon];
+    let mut service = AltService {
+        protocol: percent_decode_str(&protocol)
+            .decode_utf8()
+            .ok()?
+            .into_owned(),
+        host: if host.is_empty() {
+            None
+        } else {
+            Some(host.to_string())
+        },
+        port,
+        max_age: DEFAULT_MAX_AGE,
+    };
+
+    loop {
+        p.skip(is_ws);
+        if p.peek() != Some(b';') {
+            break;
+        }
+        p.pos += 1;
+        p.skip(is_ws);
+        let name = p.take(is_tchar);
+        if p.peek() != Some(b'=') {
+            break;
+        }
+        p.pos += 1;
+        let value = if p.peek() == Some(b'"') {
+            p.quoted_string()
+        } else {
+            p.take(is_tchar)
+        };
+        if name.eq_ignore_ascii_case("ma") {
+            service.max_age = Duration::from_secs(value.parse().ok()?);
+        }
+    }
+    Some(service)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn h3() {
+        let alt = parse_alt_svc(r#"h3=":443"; ma=2592000"#);
+        assert_eq!(
+            alt,
+            vec![AltService {
+                protocol: "h3".to_string(),
+                host: None,
+                port: 443,
+                max_age: Duration::from_secs(2592000),
+            }]
+        );
+    }
+
+    #[test]
+    fn multiple() {
+        let alt = parse_alt_svc(
+            r#"h3-29=":443"; ma=3600, h2="alt.example.com:8000"; persist=1, w%3Dx="[::1]:99""#,
+        );
+        assert_eq!(alt.len(), 3);
+        assert_eq!(alt[0].protocol(), "h3-29");
+        assert_eq!(alt[0].max_age(), Duration::from_secs(3600));
+        assert_eq!(alt[1].protocol(), "h2");
+        assert_eq!(alt[1].host(), Some("alt.example.com"));
+        assert_eq!(alt[1].port(), 8000);
+        assert_eq!(alt[1].max_age(), DEFAULT_MAX_AGE);
+        assert_eq!(alt[2].protocol(), "w=x");
+        assert_eq!(alt[2].host(), Some("[::1]"));
+        assert_eq!(alt[2].port(), 99);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse_alt_svc("clear").is_empty());
+        assert!(parse_alt_svc("").is_empty());
+        let alt = parse_alt_svc(r#"h2=443, h3=":x", h2=":1"; ma=soon, h3=":443""#);
+        assert_eq!(alt.len(), 1);
+        assert_eq!(alt[0].port(), 443);
+    }
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+
+use crate::header::{is_tchar, is_ws, ValueParser};
+
+/// One authentication challenge from a `WWW-Authenticate` header.
+/// https://tools.ietf.org/html/rfc7235#section-4.1
+///
+/// A challenge names an auth scheme, such as `Basic`, `Bearer` or `Digest`,
+/// followed either by a list of parameters, or by a single opaque token
+/// (token68).
+///
+/// ```
+/// let s = "HTTP/1.1 401 Unauthorized\r\n\
+///     WWW-Authenticate: Basic realm=\"example\", charset=\"UTF-8\"\r\n\
+///     \r\n";
+/// let resp = s.parse::<ureq::Response>().unwrap();
+/// let challenges = resp.www_authenticate();
+///
+/// assert_eq!(challenges[0].scheme(), "Basic");
+/// assert_eq!(challenges[0].param("realm"), Some("example"));
+/// ```
+#[derive(Clone, PartialEq)]
+pub struct AuthChallenge {
+    scheme: String,
+    token68: Option<String>,
+    params: Vec<(String, String)>,
+}
+
+impl fmt::Debug for AuthChallenge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.scheme)?;
+        if let Some(token68) = &self.token68 {
+            write!(f, " {}", token68)?;
+        }
+        for (i, (name, value)) in self.params.iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(f, "{}{}={:?}", sep, name, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl AuthChallenge {
+    /// The auth scheme, as sent by the server. Compare it ignoring case.
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// The value of the named parameter, if any. Names are compared ignoring
+    /// case, and quoted values are returned without quotes.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// All parameters in the order they appear in the challenge.
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// The opaque token of a challenge like `Negotiate abcd==`, if any.
+    pub fn token68(&self) -> Option<&str> {
+        self.token68.as_deref()
+    }
+}
+
+/// Parse the value of one `WWW-Authenticate` (or `Proxy-Authenticate`) header
+/// into its challenges. Parts that can't be parsed are skipped.
+pub(crate) fn parse_challenges(value: &str) -> Vec<AuthChallenge> {
+    let mut p = ValueParser::new(value);
+    let mut challenges = vec![];
+    loop {
+        p.skip(|c| c == b',' || is_ws(c));
+        if p.done() {
+            break;
+        }
+        let scheme = p.take(is_tchar);
+        if scheme.is_empty() {
+            // Not a challenge; skip ahead to the next comma.
+            p.skip(|c| c != b',');
+            continue;
+        }
+        let mut challenge = AuthChallenge {
+            scheme,
+            token68: None,
+            params: vec![],
+        };
+        p.skip(is_ws);
+
+        // A token68 is the only thing in its challenge, so it's followed by a
+        // comma or the end. Otherwise it's the name of the first param.
+        let mark = p.pos;
+        let mut token68 = p.take(is_token68);
+        token68.push_str(&p.take(|c| c == b'='));
+        p.skip(is_ws);
+        if !token68.is_empty() && (p.done() || p.peek() == Some(b',')) {
+            challenge.token68 = Some(token68);
+        } else {
+            p.pos = mark;
+        }
+
+        while challenge.token68.is_none() {
+            // Parameters are separated by commas, but so are challenges.
+            // What follows the comma is a param only if it looks like `name=`.
+            let mark = p.pos;
+            p.skip(|c| c == b',' || is_ws(c));
+            let name = p.take(is_tchar);
+            p.skip(is_ws);
+            if name.is_empty() || p.peek() != Some(b'=') {
+                p.pos = mark;
+                break;
+            }
+            p.pos += 1;
+            p.skip(is_ws);
+            let value = if p.peek() == Some(b'"') {
+                p.quoted_string()
+            } else {
+                p.take(is_tchar)
+            };
+            challenge.params.push((name, value));
+        }
+
+        challenges.push(challenge);
+    }
+    challenges
+}
+
+// token68 = 1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"="
+fn is_token68(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"-._~+/".contains(&c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_realm() {
+        let c = parse_challenges(r#"Basic realm="x""#);
+        assert_eq!(c.len(), 1);
+        assert_eq!(c[0].scheme(), "Basic");
+        assert_eq!(c[0].param("realm"), Some("x"));
+        assert_eq!(c[0].param("REALM"), Some("x"));
+        assert_eq!(c[0].token68(), None);
+    }
+
+    #[test]
+    fn digest_params() {
+        let c = parse_challenges(concat!(
+            r#"Digest realm="http-auth@example.org", qop="auth, auth-int", "#,
+            r#"algorithm=SHA-256, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", "#,
+            r#"opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS", stale=false"#,
+        ));
+        assert_eq!(c.len(), 1);
+        assert_eq!(c[0].scheme(), "Digest");
+        assert_eq!(c[0].params().len(), 6);
+        assert_eq!(c[0].param("realm"), Some("http-auth@example.org"));
+        assert_eq!(c[0].param("qop"), Some("auth, auth-int"));
+        assert_eq!(c[0].param("algorithm"), Some("SHA-256"));
+        assert_eq!(
+            c[0].param("nonce"),
+            Some("7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v")
+        );
+        assert_eq!(c[0].param("stale"), Some("false"));
+    }
+
+    #[test]
+    fn multiple_challenges() {
+        let c = parse_challenges(
+            r#"Newauth realm="apps", type=1, title="Login to \"apps\"", Basic realm="simple", Negotiate, Bearer abc.def=="#,
+        );
+        assert_eq!(c.len(), 4);
+        assert_eq!(c[0].scheme(), "Newauth");
+        assert_eq!(c[0].param("type"), Some("1"));
+        assert_eq!(c[0].param("title"), Some(r#"Login to "apps""#));
+        assert_eq!(c[1].scheme(), "Basic");
+        assert_eq!(
+            c[1].params(),
+            &[("realm".to_string(), "simple".to_string())]
+        );
+        assert_eq!(c[2].scheme(), "Negotiate");
+        assert!(c[2].params().is_empty());
+        assert_eq!(c[3].scheme(), "Bearer");
+        assert_eq!(c[3].token68(), Some("abc.def=="));
+    }
+
+    #[test]
+    fn garbage() {
+        assert!(parse_challenges("").is_empty());
+        assert!(parse_challenges(" , ,").is_empty());
+        let c = parse_challenges(r#""oops", Basic realm="unterminated"#);
+        assert_eq!(c.len(), 1);
+        assert_eq!(c[0].param("realm"), Some("unterminated"));
+    }
+}
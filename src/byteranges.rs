@@ -0,0 +1,216 @@
+use std::io::{self, BufRead, Read};
+
+use crate::header::{get_header, Header};
+
+/// One range of the body of a `206 Partial Content` response.
+///
+/// See [`Response::into_byte_ranges`](crate::Response::into_byte_ranges).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteRangePart {
+    content_type: Option<String>,
+    start: u64,
+    end: u64,
+    complete_length: Option<u64>,
+    body: Vec<u8>,
+}
+
+impl ByteRangePart {
+    pub(crate) fn new(
+        content_type: Option<String>,
+        start: u64,
+        end: u64,
+        complete_length: Option<u64>,
+        body: Vec<u8>,
+    ) -> Self {
+        ByteRangePart {
+            content_type,
+            start,
+            end,
+            complete_length,
+            body,
+        }
+    }
+
+    /// The `Content-Type` of this part, if any.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Offset of the first byte of this part in the complete body.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Offset of the last byte of this part in the complete body. The range
+    /// is inclusive, like in `Content-Range`.
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Size of the complete body, if the server knows it.
+    pub fn complete_length(&self) -> Option<u64> {
+        self.complete_length
+    }
+
+    /// The bytes of this part.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Turn this part into its bytes.
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+}
+
+/// Parse a `Content-Range` like `bytes 0-499/1234` into the first and last
+/// byte and the complete length, if known.
+/// https://tools.ietf.org/html/rfc7233#section-4.2
+pub(crate) fn parse_content_range(value: &str) -> Option<(u64, u64, Option<u64>)> {
+    let value = value.trim();
+    if value.len() < 6 || !value[..6].eq_ignore_ascii_case("bytes ") {
+        return None;
+    }
+    let (range, complete_length) = {
+        let mut split = value[6..].splitn(2, '/');
+        (split.next()?.trim(), split.next()?.trim())
+    };
+    let (start, end) = {
+        let mut split = range.splitn(2, '-');
+        (split.next()?.parse().ok()?, split.next()?.parse().ok()?)
+    };
+    if end < start {
+        return None;
+    }
+    let complete_length = match complete_length {
+        "*" => None,
+        n => Some(n.parse().ok()?),
+    };
+    Some((start, end, complete_length))
+}
+
+/// Reads the parts of a `multipart/byteranges` body one at a time.
+/// https://tools.ietf.org/html/rfc7233#appendix-A
+pub(crate) struct ByteRanges<R> {
+    reader: R,
+    delimiter: String,
+    started: bool,
+    done: bool,
+}
+
+impl<R: BufRead> ByteRanges<R> {
+    pub(crate) fn new(reader: R, boundary: &str) -> Self {
+        ByteRanges {
+            reader,
+            delimiter: format!("--{}", boundary),
+            started: false,
+            done: false,
+        }
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(invalid("unexpected end of multipart body"));
+        }
+        Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+    }
+
+    // Read up to and including the next delimiter line. Returns false if it
+    // was the final one.
+    fn next_delimiter(&mut self) -> io::Result<bool> {
+        loop {
+            let line = self.read_line()?;
+            if let Some(rest) = line.strip_prefix(&self.delimiter) {
+                return Ok(!rest.trim_end().starts_with("--"));
+            }
+        }
+    }
+
+    fn read_part(&mut self) -> io::Result<ByteRangePart> {
+        let mut headers = vec![];
+        loop {
+            let line = self.read_line()?;
+            if line.is_empty() {
+                break;
+            }
+            if let Ok(header) = line.parse::<Header>() {
+                headers.push(header);
+            }
+        }
+        let content_range = get_header(&headers, "content-range")
+            .ok_or_else(|| invalid("multipart part without Content-Range"))?;
+        let (start, end, complete_length) = parse_content_range(content_range)
+            .ok_or_else(|| invalid(&format!("bad Content-Range: {}", content_range)))?;
+        let content_type = get_header(&headers, "content-type").map(String::from);
+
+        let mut body = vec![];
+        let len = end - start + 1;
+        (&mut self.reader).take(len).read_to_end(&mut body)?;
+        if (body.len() as u64) < len {
+            return Err(invalid("unexpected end of multipart body"));
+        }
+
+        Ok(ByteRangePart::new(
+            content_type,
+            start,
+            end,
+            complete_length,
+            body,
+        ))
+    }
+}
+
+impl<R: BufRead> Iterator for ByteRanges<R> {
+    type Item = io::Result<ByteRangePart>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = (|| {
+            if !self.started {
+                // Skip the preamble.
+                self.started = true;
+                if !self.next_delimiter()? {
+                    return Ok(None);
+                }
+            }
+            let part = self.read_part()?;
+            if !self.next_delimiter()? {
+                self.done = true;
+            }
+            Ok(Some(part))
+        })();
+        match result {
+            Ok(Some(part)) => Some(Ok(part)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[test]
+fn test_parse_content_range() {
+    assert_eq!(
+        parse_content_range("bytes 0-499/1234"),
+        Some((0, 499, Some(1234)))
+    );
+    assert_eq!(
+        parse_content_range("Bytes 500-999/*"),
+        Some((500, 999, None))
+    );
+    assert_eq!(parse_content_range("bytes */1234"), None);
+    assert_eq!(parse_content_range("bytes 9-1/10"), None);
+    assert_eq!(parse_content_range("items 0-1/2"), None);
+}
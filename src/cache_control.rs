@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use crate::header::{is_ws, ValueParser};
+
+/// The directives of the `Cache-Control` headers of a response.
+/// https://tools.ietf.org/html/rfc7234#section-5.2
+///
+/// Directive names are compared ignoring case. A response without
+/// `Cache-Control` has no directives.
+///
+/// ```
+/// let s = "HTTP/1.1 200 OK\r\n\
+///     Cache-Control: public, max-age=3600, must-revalidate\r\n\
+///     \r\n";
+/// let resp = s.parse::<ureq::Response>().unwrap();
+/// let cc = resp.cache_control();
+///
+/// assert_eq!(cc.max_age().unwrap().as_secs(), 3600);
+/// assert!(cc.public());
+/// assert!(cc.must_revalidate());
+/// assert!(!cc.no_store());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    // Lowercase name and value of each directive, in order.
+    directives: Vec<(String, Option<String>)>,
+}
+
+impl CacheControl {
+    /// How long the response stays fresh, from `max-age`. None if it's
+    /// missing or not a number of seconds.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.value("max-age")?.parse().ok().map(Duration::from_secs)
+    }
+
+    /// Whether `no-cache` is set: the response must be revalidated before
+    /// it's reused.
+    pub fn no_cache(&self) -> bool {
+        self.has("no-cache")
+    }
+
+    /// Whether `no-store` is set: the response must not be stored at all.
+    pub fn no_store(&self) -> bool {
+        self.has("no-store")
+    }
+
+    /// Whether `private` is set: only a private cache may store the response.
+    pub fn private(&self) -> bool {
+        self.has("private")
+    }
+
+    /// Whether `public` is set: any cache may store the response.
+    pub fn public(&self) -> bool {
+        self.has("public")
+    }
+
+    /// Whether `must-revalidate` is set: once stale, the response must not be
+    /// used without revalidating it.
+    pub fn must_revalidate(&self) -> bool {
+        self.has("must-revalidate")
+    }
+
+    /// The value of the named directive. Quoted values are returned without
+    /// quotes. None if the directive is missing or has no value.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.directives
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    /// Whether the named directive is present, with or without a value.
+    pub fn has(&self, name: &str) -> bool {
+        self.directives
+            .iter()
+            .any(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+
+    /// All directives in the order they appear, with lowercase names.
+    pub fn directives(&self) -> &[(String, Option<String>)] {
+        &self.directives
+    }
+}
+
+/// Parse the values of all `Cache-Control` headers. Parts that can't be
+/// parsed are skipped.
+pub(crate) fn parse_cache_control<'a>(values: impl IntoIterator<Item = &'a str>) -> CacheControl {
+    let mut directives = vec![];
+    for value in values {
+        let mut p = ValueParser::new(value);
+        loop {
+            p.skip(|c| c == b',' || is_ws(c));
+            if p.done() {
+                break;
+            }
+            match p.directive(b',') {
+                Some((name, value)) => directives.push((name.to_ascii_lowercase(), value)),
+                // Not a directive; skip ahead to the next one.
+                None => p.skip(|c| c != b','),
+            }
+        }
+    }
+    CacheControl { directives }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directives() {
+        let cc = parse_cache_control(vec![
+            "private, Max-Age=60, no-cache=\"Set-Cookie, Date\"",
+            "must-revalidate",
+        ]);
+        assert_eq!(cc.max_age(), Some(Duration::from_secs(60)));
+        assert!(cc.private());
+        assert!(cc.no_cache());
+        assert_eq!(cc.value("no-cache"), Some("Set-Cookie, Date"));
+        assert!(cc.must_revalidate());
+        assert!(!cc.public());
+        assert!(!cc.no_store());
+        assert_eq!(cc.directives().len(), 4);
+        assert_eq!(cc.directives()[0], ("private".to_string(), None));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(parse_cache_control(vec![]), CacheControl::default());
+        assert_eq!(parse_cache_control(vec![" , ,"]), CacheControl::default());
+        let cc = parse_cache_control(vec!["max-age=soon, \"junk\" x, no-store"]);
+        assert_eq!(cc.max_age(), None);
+        assert!(cc.has("max-age"));
+        assert!(cc.no_store());
+        assert_eq!(cc.directives().len(), 2);
+    }
+}
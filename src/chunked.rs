@@ -0,0 +1,182 @@
+use std::io::{self, BufRead, Read};
+use std::sync::Arc;
+
+use once_cell::sync::OnceCell;
+
+use crate::header::Header;
+use crate::stream::Stream;
+
+// Longest chunk size or trailer line we accept.
+const MAX_LINE_LENGTH: u64 = 8 * 1024;
+
+/// Trailer fields of a chunked body, filled in once the body is read.
+pub(crate) type Trailers = Arc<OnceCell<Vec<Header>>>;
+
+/// Decoder for the chunked transfer coding.
+/// https://tools.ietf.org/html/rfc7230#section-4.1
+///
+/// Chunk extensions are ignored. The trailer section after the last chunk is
+/// read, and the fields named in `keep` are stored in `trailers`.
+pub(crate) struct ChunkDecoder<R> {
+    reader: R,
+    // Bytes left of the current chunk, or None between chunks.
+    remaining: Option<usize>,
+    done: bool,
+    keep: Vec<String>,
+    trailers: Trailers,
+}
+
+impl<R: BufRead> ChunkDecoder<R> {
+    pub fn new(reader: R, keep: Vec<String>, trailers: Trailers) -> Self {
+        ChunkDecoder {
+            reader,
+            remaining: None,
+            done: false,
+            keep,
+            trailers,
+        }
+    }
+
+    // chunk = chunk-size [ chunk-ext ] CRLF chunk-data CRLF
+    fn read_chunk_size(&mut self) -> io::Result<usize> {
+        let line = self.read_line()?;
+        let size = line.split(&[';', ' ', '\t'][..]).next().unwrap_or("");
+        usize::from_str_radix(size, 16).map_err(|_| bad_chunk("invalid chunk size"))
+    }
+
+    // trailer-part = *( header-field CRLF ) CRLF
+    fn read_trailers(&mut self) -> io::Result<()> {
+        let mut kept = vec![];
+        loop {
+            let line = self.read_line()?;
+            if line.is_empty() {
+                break;
+            }
+            if let Ok(header) = line.parse::<Header>() {
+                if self.keep.iter().any(|name| header.is_name(name)) {
+                    kept.push(header);
+                }
+            }
+        }
+        self.trailers.set(kept).ok();
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = vec![];
+        (&mut self.reader)
+            .take(MAX_LINE_LENGTH)
+            .read_until(b'\n', &mut line)?;
+        if !line.ends_with(b"\r\n") {
+            return Err(bad_chunk("chunked body ended early"));
+        }
+        line.truncate(line.len() - 2);
+        String::from_utf8(line).map_err(|_| bad_chunk("chunk line not UTF-8"))
+    }
+}
+
+impl<R: BufRead> Read for ChunkDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let remaining = match self.remaining {
+            Some(remaining) => remaining,
+            None => {
+                let size = self.read_chunk_size()?;
+                if size == 0 {
+                    self.read_trailers()?;
+                    self.done = true;
+                    return Ok(0);
+                }
+                size
+            }
+        };
+
+        let max = buf.len().min(remaining);
+        let n = self.reader.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(bad_chunk("chunked body ended early"));
+        }
+        if n == remaining {
+            if !self.read_line()?.is_empty() {
+                return Err(bad_chunk("chunk longer than its size"));
+            }
+            self.remaining = None;
+        } else {
+            self.remaining = Some(remaining - n);
+        }
+        Ok(n)
+    }
+}
+
+impl<R> From<ChunkDecoder<R>> for Stream
+where
+    Stream: From<R>,
+{
+    fn from(chunk_decoder: ChunkDecoder<R>) -> Stream {
+        chunk_decoder.reader.into()
+    }
+}
+
+fn bad_chunk(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn decode(s: &str) -> io::Result<String> {
+        let mut out = String::new();
+        let mut decoder = ChunkDecoder::new(Cursor::new(s), vec![], Trailers::default());
+        decoder.read_to_string(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn chunks() {
+        assert_eq!(
+            decode("3\r\nhel\r\nb\r\nlo world!!!\r\n0\r\n\r\n").unwrap(),
+            "hello world!!!"
+        );
+        assert_eq!(decode("0\r\n\r\n").unwrap(), "");
+        assert_eq!(
+            decode("A;ext=1\r\n0123456789\r\n00\r\n\r\n").unwrap(),
+            "0123456789"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(decode("m\r\n\r\n").is_err());
+        assert!(decode("3\rhel\r\n0\r\n\r\n").is_err());
+        assert!(decode("3\r\nhello\r\n0\r\n\r\n").is_err());
+        // Truncated bodies are errors, not a short read.
+        assert!(decode("5\r\nhel").is_err());
+        assert!(decode("3\r\nhel\r\n").is_err());
+        assert!(decode("3\r\nhel\r\n0\r\n").is_err());
+    }
+
+    #[test]
+    fn trailers() {
+        let s = "3\r\nhel\r\n0\r\nGrpc-Status: 0\r\nX-Other: 1\r\n\r\nnext";
+        let trailers = Trailers::default();
+        let keep = vec!["grpc-status".to_string()];
+        let mut reader = Cursor::new(s);
+        let mut decoder = ChunkDecoder::new(&mut reader, keep, trailers.clone());
+        let mut out = String::new();
+        assert!(trailers.get().is_none());
+        decoder.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "hel");
+        let trailers = trailers.get().unwrap();
+        assert_eq!(trailers.len(), 1);
+        assert_eq!(trailers[0].value(), "0");
+
+        // The stream is left right after the body.
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "next");
+    }
+}
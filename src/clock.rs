@@ -0,0 +1,62 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A source of the current time, used for timeouts.
+///
+/// By default this is [Instant::now]. Tests can provide a clock that is
+/// advanced by hand, to run into timeouts without actually waiting.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Debug)]
+pub(crate) struct StdClock;
+
+impl Clock for StdClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> Instant,
+    F: Send + Sync,
+{
+    fn now(&self) -> Instant {
+        self()
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ArcClock(Arc<dyn Clock>);
+
+impl<C> From<C> for ArcClock
+where
+    C: Clock + 'static,
+{
+    fn from(c: C) -> Self {
+        Self(Arc::new(c))
+    }
+}
+
+impl Default for ArcClock {
+    fn default() -> Self {
+        StdClock.into()
+    }
+}
+
+impl fmt::Debug for ArcClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ArcClock(...)")
+    }
+}
+
+impl std::ops::Deref for ArcClock {
+    type Target = dyn Clock;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
@@ -1,4 +1,5 @@
 use cookie_store::CookieStore;
+use log::debug;
 use std::ops::Deref;
 use std::sync::{RwLock, RwLockReadGuard};
 use url::Url;
@@ -45,4 +46,38 @@ impl CookieTin {
         let mut store = self.inner.write().unwrap();
         store.store_response_cookies(cookies, url)
     }
+
+    /// Like `store_response_cookies`, but once the store holds `max` unexpired
+    /// cookies only updates to the ones in it are stored.
+    pub(crate) fn store_response_cookies_max<I>(&self, cookies: I, url: &Url, max: usize)
+    where
+        I: Iterator<Item = cookie::Cookie<'static>>,
+    {
+        let mut store = self.inner.write().unwrap();
+        let mut count = store.iter_unexpired().count();
+        for raw in cookies {
+            let cookie = match cookie_store::Cookie::try_from_raw_cookie(&raw, url) {
+                Ok(cookie) => cookie,
+                Err(e) => {
+                    debug!("unable to store Set-Cookie: {:?}", e);
+                    continue;
+                }
+            };
+            let domain = String::from(&cookie.domain);
+            let path = String::from(&cookie.path);
+            let stored = store.contains(&domain, &path, cookie.name());
+            if count >= max && !stored {
+                debug!("cookie store full, discarding Set-Cookie '{}'", raw.name());
+                continue;
+            }
+            // An expired cookie removes the one it replaces.
+            let expired = cookie.is_expired();
+            match store.insert(cookie.into_owned(), url) {
+                Ok(_) if expired && stored => count -= 1,
+                Ok(_) if !expired && !stored => count += 1,
+                Ok(_) => (),
+                Err(e) => debug!("unable to store Set-Cookie: {:?}", e),
+            }
+        }
+    }
 }
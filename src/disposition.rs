@@ -0,0 +1,164 @@
+use crate::header::{is_tchar, is_ws, ValueParser};
+
+/// The value of a `Content-Disposition` header, which tells whether a body is
+/// meant to be shown or downloaded, and under what file name.
+/// https://tools.ietf.org/html/rfc6266
+///
+/// ```
+/// let s = "HTTP/1.1 200 OK\r\n\
+///     Content-Disposition: attachment; filename=\"report.pdf\"\r\n\
+///     \r\n";
+/// let resp = s.parse::<ureq::Response>().unwrap();
+/// let disposition = resp.content_disposition().unwrap();
+///
+/// assert!(disposition.is_attachment());
+/// assert_eq!(disposition.filename(), Some("report.pdf"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentDisposition {
+    disposition: String,
+    params: Vec<(String, String)>,
+}
+
+impl ContentDisposition {
+    /// The disposition type in lowercase, usually `inline` or `attachment`.
+    pub fn disposition(&self) -> &str {
+        &self.disposition
+    }
+
+    /// True if the body should be downloaded rather than shown.
+    pub fn is_attachment(&self) -> bool {
+        self.disposition == "attachment"
+    }
+
+    /// The value of the named parameter, if any. Names are compared ignoring
+    /// case. Extended parameters like `filename*` keep their `*`, and their
+    /// values are returned decoded.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// All parameters in the order they appear in the header.
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// The suggested file name: `filename*` if present, otherwise `filename`.
+    ///
+    /// This is exactly what the server sent. Strip any directory parts, like
+    /// `../`, before using it as a path.
+    pub fn filename(&self) -> Option<&str> {
+        self.param("filename*").or_else(|| self.param("filename"))
+    }
+}
+
+/// Parse the value of a `Content-Disposition` header. Parameters that can't
+/// be parsed or decoded are skipped.
+pub(crate) fn parse_content_disposition(value: &str) -> Option<ContentDisposition> {
+    let mut p = ValueParser::new(value);
+    p.skip(is_ws);
+    let disposition = p.take(is_tchar).to_ascii_lowercase();
+    if disposition.is_empty() {
+        return None;
+    }
+    let params = p
+        .params()
+        .into_iter()
+        .filter_map(|(name, value)| {
+            if name.ends_with('*') {
+                let value = decode_ext_value(&value)?;
+                Some((name, value))
+            } else {
+                Some((name, value))
+            }
+        })
+        .collect();
+    Some(ContentDisposition {
+        disposition,
+        params,
+    })
+}
+
+// https://tools.ietf.org/html/rfc5987#section-3.2.1
+// ext-value     = charset  "'" [ language ] "'" value-chars
+// value-chars   = *( pct-encoded / attr-char )
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let bytes = percent_decode(parts.next()?)?;
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+fn percent_decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = vec![];
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            out.push(b);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_filename() {
+        let d = parse_content_disposition("attachment; filename=report.pdf").unwrap();
+        assert_eq!(d.disposition(), "attachment");
+        assert!(d.is_attachment());
+        assert_eq!(d.filename(), Some("report.pdf"));
+    }
+
+    #[test]
+    fn quoted_filename() {
+        let d = parse_content_disposition(r#"Attachment; filename="my \"best\" report; v2.pdf""#)
+            .unwrap();
+        assert!(d.is_attachment());
+        assert_eq!(d.filename(), Some(r#"my "best" report; v2.pdf"#));
+    }
+
+    #[test]
+    fn extended_filename() {
+        let d = parse_content_disposition(
+            "attachment; filename=\"EURO rates.txt\"; filename*=UTF-8''%e2%82%ac%20rates.txt",
+        )
+        .unwrap();
+        assert_eq!(d.param("filename"), Some("EURO rates.txt"));
+        assert_eq!(d.param("FILENAME*"), Some("€ rates.txt"));
+        assert_eq!(d.filename(), Some("€ rates.txt"));
+
+        let d = parse_content_disposition("attachment; filename*=iso-8859-1'en'%A3%20rates.txt")
+            .unwrap();
+        assert_eq!(d.filename(), Some("£ rates.txt"));
+    }
+
+    #[test]
+    fn inline_and_garbage() {
+        let d = parse_content_disposition("inline").unwrap();
+        assert_eq!(d.disposition(), "inline");
+        assert!(!d.is_attachment());
+        assert_eq!(d.filename(), None);
+
+        assert!(parse_content_disposition("").is_none());
+        let d =
+            parse_content_disposition("attachment; junk; filename*=UTF-8''%zz; size=3").unwrap();
+        assert_eq!(d.params(), &[("size".to_string(), "3".to_string())]);
+    }
+}
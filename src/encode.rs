@@ -0,0 +1,80 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+// https://tools.ietf.org/html/rfc3986#section-2.3
+// unreserved = ALPHA / DIGIT / "-" / "." / "_" / "~"
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+// https://tools.ietf.org/html/rfc3986#section-3.4
+// "/" and "?" may appear as data in a query, like ":" and "@" anywhere.
+// Everything with a meaning in `name=value&...` pairs is encoded.
+const QUERY_COMPONENT: &AsciiSet = &PATH_SEGMENT
+    .remove(b'/')
+    .remove(b'?')
+    .remove(b':')
+    .remove(b'@');
+
+/// Percent-encode `s` for use as one path segment of a URL.
+///
+/// Everything but letters, digits and `-._~` is encoded, including `/`, so
+/// user input can't add path segments or start the query.
+///
+/// ```
+/// let name = "reports/2021 €.pdf";
+/// let url = format!("http://example.com/files/{}", ureq::encode(name));
+///
+/// assert_eq!(url, "http://example.com/files/reports%2F2021%20%E2%82%AC.pdf");
+/// ```
+pub fn encode(s: &str) -> String {
+    utf8_percent_encode(s, PATH_SEGMENT).to_string()
+}
+
+/// Percent-encode `s` for use as a name or value in a URL query string.
+///
+/// Spaces become `%20`, and `&`, `=`, `+` and `#` are encoded so user input
+/// can't add parameters. `/`, `?`, `:` and `@` are left as they are, since
+/// they have no special meaning in a query.
+///
+/// ```
+/// let q = "fish & chips";
+/// let url = format!("http://example.com/search?q={}", ureq::encode_query(q));
+///
+/// assert_eq!(url, "http://example.com/search?q=fish%20%26%20chips");
+/// ```
+pub fn encode_query(s: &str) -> String {
+    utf8_percent_encode(s, QUERY_COMPONENT).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaces() {
+        assert_eq!(encode("a b"), "a%20b");
+        assert_eq!(encode_query("a b"), "a%20b");
+    }
+
+    #[test]
+    fn unicode() {
+        assert_eq!(encode("æøå"), "%C3%A6%C3%B8%C3%A5");
+        assert_eq!(encode_query("€"), "%E2%82%AC");
+    }
+
+    #[test]
+    fn reserved_in_path() {
+        assert_eq!(encode("a/b?c#d"), "a%2Fb%3Fc%23d");
+        assert_eq!(encode("50%"), "50%25");
+        assert_eq!(encode("-._~"), "-._~");
+    }
+
+    #[test]
+    fn reserved_in_query() {
+        assert_eq!(encode_query("a/b?c:d@e"), "a/b?c:d@e");
+        assert_eq!(encode_query("a&b=c+d#e"), "a%26b%3Dc%2Bd%23e");
+        assert_eq!(encode_query("50%"), "50%25");
+    }
+}
@@ -218,10 +218,16 @@ pub enum ErrorKind {
     InvalidUrl,
     /// The url scheme could not be understood.
     UnknownScheme,
+    /// The request method is not a valid token, like `GET` or `PROPFIND`.
+    InvalidMethod,
     /// DNS lookup failed.
     Dns,
     /// Connection to server failed.
     ConnectionFailed,
+    /// The TLS handshake failed, for instance because the server's
+    /// certificate isn't trusted or there's no protocol version both sides
+    /// support.
+    Tls,
     /// Too many redirects.
     TooManyRedirects,
     /// A status line we don't understand `HTTP/1.1 200 OK`.
@@ -236,6 +242,9 @@ pub enum ErrorKind {
     ProxyConnect,
     /// Incorrect credentials for proxy
     ProxyUnauthorized,
+    /// The agent already sent as many requests as its
+    /// [`request_quota`](crate::AgentBuilder::request_quota) allows.
+    QuotaExceeded,
     /// HTTP status code indicating an error (e.g. 4xx, 5xx)
     /// Read the inner response body for details and to return
     /// the connection to the pool.
@@ -275,8 +284,10 @@ impl fmt::Display for ErrorKind {
         match self {
             ErrorKind::InvalidUrl => write!(f, "Bad URL"),
             ErrorKind::UnknownScheme => write!(f, "Unknown Scheme"),
+            ErrorKind::InvalidMethod => write!(f, "Invalid Method"),
             ErrorKind::Dns => write!(f, "Dns Failed"),
             ErrorKind::ConnectionFailed => write!(f, "Connection Failed"),
+            ErrorKind::Tls => write!(f, "TLS Handshake Failed"),
             ErrorKind::TooManyRedirects => write!(f, "Too Many Redirects"),
             ErrorKind::BadStatus => write!(f, "Bad Status"),
             ErrorKind::BadHeader => write!(f, "Bad Header"),
@@ -284,6 +295,7 @@ impl fmt::Display for ErrorKind {
             ErrorKind::InvalidProxyUrl => write!(f, "Malformed proxy"),
             ErrorKind::ProxyConnect => write!(f, "Proxy failed to connect"),
             ErrorKind::ProxyUnauthorized => write!(f, "Provided proxy credentials are incorrect"),
+            ErrorKind::QuotaExceeded => write!(f, "Request quota exceeded"),
             ErrorKind::HTTP => write!(f, "HTTP status error"),
         }
     }
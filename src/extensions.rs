@@ -0,0 +1,86 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A map of values keyed by their type, carried along with a request and
+/// its response.
+///
+/// Middleware and instrumentation can use this to attach data, like trace
+/// ids or timings, to a [Request](crate::Request) and read it back from the
+/// resulting [Response](crate::Response). At most one value of each type is
+/// stored.
+///
+/// Values are reference counted, so cloning a request doesn't clone them.
+///
+/// ```
+/// # fn main() -> Result<(), ureq::Error> {
+/// # ureq::is_test(true);
+/// #[derive(Debug, PartialEq)]
+/// struct TraceId(u64);
+///
+/// let mut req = ureq::get("http://example.com/");
+/// req.extensions_mut().insert(TraceId(42));
+///
+/// let resp = req.call()?;
+/// assert_eq!(resp.extensions().get::<TraceId>(), Some(&TraceId(42)));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Extensions[{} values]", self.map.len())
+    }
+}
+
+impl Extensions {
+    /// Create an empty map.
+    pub fn new() -> Self {
+        Extensions::default()
+    }
+
+    /// Store a value, replacing any previous value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.map.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// The stored value of type `T`, if any.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|v| v.downcast_ref::<T>())
+    }
+
+    /// Remove the value of type `T`. Returns true if there was one.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> bool {
+        self.map.remove(&TypeId::of::<T>()).is_some()
+    }
+
+    /// True if no values are stored.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+#[test]
+fn insert_get_remove() {
+    let mut ext = Extensions::new();
+    assert!(ext.is_empty());
+    ext.insert(5_u32);
+    ext.insert("hello");
+    ext.insert(7_u32);
+    assert_eq!(ext.get::<u32>(), Some(&7));
+    assert_eq!(ext.get::<&str>(), Some(&"hello"));
+    assert_eq!(ext.get::<u64>(), None);
+
+    let clone = ext.clone();
+    assert!(ext.remove::<u32>());
+    assert!(!ext.remove::<u32>());
+    assert_eq!(ext.get::<u32>(), None);
+    assert_eq!(clone.get::<u32>(), Some(&7));
+}
@@ -0,0 +1,135 @@
+use crate::header::{is_tchar, is_ws, ValueParser};
+
+/// One element of a `Forwarded` header, added by one proxy.
+/// https://tools.ietf.org/html/rfc7239#section-4
+///
+/// Each proxy on the way appends an element with what it knows about the
+/// request: who it came from (`for`), which interface of the proxy got it
+/// (`by`), and the `Host` and protocol it was originally sent with.
+///
+/// ```
+/// let s = "HTTP/1.1 200 OK\r\n\
+///     Forwarded: for=192.0.2.60;proto=https;by=203.0.113.43, for=\"[2001:db8::17]\"\r\n\
+///     \r\n";
+/// let resp = s.parse::<ureq::Response>().unwrap();
+/// let hops = resp.forwarded();
+///
+/// assert_eq!(hops[0].for_node(), Some("192.0.2.60"));
+/// assert_eq!(hops[0].proto(), Some("https"));
+/// assert_eq!(hops[1].for_node(), Some("[2001:db8::17]"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardedEntry {
+    params: Vec<(String, String)>,
+}
+
+impl ForwardedEntry {
+    /// The client that made the request to the proxy, from `for=`.
+    pub fn for_node(&self) -> Option<&str> {
+        self.param("for")
+    }
+
+    /// The interface the proxy received the request on, from `by=`.
+    pub fn by_node(&self) -> Option<&str> {
+        self.param("by")
+    }
+
+    /// The `Host` header of the request the proxy received, from `host=`.
+    pub fn host(&self) -> Option<&str> {
+        self.param("host")
+    }
+
+    /// The protocol of the request the proxy received, like `http` or
+    /// `https`, from `proto=`.
+    pub fn proto(&self) -> Option<&str> {
+        self.param("proto")
+    }
+
+    /// The value of the named parameter, if any. Names are compared ignoring
+    /// case, and quoted values are returned without quotes.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// All parameters in the order they appear in the element.
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+}
+
+/// Parse the value of a `Forwarded` header into its elements. Pairs that
+/// can't be parsed are skipped, as are elements left without any.
+pub(crate) fn parse_forwarded(value: &str) -> Vec<ForwardedEntry> {
+    let mut p = ValueParser::new(value);
+    let mut entries = vec![];
+    let mut params = vec![];
+    loop {
+        p.skip(|c| c == b';' || is_ws(c));
+        match p.peek() {
+            None | Some(b',') => {
+                if !params.is_empty() {
+                    entries.push(ForwardedEntry {
+                        params: std::mem::take(&mut params),
+                    });
+                }
+                if p.done() {
+                    break;
+                }
+                p.pos += 1;
+                continue;
+            }
+            _ => (),
+        }
+
+        // forwarded-pair = token "=" value
+        let name = p.take(is_tchar);
+        p.skip(is_ws);
+        if name.is_empty() || p.peek() != Some(b'=') {
+            // Not a pair; skip ahead to the next one.
+            p.skip(|c| c != b';' && c != b',');
+            continue;
+        }
+        p.pos += 1;
+        p.skip(is_ws);
+        let value = if p.peek() == Some(b'"') {
+            p.quoted_string()
+        } else {
+            p.take(is_tchar)
+        };
+        params.push((name, value));
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elements() {
+        let f = parse_forwarded(
+            r#"for=192.0.2.43;proto=http, For="[2001:db8:cafe::17]:4711";by=unknown;host="example.com""#,
+        );
+        assert_eq!(f.len(), 2);
+        assert_eq!(f[0].for_node(), Some("192.0.2.43"));
+        assert_eq!(f[0].proto(), Some("http"));
+        assert_eq!(f[0].by_node(), None);
+        assert_eq!(f[1].for_node(), Some("[2001:db8:cafe::17]:4711"));
+        assert_eq!(f[1].by_node(), Some("unknown"));
+        assert_eq!(f[1].host(), Some("example.com"));
+        assert_eq!(f[1].params().len(), 3);
+    }
+
+    #[test]
+    fn garbage() {
+        assert!(parse_forwarded("").is_empty());
+        assert!(parse_forwarded(" , ;,").is_empty());
+        let f = parse_forwarded(r#"oops, for=_hidden;junk;proto=https,"#);
+        assert_eq!(f.len(), 1);
+        assert_eq!(f[0].for_node(), Some("_hidden"));
+        assert_eq!(f[0].proto(), Some("https"));
+    }
+}
@@ -84,12 +84,12 @@ pub fn add_header(headers: &mut Vec<Header>, header: Header) {
 // token = 1*tchar
 // tchar = "!" / "#" / "$" / "%" / "&" / "'" / "*" / "+" / "-" / "." /
 // "^" / "_" / "`" / "|" / "~" / DIGIT / ALPHA
-fn valid_name(name: &str) -> bool {
+pub(crate) fn valid_name(name: &str) -> bool {
     name.len() > 0 && name.bytes().all(is_tchar)
 }
 
 #[inline]
-fn is_tchar(b: u8) -> bool {
+pub(crate) fn is_tchar(b: u8) -> bool {
     match b {
         b'!' | b'#' | b'$' | b'%' | b'&' => true,
         b'\'' | b'*' | b'+' | b'-' | b'.' => true,
@@ -125,6 +125,121 @@ fn is_field_vchar_or_obs_fold(b: u8) -> bool {
     }
 }
 
+/// A cursor over a header value, for headers with structured values like
+/// `WWW-Authenticate` and `Content-Disposition`.
+pub(crate) struct ValueParser<'a> {
+    s: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> ValueParser<'a> {
+    pub fn new(value: &'a str) -> Self {
+        ValueParser {
+            s: value.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    pub fn done(&self) -> bool {
+        self.pos >= self.s.len()
+    }
+
+    pub fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).cloned()
+    }
+
+    pub fn skip(&mut self, f: impl Fn(u8) -> bool) {
+        while self.peek().map(&f).unwrap_or(false) {
+            self.pos += 1;
+        }
+    }
+
+    pub fn take(&mut self, f: impl Fn(u8) -> bool) -> String {
+        let start = self.pos;
+        self.skip(f);
+        String::from_utf8_lossy(&self.s[start..self.pos]).into_owned()
+    }
+
+    // https://tools.ietf.org/html/rfc7230#section-3.2.6
+    // quoted-string  = DQUOTE *( qdtext / quoted-pair ) DQUOTE
+    // quoted-pair    = "\" ( HTAB / SP / VCHAR / obs-text )
+    pub fn quoted_string(&mut self) -> String {
+        let mut out = vec![];
+        self.pos += 1;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    if let Some(c) = self.peek() {
+                        out.push(c);
+                        self.pos += 1;
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+
+    /// Parse one `name [ "=" ( token / quoted-string ) ]` item that ends at
+    /// `sep` or at the end of the value, like a `Cache-Control` directive.
+    /// Quoted values are returned without quotes. None if the item is
+    /// malformed, in which case the parser is left somewhere inside it.
+    pub fn directive(&mut self, sep: u8) -> Option<(String, Option<String>)> {
+        let name = self.take(is_tchar);
+        self.skip(is_ws);
+        let value = if self.peek() == Some(b'=') {
+            self.pos += 1;
+            self.skip(is_ws);
+            if self.peek() == Some(b'"') {
+                Some(self.quoted_string())
+            } else {
+                Some(self.take(|c| c != sep && !is_ws(c)))
+            }
+        } else {
+            None
+        };
+        self.skip(is_ws);
+        if name.is_empty() || !(self.done() || self.peek() == Some(sep)) {
+            return None;
+        }
+        Some((name, value))
+    }
+
+    /// Parse the rest of the value as `*( ";" name "=" value )` parameters,
+    /// like in a `Content-Type`. Parameters that can't be parsed are skipped.
+    pub fn params(&mut self) -> Vec<(String, String)> {
+        let mut params = vec![];
+        loop {
+            self.skip(|c| c == b';' || is_ws(c));
+            if self.done() {
+                return params;
+            }
+            match self.directive(b';') {
+                Some((name, Some(value))) => params.push((name, value)),
+                // Not a param; skip ahead to the next one.
+                _ => self.skip(|c| c != b';'),
+            }
+        }
+    }
+}
+
+/// The value of the named parameter of a media type like
+/// `multipart/byteranges; boundary=abc`. Names are compared ignoring case.
+pub(crate) fn media_type_param(media_type: &str, name: &str) -> Option<String> {
+    let mut p = ValueParser::new(media_type);
+    p.skip(|c| c != b';');
+    p.params()
+        .into_iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v)
+}
+
+pub(crate) fn is_ws(c: u8) -> bool {
+    c == b' ' || c == b'\t'
+}
+
 impl FromStr for Header {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -214,3 +329,23 @@ fn name_and_value() {
     assert!(header.is_name("x-forwarded-for"));
     assert!(header.is_name("X-FORWARDED-FOR"));
 }
+
+#[test]
+fn value_parser_directive() {
+    let mut p = ValueParser::new("Max-Age = 60, no-cache=\"a, b\", public");
+    assert_eq!(
+        p.directive(b','),
+        Some(("Max-Age".into(), Some("60".into())))
+    );
+    p.skip(|c| c == b',' || is_ws(c));
+    assert_eq!(
+        p.directive(b','),
+        Some(("no-cache".into(), Some("a, b".into())))
+    );
+    p.skip(|c| c == b',' || is_ws(c));
+    assert_eq!(p.directive(b','), Some(("public".into(), None)));
+    assert!(p.done());
+
+    assert_eq!(ValueParser::new("a=b c").directive(b','), None);
+    assert_eq!(ValueParser::new("=b").directive(b','), None);
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::header::{is_ws, ValueParser};
+
+/// A parsed `Strict-Transport-Security` header.
+/// https://tools.ietf.org/html/rfc6797#section-6.1
+///
+/// A server sends this over https to say that it should only ever be
+/// reached over https, for the next `max-age` seconds. With
+/// [`AgentBuilder::hsts`](crate::AgentBuilder::hsts) enabled, the agent
+/// remembers it and upgrades later `http` requests to that host.
+///
+/// ```
+/// let s = "HTTP/1.1 200 OK\r\n\
+///     Strict-Transport-Security: max-age=31536000; includeSubDomains\r\n\
+///     \r\n";
+/// let resp = s.parse::<ureq::Response>().unwrap();
+/// let hsts = resp.hsts().unwrap();
+///
+/// assert_eq!(hsts.max_age().as_secs(), 31536000);
+/// assert!(hsts.include_subdomains());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hsts {
+    max_age: Duration,
+    include_subdomains: bool,
+}
+
+impl Hsts {
+    /// How long the host should only be reached over https. Zero means the
+    /// host asks to be forgotten.
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Whether the policy also covers all subdomains of the host.
+    pub fn include_subdomains(&self) -> bool {
+        self.include_subdomains
+    }
+}
+
+/// Parse a `Strict-Transport-Security` header value. Unknown directives are
+/// ignored, but the header is invalid without a `max-age` or with a
+/// directive given twice.
+pub(crate) fn parse_hsts(value: &str) -> Option<Hsts> {
+    let mut p = ValueParser::new(value);
+    let mut seen: Vec<String> = vec![];
+    let mut max_age = None;
+    let mut include_subdomains = false;
+    loop {
+        p.skip(|c| c == b';' || is_ws(c));
+        if p.done() {
+            break;
+        }
+        let (name, value) = p.directive(b';')?;
+        let name = name.to_ascii_lowercase();
+        if seen.contains(&name) {
+            return None;
+        }
+        match name.as_str() {
+            "max-age" => max_age = Some(value?.parse().ok()?),
+            "includesubdomains" => include_subdomains = true,
+            _ => {}
+        }
+        seen.push(name);
+    }
+    Some(Hsts {
+        max_age: Duration::from_secs(max_age?),
+        include_subdomains,
+    })
+}
+
+/// Hosts known to require https, from `Strict-Transport-Security` headers.
+#[derive(Debug, Default)]
+pub(crate) struct HstsStore {
+    // Lowercase host name to when its policy expires and whether it
+    // covers subdomains.
+    hosts: Mutex<HashMap<String, (Instant, bool)>>,
+}
+
+impl HstsStore {
+    /// Record a policy for `host` seen at `now`. A max-age of zero removes it.
+    pub(crate) fn record(&self, host: &str, hsts: Hsts, now: Instant) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let host = host.to_ascii_lowercase();
+        match now.checked_add(hsts.max_age) {
+            Some(expires) if hsts.max_age > Duration::from_secs(0) => {
+                hosts.insert(host, (expires, hsts.include_subdomains));
+            }
+            _ => {
+                hosts.remove(&host);
+            }
+        }
+    }
+
+    /// Whether requests to `host` must use https at `now`, either from a
+    /// policy for the host itself or one of its parents that includes
+    /// subdomains.
+    pub(crate) fn requires_https(&self, host: &str, now: Instant) -> bool {
+        let hosts = self.hosts.lock().unwrap();
+        let host = host.to_ascii_lowercase();
+        let mut domain = host.as_str();
+        loop {
+            if let Some((expires, include_subdomains)) = hosts.get(domain) {
+                if now < *expires && (domain == host || *include_subdomains) {
+                    return true;
+                }
+            }
+            match domain.find('.') {
+                Some(i) => domain = &domain[i + 1..],
+                None => return false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let hsts = parse_hsts("max-age=31536000").unwrap();
+        assert_eq!(hsts.max_age(), Duration::from_secs(31536000));
+        assert!(!hsts.include_subdomains());
+
+        let hsts = parse_hsts("includeSubDomains; Max-Age=\"60\"; preload").unwrap();
+        assert_eq!(hsts.max_age(), Duration::from_secs(60));
+        assert!(hsts.include_subdomains());
+
+        assert_eq!(parse_hsts("max-age=0").unwrap().max_age().as_secs(), 0);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(parse_hsts(""), None);
+        assert_eq!(parse_hsts("includeSubDomains"), None);
+        assert_eq!(parse_hsts("max-age=soon"), None);
+        assert_eq!(parse_hsts("max-age"), None);
+        assert_eq!(parse_hsts("max-age=60; max-age=70"), None);
+        assert_eq!(parse_hsts("max-age=60 junk"), None);
+    }
+
+    #[test]
+    fn store_expires() {
+        let store = HstsStore::default();
+        let now = Instant::now();
+        let hsts = parse_hsts("max-age=60").unwrap();
+        store.record("Example.com", hsts, now);
+        assert!(store.requires_https("example.com", now));
+        assert!(store.requires_https("EXAMPLE.COM", now + Duration::from_secs(59)));
+        assert!(!store.requires_https("example.com", now + Duration::from_secs(60)));
+        assert!(!store.requires_https("other.com", now));
+        assert!(!store.requires_https("www.example.com", now));
+
+        store.record("example.com", parse_hsts("max-age=0").unwrap(), now);
+        assert!(!store.requires_https("example.com", now));
+    }
+
+    #[test]
+    fn store_subdomains() {
+        let store = HstsStore::default();
+        let now = Instant::now();
+        let hsts = parse_hsts("max-age=60; includeSubDomains").unwrap();
+        store.record("example.com", hsts, now);
+        assert!(store.requires_https("example.com", now));
+        assert!(store.requires_https("a.b.example.com", now));
+        assert!(!store.requires_https("notexample.com", now));
+        assert!(!store.requires_https("com", now));
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+
+type Inspector = Arc<dyn Fn(&InspectEvent) + Send + Sync>;
+
+static INSPECTOR: Lazy<RwLock<Option<Inspector>>> = Lazy::new(|| RwLock::new(None));
+
+/// What [inspect()] reports.
+///
+/// Requires feature `ureq = { version = "*", features = ["inspect"] }`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InspectEvent {
+    /// A request head and body were sent.
+    Request {
+        /// The request method, like `GET`.
+        method: String,
+        /// The URL the request was sent to.
+        url: String,
+    },
+    /// The head of a response was received.
+    Response {
+        /// The method of the request.
+        method: String,
+        /// The URL the request was sent to.
+        url: String,
+        /// The status of the response.
+        status: u16,
+        /// The time from starting to connect until the response head was read.
+        elapsed: Duration,
+    },
+}
+
+/// Observe all HTTP activity in the process.
+///
+/// `inspector` is called for every request sent and every response received,
+/// by any agent, from any thread, including each hop of a redirect and each
+/// retry. This lets tests assert on the requests made by code under test
+/// without wiring anything into its agents. Calling this again replaces the
+/// inspector.
+///
+/// **This changes global state**, and is meant for tests and debugging.
+/// The inspector runs on the thread making the request, so it should be
+/// quick.
+///
+/// Requires feature `ureq = { version = "*", features = ["inspect"] }`
+///
+/// ```
+/// # fn main() -> Result<(), ureq::Error> {
+/// # ureq::is_test(true);
+/// use std::sync::{Arc, Mutex};
+/// use ureq::InspectEvent;
+///
+/// let seen = Arc::new(Mutex::new(vec![]));
+/// let seen2 = seen.clone();
+/// ureq::inspect(move |event| {
+///     if let InspectEvent::Response { url, status, .. } = event {
+///         seen2.lock().unwrap().push((url.clone(), *status));
+///     }
+/// });
+///
+/// ureq::get("http://example.com/").call()?;
+/// assert!(seen.lock().unwrap().contains(&("http://example.com/".to_string(), 200)));
+/// # Ok(())
+/// # }
+/// ```
+pub fn inspect(inspector: impl Fn(&InspectEvent) + Send + Sync + 'static) {
+    *INSPECTOR.write().unwrap() = Some(Arc::new(inspector));
+}
+
+/// Report the event made by `event` to the inspector, if there is one.
+pub(crate) fn emit(event: impl FnOnce() -> InspectEvent) {
+    let inspector = INSPECTOR.read().unwrap().clone();
+    if let Some(inspector) = inspector {
+        inspector(&event());
+    }
+}
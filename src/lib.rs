@@ -119,6 +119,12 @@
 //! * `charset` enables interpreting the charset part of the Content-Type header
 //!    (e.g.  `Content-Type: text/plain; charset=iso-8859-1`). Without this, the
 //!    library defaults to Rust's built in `utf-8`.
+//! * `gzip` enables decoding bodies sent with `Transfer-Encoding` or
+//!   `Content-Encoding` `gzip` or `deflate`.
+//! * `checksum` enables [Response::into_reader_checksum()] to verify a SHA-256 of the body,
+//!   and [Response::verify_content_md5()] to check a `Content-MD5` header.
+//! * `inspect` enables [inspect()] to observe all requests and responses, for
+//!   tests and debugging.
 //!
 //! # Plain requests
 //!
@@ -219,9 +225,22 @@
 //!
 
 mod agent;
+mod altsvc;
+mod auth;
 mod body;
+mod byteranges;
+mod cache_control;
+mod chunked;
+mod clock;
+mod disposition;
+mod encode;
 mod error;
+mod extensions;
+mod forwarded;
 mod header;
+mod hsts;
+#[cfg(feature = "inspect")]
+mod inspect;
 mod pool;
 mod proxy;
 mod request;
@@ -229,6 +248,8 @@ mod resolve;
 mod response;
 mod stream;
 mod unit;
+#[cfg(feature = "tls")]
+mod verify;
 
 #[cfg(feature = "cookies")]
 mod cookies;
@@ -244,12 +265,33 @@ mod testserver;
 
 pub use crate::agent::Agent;
 pub use crate::agent::AgentBuilder;
+pub use crate::agent::ConnectionHandle;
+pub use crate::agent::HostConfig;
+pub use crate::agent::IoStats;
+pub use crate::altsvc::AltService;
+pub use crate::auth::AuthChallenge;
+pub use crate::body::{guess_content_type, Body, SizedSource};
+pub use crate::byteranges::ByteRangePart;
+pub use crate::cache_control::CacheControl;
+pub use crate::clock::Clock;
+pub use crate::disposition::ContentDisposition;
+pub use crate::encode::{encode, encode_query};
 pub use crate::error::{Error, ErrorKind, Transport};
+pub use crate::extensions::Extensions;
+pub use crate::forwarded::ForwardedEntry;
 pub use crate::header::Header;
+pub use crate::hsts::Hsts;
+#[cfg(feature = "inspect")]
+pub use crate::inspect::{inspect, InspectEvent};
 pub use crate::proxy::Proxy;
-pub use crate::request::Request;
+pub use crate::request::{HttpVersion, PreparedRequest, Request};
 pub use crate::resolve::Resolver;
-pub use crate::response::Response;
+pub use crate::response::{BodyReader, Progress, RateLimitHandle, Records, Response, ResponseHead};
+#[cfg(feature = "tls")]
+pub use crate::verify::CertificateInfo;
+
+#[cfg(feature = "checksum")]
+pub use crate::response::Checksum;
 
 // re-export
 #[cfg(feature = "cookies")]
@@ -259,6 +301,8 @@ pub use serde_json::{to_value as serde_to_value, Map as SerdeMap, Value as Serde
 
 use once_cell::sync::Lazy;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Creates an [AgentBuilder].
 pub fn builder() -> AgentBuilder {
@@ -282,16 +326,62 @@ pub fn is_test(is: bool) -> bool {
     return x;
 }
 
+// The overall timeout for agents made by agent(), and so for the top-level
+// request functions.
+static DEFAULT_TIMEOUT: Lazy<Mutex<Option<Duration>>> = Lazy::new(|| Mutex::new(None));
+
+/// Set the overall timeout for requests made with the top-level functions,
+/// like [get()] and [post()], and for agents created by [agent()].
+///
+/// Without it, those requests only have the default connect timeout, and can
+/// wait forever on a server that accepts the connection but never responds.
+/// See [`AgentBuilder::timeout`] for what the timeout covers.
+///
+/// **This changes global state**: it applies to the whole process, from every
+/// thread, for agents created after the call. Agents made with [builder()]
+/// or [`Agent::new`] are not affected, and libraries should prefer those to
+/// calling this.
+///
+/// ```
+/// # fn main() -> Result<(), ureq::Error> {
+/// # ureq::is_test(true);
+/// ureq::set_default_timeout(std::time::Duration::from_secs(30));
+///
+/// let resp = ureq::get("http://example.com/").call()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn set_default_timeout(timeout: Duration) {
+    *DEFAULT_TIMEOUT.lock().unwrap() = Some(timeout);
+}
+
 /// Agents are used to hold configuration and keep state between requests.
 pub fn agent() -> Agent {
     #[cfg(not(test))]
-    if is_test(false) {
-        return testserver::test_agent();
+    let agent = if is_test(false) {
+        testserver::test_agent()
     } else {
-        return AgentBuilder::new().build();
-    }
+        AgentBuilder::new().build()
+    };
     #[cfg(test)]
-    return testserver::test_agent();
+    let agent = testserver::test_agent();
+
+    let timeout = *DEFAULT_TIMEOUT.lock().unwrap();
+    with_default_timeout(agent, timeout)
+}
+
+// Apply the timeout from set_default_timeout() to a new agent.
+fn with_default_timeout(agent: Agent, timeout: Option<Duration>) -> Agent {
+    match timeout {
+        Some(timeout) => Agent {
+            config: Arc::new(agent::AgentConfig {
+                timeout: Some(timeout),
+                ..(*agent.config).clone()
+            }),
+            state: agent.state,
+        },
+        None => agent,
+    }
 }
 
 /// Make a request with the HTTP verb as a parameter.
@@ -340,6 +430,11 @@ pub fn get(path: &str) -> Request {
     request("GET", path)
 }
 
+/// Make a GET request using an already-parsed [Url].
+pub fn get_url(url: &Url) -> Request {
+    request_url("GET", url)
+}
+
 /// Make a HEAD request.
 pub fn head(path: &str) -> Request {
     request("HEAD", path)
@@ -360,6 +455,16 @@ pub fn delete(path: &str) -> Request {
     request("DELETE", path)
 }
 
+/// Make an OPTIONS request.
+pub fn options(path: &str) -> Request {
+    request("OPTIONS", path)
+}
+
+/// Make a TRACE request.
+pub fn trace(path: &str) -> Request {
+    request("TRACE", path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,7 +1,10 @@
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, VecDeque};
 use std::io::{self, Read};
-use std::sync::Mutex;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::stream::Stream;
 use crate::unit::Unit;
@@ -32,11 +35,15 @@ use url::Url;
 ///  - The length of `lru` is less than or equal to max_idle_connections.
 ///  - The length of recycle[K] is less than or equal to max_idle_connections_per_host.
 ///
+/// Each stream remembers when it was added. If `max_idle_time` is set, streams
+/// that have been idle for longer are dropped rather than handed out again.
+///
 /// *Internal API*
 pub(crate) struct ConnectionPool {
     inner: Mutex<Inner>,
     max_idle_connections: usize,
     max_idle_connections_per_host: usize,
+    max_idle_time: Option<Duration>,
 }
 
 struct Inner {
@@ -47,6 +54,8 @@ struct Inner {
     // recently used Streams are added to the back of the queue;
     // old streams are removed from the front.
     lru: VecDeque<PoolKey>,
+    // Once closed, the pool drops streams instead of keeping them.
+    closed: bool,
 }
 
 impl fmt::Debug for ConnectionPool {
@@ -54,6 +63,7 @@ impl fmt::Debug for ConnectionPool {
         f.debug_struct("ConnectionPool")
             .field("max_idle", &self.max_idle_connections)
             .field("max_idle_per_host", &self.max_idle_connections_per_host)
+            .field("max_idle_time", &self.max_idle_time)
             .field("connections", &self.inner.lock().unwrap().lru.len())
             .finish()
     }
@@ -76,14 +86,17 @@ impl ConnectionPool {
     pub(crate) fn new_with_limits(
         max_idle_connections: usize,
         max_idle_connections_per_host: usize,
+        max_idle_time: Option<Duration>,
     ) -> Self {
         ConnectionPool {
             inner: Mutex::new(Inner {
                 recycle: HashMap::new(),
                 lru: VecDeque::new(),
+                closed: false,
             }),
             max_idle_connections,
             max_idle_connections_per_host,
+            max_idle_time,
         }
     }
 
@@ -95,7 +108,24 @@ impl ConnectionPool {
     /// How the unit::connect tries to get a pooled connection.
     pub fn try_get_connection(&self, url: &Url, proxy: Option<Proxy>) -> Option<Stream> {
         let key = PoolKey::new(url, proxy);
-        self.remove(&key)
+        // Streams come out freshest first, so once one is too stale, so are
+        // all the remaining ones for this key.
+        while let Some(stream) = self.remove(&key) {
+            match self.max_idle_time {
+                Some(max) if stream.idle_time() > max => {
+                    debug!("dropping stale stream: {:?}", stream);
+                }
+                _ => return Some(stream),
+            }
+        }
+        None
+    }
+
+    /// Pool a connection that hasn't carried a request yet.
+    pub(crate) fn add_unused(&self, url: &Url, proxy: Option<Proxy>, stream: Stream) {
+        if stream.is_poolable() {
+            self.add(PoolKey::new(url, proxy), stream);
+        }
     }
 
     fn remove(&self, key: &PoolKey) -> Option<Stream> {
@@ -123,13 +153,18 @@ impl ConnectionPool {
         }
     }
 
-    fn add(&self, key: PoolKey, stream: Stream) {
+    fn add(&self, key: PoolKey, mut stream: Stream) {
         if self.noop() {
             return;
         }
+        stream.mark_idle();
         debug!("adding stream to pool: {:?} -> {:?}", key, stream);
 
         let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            debug!("pool closed, dropping stream: {:?}", stream);
+            return;
+        }
         match inner.recycle.entry(key.clone()) {
             Entry::Occupied(mut occupied_entry) => {
                 let streams = occupied_entry.get_mut();
@@ -158,6 +193,16 @@ impl ConnectionPool {
         }
     }
 
+    /// Drop all pooled streams, which closes them, and stop pooling new ones.
+    pub(crate) fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.closed = true;
+        inner.lru.clear();
+        let recycle = mem::take(&mut inner.recycle);
+        drop(inner);
+        debug!("closing pool with streams for {} hosts", recycle.len());
+    }
+
     /// Find the oldest stream in the pool. Remove its representation from lru,
     /// and the stream itself from `recycle`. Drops the stream, which closes it.
     fn remove_oldest(&self) {
@@ -230,7 +275,7 @@ fn pool_connections_limit() {
     // Test inserting connections with different keys into the pool,
     // filling and draining it. The pool should evict earlier connections
     // when the connection limit is reached.
-    let pool = ConnectionPool::new_with_limits(10, 1);
+    let pool = ConnectionPool::new_with_limits(10, 1, None);
     let hostnames = (0..pool.max_idle_connections * 2).map(|i| format!("{}.example", i));
     let poolkeys = hostnames.map(|hostname| PoolKey {
         scheme: "https".to_string(),
@@ -255,7 +300,7 @@ fn pool_per_host_connections_limit() {
     // Test inserting connections with the same key into the pool,
     // filling and draining it. The pool should evict earlier connections
     // when the per-host connection limit is reached.
-    let pool = ConnectionPool::new_with_limits(10, 2);
+    let pool = ConnectionPool::new_with_limits(10, 2, None);
     let poolkey = PoolKey {
         scheme: "https".to_string(),
         hostname: "example.com".to_string(),
@@ -279,7 +324,7 @@ fn pool_per_host_connections_limit() {
 fn pool_checks_proxy() {
     // Test inserting different poolkeys with same address but different proxies.
     // Each insertion should result in an additional entry in the pool.
-    let pool = ConnectionPool::new_with_limits(10, 1);
+    let pool = ConnectionPool::new_with_limits(10, 1, None);
     let url = Url::parse("zzz:///example.com").unwrap();
 
     pool.add(PoolKey::new(&url, None), Stream::from_vec(vec![]));
@@ -301,22 +346,48 @@ fn pool_checks_proxy() {
     assert_eq!(pool.len(), 3);
 }
 
+#[test]
+fn pool_tracks_idle_time() {
+    // Test that a stream idle for longer than max_idle_time is dropped
+    // instead of being reused.
+    let pool = ConnectionPool::new_with_limits(10, 2, Some(Duration::from_millis(200)));
+    let url = Url::parse("zzz:///example.com").unwrap();
+
+    pool.add(PoolKey::new(&url, None), Stream::from_vec(vec![]));
+    std::thread::sleep(Duration::from_millis(20));
+    let stream = pool.try_get_connection(&url, None).unwrap();
+    assert!(stream.idle_time() >= Duration::from_millis(20));
+
+    pool.add(PoolKey::new(&url, None), stream);
+    pool.add(PoolKey::new(&url, None), Stream::from_vec(vec![]));
+    assert_eq!(pool.len(), 2);
+    std::thread::sleep(Duration::from_millis(300));
+    assert!(pool.try_get_connection(&url, None).is_none());
+    assert_eq!(pool.len(), 0);
+}
+
 /// Read wrapper that returns the stream to the pool once the
 /// read is exhausted (reached a 0).
 ///
+/// Once a read fails, the stream is never returned: a body that was cut
+/// short or badly framed leaves the connection in an unknown state.
+///
 /// *Internal API*
 pub(crate) struct PoolReturnRead<R: Read + Sized + Into<Stream>> {
     // unit that contains the agent where we want to return the reader.
     unit: Option<Unit>,
     // wrapped reader around the same stream
     reader: Option<R>,
+    // set when the body ended cleanly on a stream that can be reused.
+    reusable: Arc<AtomicBool>,
 }
 
 impl<R: Read + Sized + Into<Stream>> PoolReturnRead<R> {
-    pub fn new(unit: Option<Unit>, reader: R) -> Self {
+    pub fn new(unit: Option<Unit>, reader: R, reusable: Arc<AtomicBool>) -> Self {
         PoolReturnRead {
             unit,
             reader: Some(reader),
+            reusable,
         }
     }
 
@@ -329,6 +400,19 @@ impl<R: Read + Sized + Into<Stream>> PoolReturnRead<R> {
                 // just let it deallocate
                 return Ok(());
             }
+            self.reusable.store(true, Ordering::Relaxed);
+            if unit.agent.state.pool.noop() {
+                return Ok(());
+            }
+
+            // retire connections that have served their quota of requests.
+            let served = stream.count_request();
+            if let Some(max) = unit.agent.config.max_requests_per_connection {
+                if served >= max {
+                    debug!("dropping stream after {} requests: {:?}", served, stream);
+                    return Ok(());
+                }
+            }
 
             // ensure stream can be reused
             stream.reset()?;
@@ -351,7 +435,14 @@ impl<R: Read + Sized + Into<Stream>> PoolReturnRead<R> {
 
 impl<R: Read + Sized + Into<Stream>> Read for PoolReturnRead<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let amount = self.do_read(buf)?;
+        let amount = match self.do_read(buf) {
+            Ok(amount) => amount,
+            Err(e) => {
+                // without the unit, the stream is dropped instead of returned.
+                self.unit = None;
+                return Err(e);
+            }
+        };
         // only if the underlying reader is exhausted can we send a new
         // request to the same socket. hence, we only return it now.
         if amount == 0 {
@@ -1,14 +1,22 @@
 use std::fmt;
-use std::io::Read;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
 
+use log::debug;
 use url::{form_urlencoded, Url};
 
-use crate::body::Payload;
+use crate::body::{Body, BodyKind, ExactLenRead, Payload, SizedSource};
 use crate::error::ErrorKind;
+use crate::extensions::Extensions;
 use crate::header::{self, Header};
 use crate::unit::{self, Unit};
 use crate::Response;
-use crate::{agent::Agent, error::Error};
+use crate::{
+    agent::{Agent, AgentConfig},
+    error::Error,
+};
 
 #[cfg(feature = "json")]
 use super::SerdeValue;
@@ -40,6 +48,37 @@ pub struct Request {
     error_on_non_2xx: bool,
     headers: Vec<Header>,
     query_params: Vec<(String, String)>,
+    extensions: Extensions,
+    raw_method: bool,
+    http_version: HttpVersion,
+    // Statuses of 400 and up that don't count as errors.
+    ok_statuses: Vec<u16>,
+    idempotent: Option<bool>,
+    header_order: Vec<String>,
+    query_space_as_plus: bool,
+    no_redirects: bool,
+    #[cfg(feature = "charset")]
+    charset_fallback: Vec<String>,
+}
+
+/// The HTTP version to send a request with.
+///
+/// See [`Request::http_version`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// HTTP/1.0
+    Http10,
+    /// HTTP/1.1, the default.
+    Http11,
+}
+
+impl HttpVersion {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            HttpVersion::Http10 => "HTTP/1.0",
+            HttpVersion::Http11 => "HTTP/1.1",
+        }
+    }
 }
 
 impl fmt::Display for Urlish {
@@ -70,6 +109,16 @@ impl Request {
             headers: vec![],
             error_on_non_2xx: true,
             query_params: vec![],
+            extensions: Extensions::new(),
+            raw_method: false,
+            http_version: HttpVersion::Http11,
+            ok_statuses: vec![],
+            idempotent: None,
+            header_order: vec![],
+            query_space_as_plus: true,
+            no_redirects: false,
+            #[cfg(feature = "charset")]
+            charset_fallback: vec![],
         }
     }
 
@@ -81,6 +130,16 @@ impl Request {
             headers: vec![],
             error_on_non_2xx: true,
             query_params: vec![],
+            extensions: Extensions::new(),
+            raw_method: false,
+            http_version: HttpVersion::Http11,
+            ok_statuses: vec![],
+            idempotent: None,
+            header_order: vec![],
+            query_space_as_plus: true,
+            no_redirects: false,
+            #[cfg(feature = "charset")]
+            charset_fallback: vec![],
         }
     }
 
@@ -101,10 +160,50 @@ impl Request {
         self.do_call(Payload::Empty)
     }
 
+    /// Sends the request with no body and returns only the status code.
+    ///
+    /// The response body is read and thrown away, so the connection can go
+    /// back to the pool. Handy for health checks and pings. Unlike
+    /// [`call()`](Request::call), 4xx and 5xx statuses are returned as
+    /// `Ok`, since those are answers too.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let status = ureq::get("http://example.com/health")
+    ///     .call_status()?;
+    /// let up = status == 200;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_status(self) -> Result<u16> {
+        let resp = match self.call() {
+            Ok(resp) => resp,
+            Err(Error::Status(_, resp)) => resp,
+            Err(e) => return Err(e),
+        };
+        let status = resp.status();
+        io::copy(&mut resp.into_reader(), &mut io::sink())?;
+        Ok(status)
+    }
+
     fn do_call(&self, payload: Payload) -> Result<Response> {
+        // https://tools.ietf.org/html/rfc7230#section-3.1.1
+        // method = token
+        if !self.raw_method && !header::valid_name(&self.method) {
+            return Err(ErrorKind::InvalidMethod.msg(&format!("invalid method '{}'", self.method)));
+        }
         for h in &self.headers {
             h.validate()?;
         }
+        // content-coding = token, in a comma separated list
+        for value in self.all("Content-Encoding") {
+            if !value.split(',').all(|c| header::valid_name(c.trim())) {
+                return Err(
+                    ErrorKind::BadHeader.msg(&format!("invalid content encoding '{}'", value))
+                );
+            }
+        }
         let mut url: Url = match self.url.clone() {
             Urlish::Url(u) => u,
             Urlish::Str(s) => s.parse().map_err(|e: url::ParseError| {
@@ -113,20 +212,89 @@ impl Request {
                     .src(e)
             })?,
         };
-        for (name, value) in self.query_params.clone() {
-            url.query_pairs_mut().append_pair(&name, &value);
-        }
+        append_query(&mut url, &self.query_params, self.query_space_as_plus);
         let reader = payload.into_read();
-        let unit = Unit::new(&self.agent, &self.method, &url, &self.headers, &reader);
+        let mut idempotent = self.idempotent;
+        let mut headers = self.headers.clone();
+        if self.agent.config.idempotency_keys
+            && (self.method == "POST" || self.method == "PATCH")
+            && !self.has(IDEMPOTENCY_KEY)
+        {
+            headers.push(Header::new(IDEMPOTENCY_KEY, &new_idempotency_key()));
+            idempotent.get_or_insert(true);
+        }
+        let mut unit = Unit::new(&self.agent, &self.method, &url, &headers, &reader);
+        if let Some(host) = self.agent.config.host_config(&unit.url) {
+            for h in &host.headers {
+                h.validate()?;
+            }
+        }
+        if let Some(languages) = &self.agent.config.accept_language {
+            Header::new("Accept-Language", languages).validate()?;
+        }
+        unit.extensions = self.extensions.clone();
+        unit.http_version = self.http_version;
+        unit.idempotent = idempotent;
+        unit.header_order = self.header_order.clone();
+        unit.no_redirects = self.no_redirects;
+        #[cfg(feature = "charset")]
+        {
+            unit.charset_fallback = self.charset_fallback.clone();
+        }
         let response = unit::connect(unit, true, reader, None).map_err(|e| e.url(url.clone()))?;
 
-        if response.status() >= 400 {
+        if response.status() >= 400 && !self.ok_statuses.contains(&response.status()) {
+            let mut response = response;
+            if let Some(max) = self.agent.config.max_error_body_size {
+                if let Err(e) = response.buffer_body(Some(max)) {
+                    debug!("failed to read error body: {}", e);
+                }
+            }
             Err(Error::Status(response.status(), response))
         } else {
             Ok(response)
         }
     }
 
+    /// Send a body of any kind, setting headers as the matching `send_*` method
+    /// does.
+    ///
+    /// | Body from            | Sent like                                  |
+    /// |----------------------|--------------------------------------------|
+    /// | `&str`, `String`     | [`send_string()`](#method.send_string)     |
+    /// | `&[u8]`, `Vec<u8>`   | [`send_bytes()`](#method.send_bytes)       |
+    /// | [`Body::reader()`]   | [`send()`](#method.send)                   |
+    /// | [`Body::form()`]     | [`send_form()`](#method.send_form)         |
+    /// | [`SerdeValue`]       | [`send_json()`](#method.send_json)         |
+    ///
+    /// [`SerdeValue`]: crate::SerdeValue
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::post("http://httpbin.org/post")
+    ///     .set("Content-Type", "text/csv")
+    ///     .call_with_body(vec![b'a', b',', b'b'])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_with_body<'a>(self, body: impl Into<Body<'a>>) -> Result<Response> {
+        match body.into().0 {
+            BodyKind::Text(text) => self.send_string(&text),
+            BodyKind::Bytes(bytes) => self.send_bytes(&bytes),
+            BodyKind::Reader(reader) => self.send(reader),
+            BodyKind::Form(pairs) => {
+                let pairs: Vec<(&str, &str)> = pairs
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.as_str()))
+                    .collect();
+                self.send_form(&pairs)
+            }
+            #[cfg(feature = "json")]
+            BodyKind::Json(value) => self.send_json(value),
+        }
+    }
+
     /// Send data a json value.
     ///
     /// Requires feature `ureq = { version = "*", features = ["json"] }`
@@ -247,6 +415,359 @@ impl Request {
         self.do_call(Payload::Reader(Box::new(reader)))
     }
 
+    /// Send a body by writing it from a closure.
+    ///
+    /// The closure is called with a writer for the body once the request
+    /// head has been sent. This is more flexible than [`send()`](#method.send)
+    /// for streaming uploads: data can be written as it becomes available,
+    /// and the caller decides when it goes out. Writes are buffered until
+    /// the writer is flushed, or the buffer holds 16,384 bytes, so flushing
+    /// batches them into a single write to the connection.
+    ///
+    /// Like with `send()`, the body is sent with the
+    /// [chunked transfer encoding](https://tools.ietf.org/html/rfc7230#section-4.1),
+    /// each flush making one chunk, unless a `Content-Length` or
+    /// `Transfer-Encoding` header is set. With `Content-Length`, the closure
+    /// must write exactly that many bytes. If the closure returns an error,
+    /// the request fails with it.
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::post("http://httpbin.org/post")
+    ///     .send_with(|body| {
+    ///         for line in &["one", "two", "three"] {
+    ///             writeln!(body, "{}", line)?;
+    ///             body.flush()?;
+    ///         }
+    ///         Ok(())
+    ///     })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_with(
+        self,
+        writer: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+    ) -> Result<Response> {
+        self.do_call(Payload::Writer(Box::new(writer)))
+    }
+
+    /// Send data from a reader with the chunked transfer encoding, followed
+    /// by trailers.
+    ///
+    /// Once the reader is done, `trailers` is called, and the headers it
+    /// returns are sent after the last chunk. This is useful for values only
+    /// known after the whole body was sent, like a checksum computed while
+    /// reading it. To announce the trailer names to the server up front,
+    /// set a `Trailer` header, like `.set("Trailer", "Digest")`.
+    ///
+    /// The body is always chunked, so a `Content-Length` header must not be
+    /// set. An invalid trailer fails the request with an error of kind
+    /// [`Io`](crate::ErrorKind::Io), without ending the body.
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let read = Cursor::new(vec![0x20; 100]);
+    /// let resp = ureq::post("http://httpbin.org/post")
+    ///     .set("Trailer", "X-Upload-Status")
+    ///     .send_chunked_with_trailers(read, || {
+    ///         vec![ureq::Header::new("X-Upload-Status", "complete")]
+    ///     })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_chunked_with_trailers(
+        self,
+        reader: impl Read,
+        trailers: impl FnOnce() -> Vec<Header>,
+    ) -> Result<Response> {
+        self.set("Transfer-Encoding", "chunked")
+            .do_call(Payload::Trailed(Box::new(reader), Box::new(trailers)))
+    }
+
+    /// Send data from a reader of a known length, with a `Content-Length`
+    /// rather than the chunked transfer encoding.
+    ///
+    /// Exactly `len` bytes are sent. If the reader has fewer or more, the
+    /// request fails with an error of kind [`Io`](crate::ErrorKind::Io).
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let read = Cursor::new(vec![0x20; 100]);
+    /// let resp = ureq::post("http://httpbin.org/post")
+    ///     .send_with_len(read, 100)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_with_len(self, reader: impl Read, len: u64) -> Result<Response> {
+        self.set("Content-Length", &len.to_string())
+            .do_call(Payload::Reader(Box::new(ExactLenRead::new(reader, len))))
+    }
+
+    /// Send a body whose length is known up front, like a byte slice or a
+    /// file, with a `Content-Length` rather than the chunked transfer
+    /// encoding.
+    ///
+    /// Any type implementing [`SizedSource`](crate::SizedSource) can be sent.
+    /// The body is streamed, as with [`send_with_len()`](#method.send_with_len).
+    /// Failing to get the length, for instance of a file, is an error of kind
+    /// [`Io`](crate::ErrorKind::Io).
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let data: Vec<u8> = vec![0x20; 100];
+    /// let resp = ureq::post("http://httpbin.org/post")
+    ///     .send_sized(data)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_sized(self, source: impl SizedSource) -> Result<Response> {
+        let (reader, len) = source.into_sized().map_err(|e| {
+            ErrorKind::Io
+                .msg("failed to get the length of the body")
+                .src(e)
+        })?;
+        self.send_with_len(reader, len)
+    }
+
+    /// Send the contents of a file.
+    ///
+    /// The `Content-Length` is taken from the file's size, unless already set,
+    /// and the file is streamed rather than read into memory. Failing to open
+    /// the file is an error of kind [`Io`](crate::ErrorKind::Io).
+    ///
+    /// The `Content-Type` isn't set; use [`guess_content_type()`](#method.guess_content_type)
+    /// to set it from the file name.
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// let resp = ureq::put("http://example.com/upload/report.pdf")
+    ///     .set("Content-Type", "application/pdf")
+    ///     .send_file("report.pdf")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_file(mut self, path: impl AsRef<Path>) -> Result<Response> {
+        let path = path.as_ref();
+        let open = || -> std::io::Result<(File, u64)> {
+            let file = File::open(path)?;
+            let len = file.metadata()?.len();
+            Ok((file, len))
+        };
+        let (file, len) = open().map_err(|e| {
+            ErrorKind::Io
+                .msg(&format!("failed to open {}", path.display()))
+                .src(e)
+        })?;
+        if self.header("Content-Length").is_none() && self.header("Transfer-Encoding").is_none() {
+            self = self.set("Content-Length", &len.to_string());
+        }
+        self.do_call(Payload::Reader(Box::new(file)))
+    }
+
+    /// Set the `Content-Type` from the extension of `path`, using
+    /// [`ureq::guess_content_type`](crate::guess_content_type).
+    ///
+    /// Nothing is set if the request already has a `Content-Type` or the
+    /// extension isn't known.
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// let resp = ureq::put("http://example.com/upload/photo.png")
+    ///     .guess_content_type("photo.png")
+    ///     .send_file("photo.png")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn guess_content_type(self, path: impl AsRef<Path>) -> Self {
+        match crate::guess_content_type(path) {
+            Some(content_type) if self.header("Content-Type").is_none() => {
+                self.set("Content-Type", content_type)
+            }
+            _ => self,
+        }
+    }
+
+    /// Send this request with a specific HTTP version. The default is HTTP/1.1.
+    ///
+    /// HTTP/1.0 is for legacy servers that don't speak 1.1. An HTTP/1.0
+    /// connection is closed after the response rather than returned to the
+    /// connection pool. HTTP/1.0 has no chunked transfer encoding, so
+    /// bodies of unknown size, like with [`send()`](#method.send), need a
+    /// `Content-Length` set by hand.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// use ureq::HttpVersion;
+    ///
+    /// let resp = ureq::get("http://example.com/legacy")
+    ///     .http_version(HttpVersion::Http10)
+    ///     .call()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn http_version(mut self, version: HttpVersion) -> Self {
+        self.http_version = version;
+        self
+    }
+
+    /// Send `method` verbatim as the method of the request line.
+    ///
+    /// Methods are normally checked to be valid tokens, and requests with
+    /// methods like `"GET /x"` fail with [`ErrorKind::InvalidMethod`](crate::ErrorKind::InvalidMethod).
+    /// This skips that check, which is only useful for tools that probe how
+    /// servers deal with malformed requests. Don't use it for anything else:
+    /// a method with spaces or line breaks lets the method rewrite the rest
+    /// of the request.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let result = ureq::get("http://example.com/")
+    ///     .raw_method("G@T")
+    ///     .call();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn raw_method(mut self, method: &str) -> Self {
+        self.method = method.to_string();
+        self.raw_method = true;
+        self
+    }
+
+    /// Treat these statuses as success, for APIs that use error codes for
+    /// outcomes that aren't failures.
+    ///
+    /// Responses with a status of 400 or above are normally returned as
+    /// [`Error::Status`](crate::Error::Status). With their status listed here,
+    /// they're returned as `Ok` instead. Other statuses of 400 and up are
+    /// still errors. Calling this again adds to the list.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/maybe-missing")
+    ///     .ok(&[404, 410])
+    ///     .call()?;
+    ///
+    /// if resp.status() == 200 {
+    ///     println!("{}", resp.into_string()?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ok(mut self, statuses: &[u16]) -> Self {
+        self.ok_statuses.extend_from_slice(statuses);
+        self
+    }
+
+    /// Declare whether this request is safe to send more than once.
+    ///
+    /// When a connection from the pool turns out to have been closed by the
+    /// server, requests with idempotent methods, like GET and PUT, are sent
+    /// again on a fresh connection. This overrides the decision based on the
+    /// method, for instance for a POST to an API that deduplicates requests
+    /// by an `Idempotency-Key` header, or to never resend a PUT. As before,
+    /// only requests without a body, or with an empty one, are resent.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::post("http://example.com/charges/retry")
+    ///     .set("Idempotency-Key", "9f3a8c")
+    ///     .idempotent(true)
+    ///     .call()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.idempotent = Some(idempotent);
+        self
+    }
+
+    /// The order to send headers in.
+    ///
+    /// Headers named in `names` are sent first, in that order, followed by
+    /// the others in the order they would be sent otherwise. This includes
+    /// the headers ureq adds, like `Host`, `User-Agent` and `Content-Length`.
+    /// Names are compared ignoring case.
+    ///
+    /// Some servers, and request signing schemes, depend on the order of
+    /// headers.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/")
+    ///     .set("X-Signature", "abc")
+    ///     .header_order(&["Host", "X-Signature", "User-Agent"])
+    ///     .call()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn header_order(mut self, names: &[&str]) -> Self {
+        self.header_order = names.iter().map(|n| n.to_ascii_lowercase()).collect();
+        self
+    }
+
+    /// Send an `Idempotency-Key` header with this request.
+    ///
+    /// APIs that support it use the key to recognize a request that is sent
+    /// more than once, and process it only once. Because of that, this also
+    /// makes the request retryable, like [`idempotent(true)`](#method.idempotent),
+    /// unless that was set to false. A retry sends the same key.
+    ///
+    /// To generate keys for all POST and PATCH requests, see
+    /// [`AgentBuilder::idempotency_keys`](crate::AgentBuilder::idempotency_keys).
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::post("http://example.com/charges")
+    ///     .with_idempotency_key("9f3a8c")
+    ///     .call()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_idempotency_key(mut self, key: &str) -> Self {
+        self.idempotent.get_or_insert(true);
+        self.set(IDEMPOTENCY_KEY, key)
+    }
+
+    /// Character encodings to try, in order, when the one in the response's
+    /// `Content-Type` header is unknown or doesn't fit the body.
+    ///
+    /// [`Response::into_string()`] decodes the body with the first encoding,
+    /// starting with the declared one, that decodes it without malformed byte
+    /// sequences. If none does, the first known encoding is used, with malformed
+    /// sequences replaced. Unknown labels are skipped. A byte order mark at the
+    /// start of the body still takes precedence.
+    ///
+    /// Requires feature `ureq = { version = "*", features = ["charset"] }`
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let text = ureq::get("http://example.com/legacy.html")
+    ///     .charset_fallback(&["windows-1252", "utf-8"])
+    ///     .call()?
+    ///     .into_string()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "charset")]
+    pub fn charset_fallback(mut self, labels: &[&str]) -> Self {
+        self.charset_fallback = labels.iter().map(|l| l.to_string()).collect();
+        self
+    }
+
     /// Set a header field.
     ///
     /// ```
@@ -264,6 +785,75 @@ impl Request {
         self
     }
 
+    /// Label the body as already compressed with `encoding`, like `gzip` or
+    /// `zstd`, by setting the `Content-Encoding` header.
+    ///
+    /// ureq doesn't compress request bodies, so the body is sent exactly as
+    /// given. Use this with [`send_bytes()`](#method.send_bytes) or
+    /// [`send()`](#method.send) for data that was compressed beforehand.
+    /// An encoding that isn't a valid token, or a comma separated list of
+    /// them, fails the request with
+    /// [`ErrorKind::BadHeader`](crate::ErrorKind::BadHeader).
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// # let gzipped: Vec<u8> = vec![0x1f, 0x8b];
+    /// let resp = ureq::post("http://example.com/upload")
+    ///     .content_encoding("gzip")
+    ///     .send_bytes(&gzipped)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn content_encoding(self, encoding: &str) -> Self {
+        self.set("Content-Encoding", encoding)
+    }
+
+    /// Tell the server that trailers are welcome, by sending `TE: trailers`
+    /// and `Connection: TE`.
+    ///
+    /// Some servers, like gRPC ones, only send trailers when asked this way.
+    /// The trailers the response announces in its `Trailer` header are then
+    /// kept and merged into the headers, like the ones named with
+    /// [`AgentBuilder::merge_trailers`](crate::AgentBuilder::merge_trailers).
+    /// They're only there once the body has been read to the end, so this is
+    /// mostly useful with [`Response::split`](crate::Response::split).
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # ureq::is_test(true);
+    /// use std::io::Read;
+    ///
+    /// let resp = ureq::post("http://example.com/grpc.Service/Method")
+    ///     .te_trailers()
+    ///     .call()?;
+    /// let (head, mut body) = resp.split();
+    /// let mut bytes = vec![];
+    /// body.read_to_end(&mut bytes)?;
+    /// let status = head.header("grpc-status");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn te_trailers(self) -> Self {
+        self.set("TE", "trailers").set("Connection", "TE")
+    }
+
+    /// Set the `Accept-Language` header, overriding any default set with
+    /// [`AgentBuilder::accept_language`](crate::AgentBuilder::accept_language).
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://httpbin.org/get")
+    ///     .accept_language("fr-CH, fr;q=0.9, en;q=0.8")
+    ///     .call()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn accept_language(self, languages: &str) -> Self {
+        self.set("Accept-Language", languages)
+    }
+
     /// Returns the value for a set header.
     ///
     /// ```
@@ -317,6 +907,204 @@ impl Request {
         header::get_all_headers(&self.headers, name)
     }
 
+    /// Disable all timeouts for this request.
+    ///
+    /// Clears the connect, read, write and overall timeouts configured on the
+    /// agent, and the timeouts of its
+    /// [`host_config`](crate::AgentBuilder::host_config), for this request
+    /// only. A request made with `no_timeout()` will
+    /// block for as long as the server takes to respond, which is useful for
+    /// long-polling endpoints. Other requests from the same agent, including
+    /// ones sharing its pooled connections, keep the agent's timeouts.
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// use std::time::Duration;
+    /// let agent = ureq::builder()
+    ///     .timeout(Duration::from_secs(10))
+    ///     .build();
+    ///
+    /// let resp = agent.get("http://example.com/long-poll")
+    ///     .no_timeout()
+    ///     .call()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn no_timeout(mut self) -> Self {
+        let mut config = AgentConfig {
+            timeout_connect: None,
+            timeout_read: None,
+            timeout_write: None,
+            timeout: None,
+            ..(*self.agent.config).clone()
+        };
+        for (_, host) in &mut config.host_configs {
+            host.timeout = None;
+        }
+        self.agent = Agent {
+            config: Arc::new(config),
+            state: self.agent.state.clone(),
+        };
+        self
+    }
+
+    /// Don't follow redirects for this request.
+    ///
+    /// A 3xx response is returned as it is, so its `Location` header can be
+    /// inspected, as if the agent was built with
+    /// [`AgentBuilder::redirects(0)`](crate::AgentBuilder::redirects). Other
+    /// requests made with the agent still follow redirects.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/short/abc")
+    ///     .no_redirects()
+    ///     .call()?;
+    ///
+    /// if let Some(target) = resp.header("location") {
+    ///     println!("points to {}", target);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn no_redirects(mut self) -> Self {
+        self.no_redirects = true;
+        self
+    }
+
+    /// Leave the body of the response compressed.
+    ///
+    /// With the `gzip` feature, a body sent with `Content-Encoding: gzip` or
+    /// `deflate` is normally decoded when read. This turns that off for this
+    /// request only, so the raw bytes can, for instance, be stored as they
+    /// are. The `Content-Encoding` header is kept, and transfer codings are
+    /// still decoded. Without the `gzip` feature this does nothing.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/archive.json")
+    ///     .set("Accept-Encoding", "gzip")
+    ///     .no_decompress()
+    ///     .call()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(not(feature = "gzip"), allow(unused_mut))]
+    pub fn no_decompress(mut self) -> Self {
+        #[cfg(feature = "gzip")]
+        {
+            use crate::agent::DecompressFilter;
+            let config = AgentConfig {
+                should_decompress: Some(DecompressFilter(Arc::new(|_| false))),
+                sniff_encoding: false,
+                ..(*self.agent.config).clone()
+            };
+            self.agent = Agent {
+                config: Arc::new(config),
+                state: self.agent.state.clone(),
+            };
+        }
+        self
+    }
+
+    /// A copy of this request, to be made with `agent` instead.
+    ///
+    /// The method, URL, query parameters, headers and other settings of the
+    /// request are kept. The copy uses the other agent's configuration, like
+    /// its proxy, resolver and timeouts, and its connection pool and cookies.
+    /// This includes its timeouts and decompression settings, so a
+    /// [`no_timeout()`](#method.no_timeout) or
+    /// [`no_decompress()`](#method.no_decompress) has to be repeated.
+    ///
+    /// This is useful to fail over to another agent when a request fails.
+    /// The body isn't part of the request, but is given when sending, so send
+    /// the copy with the same body again.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let primary = ureq::agent();
+    /// let fallback = ureq::builder()
+    ///     .proxy(ureq::Proxy::new("fallback-proxy:8080")?)
+    ///     .build();
+    ///
+    /// let req = primary.post("http://example.com/upload")
+    ///     .set("X-Upload-Id", "42");
+    /// let resp = match req.clone().send_string("data") {
+    ///     Err(ureq::Error::Transport(_)) => req.clone_for(&fallback).send_string("data")?,
+    ///     result => result?,
+    /// };
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clone_for(&self, agent: &Agent) -> Request {
+        Request {
+            agent: agent.clone(),
+            ..self.clone()
+        }
+    }
+
+    /// A copy of this request that can be sent any number of times.
+    ///
+    /// Sending a `Request` consumes it. A [`PreparedRequest`] is sent by
+    /// reference instead, which suits polling the same URL over and over.
+    /// The query parameters are added to the URL once, here, rather than on
+    /// every call.
+    ///
+    /// A prepared request has no streaming bodies, only ones that can be
+    /// sent again, like `&[u8]`. Errors in the URL, method or headers are
+    /// returned by each call, as they are for a `Request`.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let poll = ureq::get("http://example.com/jobs/42")
+    ///     .query("fields", "status")
+    ///     .set("Accept", "application/json")
+    ///     .build();
+    ///
+    /// let resp = poll.call()?;
+    /// // later
+    /// let resp = poll.call()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn build(&self) -> PreparedRequest {
+        let mut request = self.clone();
+        if let Urlish::Str(s) = &request.url {
+            if let Ok(url) = s.parse() {
+                request.url = Urlish::Url(url);
+            }
+        }
+        if let Urlish::Url(url) = &mut request.url {
+            append_query(url, &request.query_params, request.query_space_as_plus);
+            request.query_params.clear();
+        }
+        PreparedRequest { request }
+    }
+
+    /// Typed values attached to this request. They are passed on to the
+    /// [Response](crate::Response), including across redirects.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Mutable access to the typed values attached to this request.
+    ///
+    /// ```
+    /// struct Attempt(u32);
+    ///
+    /// let mut req = ureq::get("http://example.com/");
+    /// req.extensions_mut().insert(Attempt(1));
+    /// assert_eq!(req.extensions().get::<Attempt>().unwrap().0, 1);
+    /// ```
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
     /// Set a query parameter.
     ///
     /// For example, to set `?format=json&dest=/login`
@@ -336,6 +1124,139 @@ impl Request {
             .push((param.to_string(), value.to_string()));
         self
     }
+
+    /// Whether spaces in [`query()`](#method.query) parameters are encoded as
+    /// `+`, like in an HTML form, or as `%20`.
+    ///
+    /// The default is `+`, which is what the `url` crate does for query
+    /// pairs. Some servers only understand `%20`. With `false`, names and
+    /// values are encoded like [`ureq::encode_query`](crate::encode_query).
+    /// Query parameters already in the URL are sent as they are.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// // GET /search?q=fish%20chips
+    /// let resp = ureq::get("http://example.com/search")
+    ///     .query_space_as_plus(false)
+    ///     .query("q", "fish chips")
+    ///     .call()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn query_space_as_plus(mut self, plus: bool) -> Self {
+        self.query_space_as_plus = plus;
+        self
+    }
+}
+
+// Add the query parameters to the query of `url`.
+fn append_query(url: &mut Url, params: &[(String, String)], space_as_plus: bool) {
+    if space_as_plus {
+        for (name, value) in params {
+            url.query_pairs_mut().append_pair(name, value);
+        }
+        return;
+    }
+    if params.is_empty() {
+        return;
+    }
+    let mut query = url.query().unwrap_or("").to_string();
+    for (name, value) in params {
+        if !query.is_empty() {
+            query.push('&');
+        }
+        query.push_str(&crate::encode_query(name));
+        query.push('=');
+        query.push_str(&crate::encode_query(value));
+    }
+    url.set_query(Some(&query));
+}
+
+/// A request that can be sent more than once.
+///
+/// Made with [`Request::build()`]. Cloning one is cheap compared to building
+/// the request again.
+#[derive(Clone)]
+pub struct PreparedRequest {
+    request: Request,
+}
+
+impl fmt::Debug for PreparedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PreparedRequest({} {}, {:?})",
+            self.request.method, self.request.url, self.request.headers
+        )
+    }
+}
+
+impl PreparedRequest {
+    /// Send the request without a body, like [`Request::call()`].
+    pub fn call(&self) -> Result<Response> {
+        self.request.do_call(Payload::Empty)
+    }
+
+    /// Send the request with `data` as the body, like
+    /// [`Request::send_bytes()`].
+    pub fn send_bytes(&self, data: &[u8]) -> Result<Response> {
+        self.request.do_call(Payload::Bytes(data))
+    }
+
+    /// The URL this request is sent to, including the query.
+    pub fn url(&self) -> String {
+        self.request.url.to_string()
+    }
+}
+
+const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+// A random (version 4) UUID to use as an idempotency key.
+// https://tools.ietf.org/html/rfc4122#section-4.4
+fn new_idempotency_key() -> String {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::SystemTime;
+
+    // Each RandomState has its own random keys, so this hashes a counter
+    // into unpredictable bits without depending on a random generator.
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let random = || {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        if let Ok(since_epoch) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            hasher.write_u128(since_epoch.as_nanos());
+        }
+        hasher.finish()
+    };
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&random().to_be_bytes());
+    bytes[8..].copy_from_slice(&random().to_be_bytes());
+    bytes[6] = bytes[6] & 0x0f | 0x40;
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[test]
+fn idempotency_key_is_uuid_v4() {
+    let key = new_idempotency_key();
+    let parts: Vec<_> = key.split('-').map(str::len).collect();
+    assert_eq!(parts, vec![8, 4, 4, 4, 12]);
+    assert!(key.chars().all(|c| c == '-' || c.is_ascii_hexdigit()));
+    assert_eq!(&key[14..15], "4");
+    assert!("89ab".contains(&key[19..20]));
+    assert_ne!(key, new_idempotency_key());
 }
 
 #[test]
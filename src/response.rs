@@ -1,18 +1,36 @@
+use std::collections::VecDeque;
 use std::str::FromStr;
 use std::{fmt, io::BufRead};
 use std::{
-    io::{self, Read},
+    io::{self, Cursor, Read, Write},
+    mem,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
-use chunked_transfer::Decoder as ChunkDecoder;
+use log::debug;
 use url::Url;
 
-use crate::error::{Error, ErrorKind::BadStatus};
-use crate::header::Header;
+use crate::agent::DEFAULT_MAX_STATUS_LINE_LENGTH;
+use crate::altsvc::{self, AltService};
+use crate::auth::{self, AuthChallenge};
+use crate::byteranges::{parse_content_range, ByteRangePart, ByteRanges};
+use crate::cache_control::{self, CacheControl};
+use crate::chunked::{ChunkDecoder, Trailers};
+use crate::clock::ArcClock;
+use crate::disposition::{self, ContentDisposition};
+use crate::error::{
+    Error,
+    ErrorKind::{BadHeader, BadStatus},
+};
+use crate::extensions::Extensions;
+use crate::forwarded::{self, ForwardedEntry};
+use crate::header::{media_type_param, Header};
+use crate::hsts::{self, Hsts};
 use crate::pool::PoolReturnRead;
-use crate::stream::{DeadlineStream, Stream};
-use crate::unit::Unit;
+use crate::stream::{CallDeadline, DeadlineStream, Stream};
+use crate::unit::{is_sensitive, Unit};
 use crate::stream;
 
 #[cfg(feature = "json")]
@@ -21,6 +39,11 @@ use serde::de::DeserializeOwned;
 #[cfg(feature = "charset")]
 use encoding_rs::Encoding;
 
+#[cfg(feature = "checksum")]
+use md5::Md5;
+#[cfg(feature = "checksum")]
+use sha2::{Digest, Sha256};
+
 pub const DEFAULT_CONTENT_TYPE: &str = "text/plain";
 pub const DEFAULT_CHARACTER_SET: &str = "utf-8";
 
@@ -55,6 +78,12 @@ pub struct Response {
     // If this Response resulted from a redirect, the Response containing
     // that redirect.
     previous: Option<Arc<Response>>,
+    // The decoded body, once buffered by body_mut().
+    body: Option<Vec<u8>>,
+    extensions: Extensions,
+    time_to_first_byte: Option<Duration>,
+    // Trailers merged into the headers once a chunked body is read.
+    trailers: Trailers,
 }
 
 /// index into status_line where we split: HTTP/1.1 200 OK
@@ -96,12 +125,271 @@ impl Response {
         (r.as_ref() as &str).parse()
     }
 
+    /// Construct a response with a status, headers and a binary body.
+    ///
+    /// Like [`Response::new`], this is meant for tests. The headers are used
+    /// as they are, in order, so nothing like `Content-Length` is added. The
+    /// status text is empty.
+    ///
+    /// Panics if the status doesn't have three digits, or a header isn't
+    /// valid.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::io::Read;
+    ///
+    /// let resp = ureq::Response::from_parts(
+    ///     200,
+    ///     &[("Content-Type", "image/png")],
+    ///     vec![0x89, b'P', b'N', b'G'],
+    /// );
+    /// assert_eq!(resp.content_type(), "image/png");
+    ///
+    /// let mut body = vec![];
+    /// resp.into_reader().read_to_end(&mut body).unwrap();
+    /// assert_eq!(body, [0x89, b'P', b'N', b'G']);
+    /// ```
+    pub fn from_parts(status: u16, headers: &[(&str, &str)], body: Vec<u8>) -> Response {
+        let mut wire = format!("HTTP/1.1 {} \r\n", status).into_bytes();
+        for (name, value) in headers {
+            let header = Header::new(name, value);
+            if let Err(e) = header.validate() {
+                panic!("invalid header for Response::from_parts: {}", e);
+            }
+            wire.extend(format!("{}: {}\r\n", header.name(), header.value()).bytes());
+        }
+        wire.extend(b"\r\n");
+        wire.extend(body);
+        Self::do_from_stream(Stream::from_vec(wire), None)
+            .expect("invalid status for Response::from_parts")
+    }
+
+    /// Buffer the body of a constructed response and give access to it.
+    ///
+    /// The `into_*` methods all consume the response, which gets in the way
+    /// when a test wants to inspect a body more than once. The first call to
+    /// `body_mut()` reads the whole (decoded) body into memory; later calls,
+    /// as well as [`into_reader()`](#method.into_reader) and friends, read from
+    /// that buffer instead.
+    ///
+    /// This is a helper for tests. It only works for responses created with
+    /// [`Response::new`], [`Response::from_parts`], [`Response::from_reader`]
+    /// or by parsing a string, and returns an error for responses received
+    /// from a server.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut resp = ureq::Response::new(200, "OK", "hello")?;
+    ///
+    /// assert_eq!(resp.body_mut()?, b"hello\n");
+    /// assert_eq!(resp.body_mut()?, b"hello\n");
+    /// assert_eq!(resp.into_string()?, "hello\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn body_mut(&mut self) -> io::Result<&mut Vec<u8>> {
+        if self.body.is_none() {
+            if self.unit.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "body_mut() is only available for constructed responses",
+                ));
+            }
+            self.buffer_body(None)?;
+        }
+        Ok(self.body.as_mut().unwrap())
+    }
+
+    // Read the (decoded) body into memory, up to `limit` bytes, so that later
+    // reads come from the buffer. Anything past the limit is discarded, and
+    // the connection is closed rather than returned to the pool.
+    pub(crate) fn buffer_body(&mut self, limit: Option<usize>) -> io::Result<()> {
+        if self.body.is_some() {
+            return Ok(());
+        }
+        let emptied = self.stream.emptied();
+        let unread = Response {
+            url: None,
+            status_line: self.status_line.clone(),
+            index: self.index,
+            status: self.status,
+            headers: self.headers.clone(),
+            unit: self.unit.clone(),
+            stream: mem::replace(&mut self.stream, emptied),
+            previous: None,
+            body: None,
+            extensions: Extensions::new(),
+            time_to_first_byte: None,
+            trailers: self.trailers.clone(),
+        };
+        let limit = limit.map(|l| l as u64).unwrap_or(u64::MAX);
+        let mut buf = vec![];
+        unread
+            .into_boxed_reader()
+            .take(limit)
+            .read_to_end(&mut buf)?;
+        self.body = Some(buf);
+        Ok(())
+    }
+
     /// The URL we ended up at. This can differ from the request url when
     /// we have followed redirects.
     pub fn get_url(&self) -> &str {
         self.url.as_ref().map(|s| &s[..]).unwrap_or("")
     }
 
+    /// The URL we ended up at, as a parsed [Url]. This can differ from the
+    /// request url when we have followed redirects.
+    ///
+    /// Returns `None` for responses that weren't the result of a request, like
+    /// ones created with [`Response::new`].
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/page?q=1").call()?;
+    /// let url = resp.url().unwrap();
+    ///
+    /// assert_eq!(url.host_str(), Some("example.com"));
+    /// assert_eq!(url.query(), Some("q=1"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
+    /// Whether the request was sent on a connection kept in the agent's pool,
+    /// rather than on a new one.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let agent = ureq::agent();
+    /// let resp = agent.get("http://example.com/").call()?;
+    /// println!("reused: {}", resp.connection_reused());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connection_reused(&self) -> bool {
+        match &self.unit {
+            Some(unit) => unit.connection_reused,
+            None => false,
+        }
+    }
+
+    /// The scheme of the [url](#method.url), like `"https"`.
+    ///
+    /// This and the other `request_*` methods are `None` for responses that
+    /// weren't the result of a request.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/page?q=1").call()?;
+    ///
+    /// assert_eq!(resp.request_scheme(), Some("http"));
+    /// assert_eq!(resp.request_host(), Some("example.com"));
+    /// assert_eq!(resp.request_port(), Some(80));
+    /// assert_eq!(resp.request_path(), Some("/page"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn request_scheme(&self) -> Option<&str> {
+        self.url.as_ref().map(|u| u.scheme())
+    }
+
+    /// The host of the [url](#method.url), a domain or an IP address.
+    pub fn request_host(&self) -> Option<&str> {
+        self.url.as_ref().and_then(|u| u.host_str())
+    }
+
+    /// The port of the [url](#method.url). If the url has none, this is the
+    /// default port of the scheme: 80 for http and 443 for https.
+    pub fn request_port(&self) -> Option<u16> {
+        self.url.as_ref().and_then(|u| u.port_or_known_default())
+    }
+
+    /// The path of the [url](#method.url), without the query.
+    pub fn request_path(&self) -> Option<&str> {
+        self.url.as_ref().map(|u| u.path())
+    }
+
+    /// The headers of the request that produced this response, as they were
+    /// sent.
+    ///
+    /// Besides the headers set on the [Request](crate::Request), this
+    /// includes the ones ureq adds, like `Host`, `User-Agent` and
+    /// `Content-Length`. The values of headers carrying credentials, such as
+    /// `Authorization` and `Cookie`, are replaced with `***`. Responses that
+    /// weren't the result of a request have none.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::post("http://example.com/form")
+    ///     .set("Authorization", "Bearer secret")
+    ///     .send_string("hello")?;
+    ///
+    /// for (name, value) in resp.request_headers() {
+    ///     println!("{}: {}", name, value);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn request_headers(&self) -> Vec<(String, String)> {
+        let headers = self.unit.as_ref().map(|u| &u.sent_headers[..]);
+        headers
+            .unwrap_or(&[])
+            .iter()
+            .map(|h| {
+                let value = if is_sensitive(h) { "***" } else { h.value() };
+                (h.name().to_string(), value.to_string())
+            })
+            .collect()
+    }
+
+    /// The redirect responses that led to this one, most recent first.
+    ///
+    /// Each keeps its status and headers, like `Location` and `Set-Cookie`,
+    /// which helps when debugging redirect chains. If this response wasn't
+    /// redirected, there are none. The bodies of the redirects aren't read.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/").call()?;
+    ///
+    /// for hop in resp.redirect_history() {
+    ///     println!("{} {} -> {:?}", hop.status(), hop.get_url(), hop.header("location"));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn redirect_history(&self) -> impl Iterator<Item = &Response> + '_ {
+        self.history()
+    }
+
+    /// How many redirects were followed to reach this response. Zero if it
+    /// wasn't redirected.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/").call()?;
+    /// if resp.redirect_count() > 0 {
+    ///     println!("redirected to {}", resp.get_url());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn redirect_count(&self) -> usize {
+        self.history().count()
+    }
+
     /// The http version: `HTTP/1.1`
     pub fn http_version(&self) -> &str {
         &self.status_line.as_str()[0..self.index.http_version]
@@ -118,9 +406,14 @@ impl Response {
     }
 
     /// The header corresponding header value for the give name, if any.
+    ///
+    /// Trailers picked with
+    /// [`AgentBuilder::merge_trailers`](crate::AgentBuilder::merge_trailers)
+    /// are found here too, once the body has been read to the end.
     pub fn header(&self, name: &str) -> Option<&str> {
         self.headers
             .iter()
+            .chain(self.trailers())
             .find(|h| h.is_name(name))
             .map(|h| h.value())
     }
@@ -130,6 +423,7 @@ impl Response {
     pub fn headers_names(&self) -> Vec<String> {
         self.headers
             .iter()
+            .chain(self.trailers())
             .map(|h| h.name().to_lowercase())
             .collect()
     }
@@ -143,631 +437,3466 @@ impl Response {
     pub fn all(&self, name: &str) -> Vec<&str> {
         self.headers
             .iter()
+            .chain(self.trailers())
             .filter(|h| h.is_name(name))
             .map(|h| h.value())
             .collect()
     }
 
-    /// The content type part of the "Content-Type" header without
-    /// the charset.
+    // The merged trailers, or none before the body has been read.
+    fn trailers(&self) -> &[Header] {
+        self.trailers.get().map(|t| &t[..]).unwrap_or(&[])
+    }
+
+    // The names in the `Trailer` header, if the request asked for trailers
+    // with `TE: trailers`.
+    fn announced_trailers(&self) -> Vec<String> {
+        let asked = match &self.unit {
+            Some(unit) => unit
+                .all("te")
+                .iter()
+                .flat_map(|v| v.split(','))
+                .any(|t| t.trim().eq_ignore_ascii_case("trailers")),
+            None => false,
+        };
+        if !asked {
+            return vec![];
+        }
+        self.all("trailer")
+            .iter()
+            .flat_map(|v| v.split(','))
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+
+    /// The raw values of all `Set-Cookie` headers, in the order received.
+    ///
+    /// The values are not parsed, so a proxy can forward them verbatim to its
+    /// own client without losing attributes in a parse/serialize round trip.
     ///
     /// Example:
     ///
     /// ```
-    /// # fn main() -> Result<(), ureq::Error> {
-    /// # ureq::is_test(true);
-    /// let resp = ureq::get("http://example.com/").call()?;
-    /// assert!(matches!(resp.header("content-type"), Some("text/html; charset=ISO-8859-1")));
-    /// assert_eq!("text/html", resp.content_type());
-    /// # Ok(())
-    /// # }
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Set-Cookie: a=1; Path=/\r\n\
+    ///     Set-Cookie: b=2; HttpOnly\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    /// assert_eq!(resp.set_cookie_raw(), vec!["a=1; Path=/", "b=2; HttpOnly"]);
     /// ```
-    pub fn content_type(&self) -> &str {
-        self.header("content-type")
-            .map(|header| {
-                header
-                    .find(';')
-                    .map(|index| &header[0..index])
-                    .unwrap_or(header)
-            })
-            .unwrap_or(DEFAULT_CONTENT_TYPE)
+    pub fn set_cookie_raw(&self) -> Vec<&str> {
+        self.all("set-cookie")
     }
 
-    /// The character set part of the "Content-Type".
+    /// The authentication challenges in the `WWW-Authenticate` headers.
+    ///
+    /// Useful when handling a `401 Unauthorized` manually: each challenge names
+    /// a scheme the server accepts (like `Basic`, `Bearer` or `Digest`) and the
+    /// parameters needed to answer it. Challenges are returned in the order
+    /// they appear, across all `WWW-Authenticate` headers.
     ///
     /// Example:
     ///
     /// ```
-    /// # fn main() -> Result<(), ureq::Error> {
-    /// # ureq::is_test(true);
-    /// let resp = ureq::get("http://example.com/").call()?;
-    /// assert!(matches!(resp.header("content-type"), Some("text/html; charset=ISO-8859-1")));
-    /// assert_eq!("ISO-8859-1", resp.charset());
-    /// # Ok(())
-    /// # }
+    /// let s = "HTTP/1.1 401 Unauthorized\r\n\
+    ///     WWW-Authenticate: Bearer realm=\"api\", Basic realm=\"site\"\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    /// let schemes: Vec<_> = resp.www_authenticate()
+    ///     .iter()
+    ///     .map(|c| c.scheme().to_string())
+    ///     .collect();
+    ///
+    /// assert_eq!(schemes, vec!["Bearer", "Basic"]);
     /// ```
-    pub fn charset(&self) -> &str {
-        charset_from_content_type(self.header("content-type"))
+    pub fn www_authenticate(&self) -> Vec<AuthChallenge> {
+        self.all("www-authenticate")
+            .into_iter()
+            .flat_map(auth::parse_challenges)
+            .collect()
     }
 
-    /// Turn this response into a `impl Read` of the body.
-    ///
-    /// 1. If `Transfer-Encoding: chunked`, the returned reader will unchunk it
-    ///    and any `Content-Length` header is ignored.
-    /// 2. If `Content-Length` is set, the returned reader is limited to this byte
-    ///    length regardless of how many bytes the server sends.
-    /// 3. If no length header, the reader is until server stream end.
+    /// The parsed `Content-Disposition` header, if any. Download tools can
+    /// use it to pick a file name.
     ///
     /// Example:
     ///
     /// ```
-    /// use std::io::Read;
-    /// # fn main() -> Result<(), ureq::Error> {
-    /// # ureq::is_test(true);
-    /// let resp = ureq::get("http://httpbin.org/bytes/100")
-    ///     .call()?;
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Content-Disposition: attachment; filename*=UTF-8''%e2%82%ac%20rates.txt\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
     ///
-    /// assert!(resp.has("Content-Length"));
-    /// let len = resp.header("Content-Length")
-    ///     .and_then(|s| s.parse::<usize>().ok()).unwrap();
+    /// let filename = resp.content_disposition().and_then(|d| d.filename().map(String::from));
+    /// assert_eq!(filename.as_deref(), Some("€ rates.txt"));
+    /// ```
+    pub fn content_disposition(&self) -> Option<ContentDisposition> {
+        self.header("content-disposition")
+            .and_then(disposition::parse_content_disposition)
+    }
+
+    /// The parsed `Strict-Transport-Security` header, if any. Only the first
+    /// such header counts, and `None` is returned if it's malformed.
     ///
-    /// let mut bytes: Vec<u8> = Vec::with_capacity(len);
-    /// resp.into_reader()
-    ///     .read_to_end(&mut bytes)?;
+    /// Example:
     ///
-    /// assert_eq!(bytes.len(), len);
-    /// # Ok(())
-    /// # }
     /// ```
-    pub fn into_reader(self) -> impl Read + Send {
-        //
-        let is_http10 = self.http_version().eq_ignore_ascii_case("HTTP/1.0");
-        let is_close = self
-            .header("connection")
-            .map(|c| c.eq_ignore_ascii_case("close"))
-            .unwrap_or(false);
-
-        let is_head = (&self.unit).as_ref().map(|u| u.is_head()).unwrap_or(false);
-        let has_no_body = is_head
-            || match self.status {
-                204 | 304 => true,
-                _ => false,
-            };
-
-        let is_chunked = self
-            .header("transfer-encoding")
-            .map(|enc| !enc.is_empty()) // whatever it says, do chunked
-            .unwrap_or(false);
-
-        let use_chunked = !is_http10 && !has_no_body && is_chunked;
-
-        let limit_bytes = if is_http10 || is_close {
-            None
-        } else if has_no_body {
-            // head requests never have a body
-            Some(0)
-        } else {
-            self.header("content-length")
-                .and_then(|l| l.parse::<usize>().ok())
-        };
-
-        let stream = self.stream;
-        let unit = self.unit;
-        if let Some(unit) = &unit {
-            let result = stream.set_read_timeout(unit.agent.config.timeout_read);
-            if let Err(e) = result {
-                return Box::new(ErrorReader(e)) as Box<dyn Read + Send>;
-            }
-        }
-        let deadline = unit.as_ref().and_then(|u| u.deadline);
-        let stream = DeadlineStream::new(stream, deadline);
-
-        match (use_chunked, limit_bytes) {
-            (true, _) => Box::new(PoolReturnRead::new(unit, ChunkDecoder::new(stream))),
-            (false, Some(len)) => {
-                Box::new(PoolReturnRead::new(unit, LimitedRead::new(stream, len)))
-            }
-            (false, None) => Box::new(stream),
-        }
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Strict-Transport-Security: max-age=63072000; includeSubDomains; preload\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    ///
+    /// let hsts = resp.hsts().unwrap();
+    /// assert_eq!(hsts.max_age().as_secs(), 63072000);
+    /// assert!(hsts.include_subdomains());
+    /// ```
+    pub fn hsts(&self) -> Option<Hsts> {
+        self.header("strict-transport-security")
+            .and_then(hsts::parse_hsts)
     }
 
-    /// Turn this response into a String of the response body. By default uses `utf-8`,
-    /// but can work with charset, see below.
-    ///
-    /// This is potentially memory inefficient for large bodies since the
-    /// implementation first reads the reader to end into a `Vec<u8>` and then
-    /// attempts to decode it using the charset.
+    /// The alternative services advertised in the `Alt-Svc` headers, such as
+    /// an HTTP/3 endpoint of the same server. Multiple headers are combined.
     ///
     /// Example:
     ///
     /// ```
-    /// # fn main() -> Result<(), ureq::Error> {
-    /// # ureq::is_test(true);
-    /// let text = ureq::get("http://httpbin.org/get/success")
-    ///     .call()?
-    ///     .into_string()?;
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Alt-Svc: h3=\":443\"; ma=2592000\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
     ///
-    /// assert!(text.contains("success"));
-    /// # Ok(())
-    /// # }
+    /// let h3 = resp.alt_svc().into_iter().find(|alt| alt.protocol() == "h3");
+    /// assert_eq!(h3.map(|alt| alt.port()), Some(443));
     /// ```
+    pub fn alt_svc(&self) -> Vec<AltService> {
+        self.all("alt-svc")
+            .into_iter()
+            .flat_map(altsvc::parse_alt_svc)
+            .collect()
+    }
+
+    /// The elements of the `Forwarded` headers, one per proxy the message
+    /// passed, in the order they were added. Multiple headers are combined.
     ///
-    /// ## Charset support
+    /// Example:
     ///
-    /// If you enable feature `ureq = { version = "*", features = ["charset"] }`, into_string()
-    /// attempts to respect the character encoding of the `Content-Type` header. If there is no
-    /// Content-Type header, or the Content-Type header does not specify a charset, into_string()
-    /// uses `utf-8`.
+    /// ```
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Forwarded: for=192.0.2.60;proto=https, for=198.51.100.17\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
     ///
-    /// I.e. `Content-Length: text/plain; charset=iso-8859-1` would be decoded in latin-1.
-    ///
-    pub fn into_string(self) -> io::Result<String> {
-        #[cfg(feature = "charset")]
-        {
-            let encoding = Encoding::for_label(self.charset().as_bytes())
-                .or_else(|| Encoding::for_label(DEFAULT_CHARACTER_SET.as_bytes()))
-                .unwrap();
-            let mut buf: Vec<u8> = vec![];
-            self.into_reader().read_to_end(&mut buf)?;
-            let (text, _, _) = encoding.decode(&buf);
-            Ok(text.into_owned())
-        }
-        #[cfg(not(feature = "charset"))]
-        {
-            let mut buf: Vec<u8> = vec![];
-            self.into_reader().read_to_end(&mut buf)?;
-            Ok(String::from_utf8_lossy(&buf).to_string())
-        }
+    /// let hops = resp.forwarded();
+    /// let clients: Vec<_> = hops.iter().filter_map(|f| f.for_node()).collect();
+    /// assert_eq!(clients, vec!["192.0.2.60", "198.51.100.17"]);
+    /// ```
+    pub fn forwarded(&self) -> Vec<ForwardedEntry> {
+        self.all("forwarded")
+            .into_iter()
+            .flat_map(forwarded::parse_forwarded)
+            .collect()
     }
 
-    /// Read the body of this response into a serde_json::Value, or any other type that
-    // implements the [serde::Deserialize] trait.
+    /// The addresses in the `X-Forwarded-For` headers, the legacy form of
+    /// `Forwarded: for=`. The first is the original client, followed by the
+    /// proxies on the way. Multiple headers are combined.
     ///
-    /// You must use either a type annotation as shown below (`message: Message`), or the
-    /// [turbofish operator] (`::<Type>`) so Rust knows what type you are trying to read.
+    /// Example:
     ///
-    /// [turbofish operator]: https://matematikaadit.github.io/posts/rust-turbofish.html
+    /// ```
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     X-Forwarded-For: 203.0.113.195, 70.41.3.18\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
     ///
-    /// Requires feature `ureq = { version = "*", features = ["json"] }`
+    /// assert_eq!(resp.x_forwarded_for(), vec!["203.0.113.195", "70.41.3.18"]);
+    /// ```
+    pub fn x_forwarded_for(&self) -> Vec<&str> {
+        self.header_list("x-forwarded-for")
+    }
+
+    /// The protocol in the `X-Forwarded-Proto` header, the legacy form of
+    /// `Forwarded: proto=`. If proxies appended to it, the first, original,
+    /// protocol.
+    pub fn x_forwarded_proto(&self) -> Option<&str> {
+        self.header_list("x-forwarded-proto").first().cloned()
+    }
+
+    /// The directives of the `Cache-Control` headers. Multiple headers are
+    /// combined.
     ///
     /// Example:
     ///
     /// ```
-    /// # fn main() -> Result<(), ureq::Error> {
-    /// # ureq::is_test(true);
-    /// use serde::{Deserialize, de::DeserializeOwned};
-    ///
-    /// #[derive(Deserialize)]
-    /// struct Message {
-    ///     hello: String,
-    /// }
-    ///
-    /// let message: Message =
-    ///     ureq::get("http://example.com/hello_world.json")
-    ///         .call()?
-    ///         .into_json()?;
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Cache-Control: no-cache, max-age=0\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
     ///
-    /// assert_eq!(message.hello, "world");
-    /// # Ok(())
-    /// # }
+    /// assert!(resp.cache_control().no_cache());
     /// ```
+    pub fn cache_control(&self) -> CacheControl {
+        cache_control::parse_cache_control(self.all("cache-control"))
+    }
+
+    /// How long the response has been in a cache, from the `Age` header.
+    /// None if the header is missing or not a number of seconds.
     ///
-    /// Or, if you don't want to define a struct to read your JSON into, you can
-    /// use the convenient `serde_json::Value` type to parse arbitrary or unknown
-    /// JSON.
+    /// Example:
     ///
     /// ```
-    /// # fn main() -> Result<(), ureq::Error> {
-    /// # ureq::is_test(true);
-    /// let json: serde_json::Value = ureq::get("http://example.com/hello_world.json")
-    ///     .call()?
-    ///     .into_json()?;
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Age: 120\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
     ///
-    /// assert_eq!(json["hello"], "world");
-    /// # Ok(())
-    /// # }
+    /// assert_eq!(resp.age().unwrap().as_secs(), 120);
     /// ```
-    #[cfg(feature = "json")]
-    pub fn into_json<T: DeserializeOwned>(self) -> io::Result<T> {
-        use crate::stream::io_err_timeout;
-        use std::error::Error;
-
-        let reader = self.into_reader();
-        serde_json::from_reader(reader).map_err(|e| {
-            // This is to unify TimedOut io::Error in the API.
-            // We make a clone of the original error since serde_json::Error doesn't
-            // let us get the wrapped error instance back.
-            if let Some(ioe) = e.source().and_then(|s| s.downcast_ref::<io::Error>()) {
-                if ioe.kind() == io::ErrorKind::TimedOut {
-                    return io_err_timeout(ioe.to_string());
-                }
-            }
+    pub fn age(&self) -> Option<Duration> {
+        self.header("age")?
+            .trim()
+            .parse()
+            .ok()
+            .map(Duration::from_secs)
+    }
 
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Failed to read JSON: {}", e),
-            )
-        })
+    /// Typed values attached to the [Request](crate::Request) that produced
+    /// this response, or added to the response since.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
     }
 
-    // Returns an iterator across the redirect history of this response,
-    // if any. The iterator starts with the response before this one.
-    // If this response was not redirected, the iterator is empty.
-    pub(crate) fn history(&self) -> Hist {
-        Hist::new(self.previous.as_deref())
+    /// Mutable access to the typed values attached to this response.
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
     }
 
-    /// Create a response from a Read trait impl.
+    /// How long it took from sending the request until the first byte of
+    /// the response arrived, including any interim responses.
     ///
-    /// This is hopefully useful for unit tests.
+    /// This is only recorded when the agent was built with
+    /// [`timing(true)`](crate::AgentBuilder::timing), and is None otherwise.
+    /// For a redirected request, it's the time of the last request.
+    pub fn time_to_first_byte(&self) -> Option<Duration> {
+        self.time_to_first_byte
+    }
+
+    /// The value of `Access-Control-Allow-Origin`: either a single origin,
+    /// `*` or `null`.
+    pub fn cors_allow_origin(&self) -> Option<&str> {
+        self.header("access-control-allow-origin")
+    }
+
+    /// The methods listed in `Access-Control-Allow-Methods`, as sent by the
+    /// server. Multiple headers are combined.
     ///
     /// Example:
     ///
-    /// use std::io::Cursor;
-    ///
-    /// let text = "HTTP/1.1 401 Authorization Required\r\n\r\nPlease log in\n";
-    /// let read = Cursor::new(text.to_string().into_bytes());
-    /// let resp = ureq::Response::do_from_read(read);
+    /// ```
+    /// let s = "HTTP/1.1 204 No Content\r\n\
+    ///     Access-Control-Allow-Origin: https://app.example.com\r\n\
+    ///     Access-Control-Allow-Methods: GET, POST,PUT\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
     ///
-    /// assert_eq!(resp.status(), 401);
-    pub(crate) fn do_from_stream(stream: Stream, unit: Option<Unit>) -> Result<Response, Error> {
-        //
-        // HTTP/1.1 200 OK\r\n
-        let mut stream =
-            stream::DeadlineStream::new(stream, unit.as_ref().and_then(|u| u.deadline.clone()));
-        let status_line = read_next_line(&mut stream)?;
+    /// assert_eq!(resp.cors_allow_origin(), Some("https://app.example.com"));
+    /// assert_eq!(resp.cors_allow_methods(), vec!["GET", "POST", "PUT"]);
+    /// ```
+    pub fn cors_allow_methods(&self) -> Vec<&str> {
+        self.header_list("access-control-allow-methods")
+    }
 
-        let (index, status) = parse_status_line(status_line.as_str())?;
+    /// The headers listed in `Access-Control-Allow-Headers`, as sent by the
+    /// server. Multiple headers are combined.
+    pub fn cors_allow_headers(&self) -> Vec<&str> {
+        self.header_list("access-control-allow-headers")
+    }
 
-        let mut headers: Vec<Header> = Vec::new();
-        loop {
-            let line = read_next_line(&mut stream)?;
-            if line.is_empty() {
-                break;
+    /// The request header names listed in `Vary`, lowercased. Multiple
+    /// headers are combined, and each name is listed once.
+    ///
+    /// A cache must only reuse this response for requests that have the same
+    /// values for these headers. If the list contains `*`, the response
+    /// depends on more than headers and can't be reused at all; then `*` is
+    /// the only element returned.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Vary: Accept-Encoding, Accept-Language\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    ///
+    /// assert_eq!(resp.vary(), vec!["accept-encoding", "accept-language"]);
+    /// ```
+    pub fn vary(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for name in self.header_list("vary") {
+            if name == "*" {
+                return vec![name.to_string()];
             }
-            if let Ok(header) = line.as_str().parse::<Header>() {
-                headers.push(header);
+            let name = name.to_ascii_lowercase();
+            if !names.contains(&name) {
+                names.push(name);
             }
         }
-
-        Ok(Response {
-            url: None,
-            status_line,
-            index,
-            status,
-            headers,
-            unit,
-            stream: stream.into(),
-            previous: None,
-        })
+        names
     }
 
-    pub(crate) fn do_from_request(
-        unit: Unit,
-        stream: Stream,
-        previous: Option<Arc<Response>>,
-    ) -> Result<Response, Error> {
-        let url = Some(unit.url.clone());
-        let mut resp = Response::do_from_stream(stream, Some(unit))?;
-        resp.previous = previous;
-        resp.url = url;
-        Ok(resp)
+    /// The language tags in `Content-Language`, like `en` or `de-CH`, for
+    /// the languages of the intended audience of the body. Multiple headers
+    /// are combined, and the tags are returned as sent.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Content-Language: en, fr\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    ///
+    /// assert_eq!(resp.content_language(), vec!["en", "fr"]);
+    /// ```
+    pub fn content_language(&self) -> Vec<String> {
+        self.header_list("content-language")
+            .into_iter()
+            .map(|tag| tag.to_string())
+            .collect()
     }
 
-    #[cfg(test)]
-    pub fn to_write_vec(self) -> Vec<u8> {
-        self.stream.to_write_vec()
+    // The comma separated elements of all headers with the given name.
+    fn header_list(&self, name: &str) -> Vec<&str> {
+        self.all(name)
+            .into_iter()
+            .flat_map(|v| v.split(','))
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .collect()
     }
 
-    #[cfg(test)]
-    pub fn set_url(&mut self, url: Url) {
-        self.url = Some(url);
+    /// The content type part of the "Content-Type" header without
+    /// the charset.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/").call()?;
+    /// assert!(matches!(resp.header("content-type"), Some("text/html; charset=ISO-8859-1")));
+    /// assert_eq!("text/html", resp.content_type());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn content_type(&self) -> &str {
+        self.header("content-type")
+            .map(|header| {
+                header
+                    .find(';')
+                    .map(|index| &header[0..index])
+                    .unwrap_or(header)
+            })
+            .unwrap_or(DEFAULT_CONTENT_TYPE)
     }
 
-    #[cfg(test)]
-    pub fn set_previous(&mut self, previous: Arc<Response>) {
-        self.previous = Some(previous);
+    /// The character set part of the "Content-Type".
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/").call()?;
+    /// assert!(matches!(resp.header("content-type"), Some("text/html; charset=ISO-8859-1")));
+    /// assert_eq!("ISO-8859-1", resp.charset());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn charset(&self) -> &str {
+        charset_from_content_type(self.header("content-type"))
     }
-}
-
-/// parse a line like: HTTP/1.1 200 OK\r\n
-fn parse_status_line(line: &str) -> Result<(ResponseStatusIndex, u16), Error> {
-    //
 
-    if !line.is_ascii() {
-        return Err(BadStatus.msg("Status line not ASCII"));
-    }
-    // https://tools.ietf.org/html/rfc7230#section-3.1.2
-    //      status-line = HTTP-version SP status-code SP reason-phrase CRLF
-    let split: Vec<&str> = line.splitn(3, ' ').collect();
-    if split.len() != 3 {
-        return Err(BadStatus.msg("Wrong number of tokens in status line"));
+    /// Tells if the "Content-Type" is JSON: `application/json`, or any type with
+    /// a `+json` suffix, like `application/vnd.api+json`.
+    ///
+    /// Types are compared ignoring case, and parameters like the charset are
+    /// ignored. A response without a "Content-Type" header is neither JSON,
+    /// HTML nor text.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Content-Type: application/problem+json\r\n\
+    ///     \r\n";
+    /// let resp = s.parse::<ureq::Response>()?;
+    /// assert!(resp.is_json());
+    /// assert!(!resp.is_text());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_json(&self) -> bool {
+        let t = self.media_type();
+        t == "application/json" || t.ends_with("+json")
     }
 
-    // https://tools.ietf.org/html/rfc7230#appendix-B
-    //    HTTP-name = %x48.54.54.50 ; HTTP
-    //    HTTP-version = HTTP-name "/" DIGIT "." DIGIT
-    let http_version = split[0];
-    if !http_version.starts_with("HTTP/") {
-        return Err(BadStatus.msg("HTTP version did not start with HTTP/"));
-    }
-    if http_version.len() != 8 {
-        return Err(BadStatus.msg("HTTP version was wrong length"));
-    }
-    if !http_version.as_bytes()[5].is_ascii_digit() || !http_version.as_bytes()[7].is_ascii_digit()
-    {
-        return Err(BadStatus.msg("HTTP version did not match format"));
+    /// Tells if the "Content-Type" is `text/html` or `application/xhtml+xml`.
+    ///
+    /// See [`is_json()`](#method.is_json) for how the type is compared.
+    pub fn is_html(&self) -> bool {
+        let t = self.media_type();
+        t == "text/html" || t == "application/xhtml+xml"
     }
 
-    let status_str: &str = split[1];
-    //      status-code    = 3DIGIT
-    if status_str.len() != 3 {
-        return Err(BadStatus.msg("Status code was wrong length"));
+    /// Tells if the "Content-Type" is a `text/*` type, like `text/plain`,
+    /// `text/csv` or `text/html`.
+    ///
+    /// See [`is_json()`](#method.is_json) for how the type is compared.
+    pub fn is_text(&self) -> bool {
+        self.media_type().starts_with("text/")
     }
 
-    let status: u16 = status_str.parse().map_err(|_| BadStatus.new())?;
-
-    Ok((
-        ResponseStatusIndex {
-            http_version: http_version.len(),
-            response_code: http_version.len() + status_str.len(),
-        },
-        status,
-    ))
-}
+    // The type/subtype of the Content-Type header, lowercased, or "" if
+    // there's no header.
+    fn media_type(&self) -> String {
+        let header = self.header("content-type").unwrap_or("");
+        let media_type = header.split(';').next().unwrap_or("");
+        media_type.trim().to_ascii_lowercase()
+    }
 
-impl FromStr for Response {
-    type Err = Error;
-    /// Parse a response from a string.
+    /// Tells if the response is known to have an empty body, without reading it.
+    ///
+    /// This is the case for responses to `HEAD` requests, `204 No Content` and
+    /// `304 Not Modified` responses, and responses with `Content-Length: 0`.
+    /// Callers can use this to skip [`into_string()`](#method.into_string) or
+    /// similar entirely.
     ///
     /// Example:
+    ///
     /// ```
-    /// let s = "HTTP/1.1 200 OK\r\n\
-    ///     X-Forwarded-For: 1.2.3.4\r\n\
-    ///     Content-Type: text/plain\r\n\
-    ///     \r\n\
-    ///     Hello World!!!";
-    /// let resp = s.parse::<ureq::Response>().unwrap();
-    /// assert!(resp.has("X-Forwarded-For"));
-    /// let body = resp.into_string().unwrap();
-    /// assert_eq!(body, "Hello World!!!");
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::Response::new(204, "No Content", "")?;
+    /// assert!(resp.has_empty_body());
+    /// # Ok(())
+    /// # }
     /// ```
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let stream = Stream::from_vec(s.as_bytes().to_owned());
-        Self::do_from_stream(stream, None)
+    pub fn has_empty_body(&self) -> bool {
+        self.body_framing()
+            .map(|f| !f.chunked && f.limit_bytes == Some(0))
+            .unwrap_or(false)
+    }
+
+    // Decide how the body is delimited and encoded.
+    fn body_framing(&self) -> Result<BodyFraming, Error> {
+        let is_http10 = self.http_version().eq_ignore_ascii_case("HTTP/1.0");
+        let is_close = self
+            .header("connection")
+            .map(|c| c.eq_ignore_ascii_case("close"))
+            .unwrap_or(false);
+
+        let is_head = (&self.unit).as_ref().map(|u| u.is_head()).unwrap_or(false);
+        let has_no_body = is_head
+            || match self.status {
+                204 | 304 => true,
+                _ => false,
+            };
+
+        // Transfer-Encoding is a HTTP/1.1 thing, and irrelevant if there is no body.
+        let transfer_encoding = if is_http10 || has_no_body {
+            None
+        } else {
+            self.header("transfer-encoding")
+                .map(parse_transfer_encoding)
+                .transpose()?
+        };
+        let (codings, chunked) = transfer_encoding.unwrap_or((vec![], false));
+
+        let limit_bytes = if has_no_body {
+            // head requests never have a body
+            Some(0)
+        } else if is_http10 || is_close || chunked || !codings.is_empty() {
+            // https://tools.ietf.org/html/rfc7230#section-3.3.3
+            // If a Transfer-Encoding is present, it overrides Content-Length.
+            // If the final coding isn't chunked, read until the server closes.
+            None
+        } else {
+            self.header("content-length")
+                .and_then(|l| l.parse::<usize>().ok())
+        };
+
+        Ok(BodyFraming {
+            chunked,
+            codings,
+            limit_bytes,
+        })
+    }
+
+    /// Turn this response into a `impl Read` of the body.
+    ///
+    /// 1. If `Transfer-Encoding: chunked`, the returned reader will unchunk it
+    ///    and any `Content-Length` header is ignored.
+    /// 2. If `Content-Length` is set, the returned reader is limited to this byte
+    ///    length regardless of how many bytes the server sends.
+    /// 3. If no length header, the reader is until server stream end.
+    ///
+    /// Any other transfer codings, like `Transfer-Encoding: gzip, chunked`, are
+    /// decoded in order. This requires the `gzip` feature; responses with transfer
+    /// codings we can't decode are rejected with a `BadHeader` error.
+    ///
+    /// With the `gzip` feature, a body sent with `Content-Encoding: gzip` or
+    /// `deflate` is decoded too, unless the agent's
+    /// [`should_decompress`](crate::AgentBuilder::should_decompress) says otherwise.
+    /// Other content codings are left as they are.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// assert!(resp.has("Content-Length"));
+    /// let len = resp.header("Content-Length")
+    ///     .and_then(|s| s.parse::<usize>().ok()).unwrap();
+    ///
+    /// let mut bytes: Vec<u8> = Vec::with_capacity(len);
+    /// resp.into_reader()
+    ///     .read_to_end(&mut bytes)?;
+    ///
+    /// assert_eq!(bytes.len(), len);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_reader(self) -> impl Read + Send {
+        self.into_boxed_reader()
+    }
+
+    fn into_boxed_reader(self) -> Box<dyn Read + Send> {
+        self.into_tracked_reader(Arc::default(), Arc::default(), CallDeadline::default())
+    }
+
+    // Like into_boxed_reader, but sets `ended` once the body has been read up to
+    // the end its framing promised, and `reusable` if the connection can then
+    // go back to the pool.
+    fn into_tracked_reader(
+        self,
+        ended: Arc<AtomicBool>,
+        reusable: Arc<AtomicBool>,
+        call_deadline: CallDeadline,
+    ) -> Box<dyn Read + Send> {
+        //
+        if let Some(body) = self.body {
+            return Box::new(EndRead::new(Cursor::new(body), ended)) as Box<dyn Read + Send>;
+        }
+
+        let framing = match self.body_framing() {
+            Ok(framing) => framing,
+            Err(e) => {
+                let e = io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+                return Box::new(ErrorReader(e)) as Box<dyn Read + Send>;
+            }
+        };
+        #[cfg(feature = "gzip")]
+        let content_coding = self.content_coding();
+        #[cfg(feature = "gzip")]
+        let sniff = self.sniffs_encoding();
+
+        let announced = self.announced_trailers();
+        let trailers = self.trailers;
+        let stream = self.stream;
+        let unit = self.unit;
+        if let Some(unit) = &unit {
+            let result = stream.set_read_timeout(unit.agent.config.timeout_read);
+            if let Err(e) = result {
+                return Box::new(ErrorReader(e)) as Box<dyn Read + Send>;
+            }
+        }
+        let deadline = unit.as_ref().and_then(|u| u.deadline);
+        let clock = unit.as_ref().map(|u| u.clock()).unwrap_or_default();
+        let timeout_read = unit.as_ref().and_then(|u| u.agent.config.timeout_read);
+        let stream = DeadlineStream::new(stream, deadline, clock)
+            .with_call_deadline(call_deadline, timeout_read);
+
+        let framed = framing.chunked || framing.limit_bytes.is_some();
+        let body_reader: Box<dyn Read + Send> = match (framing.chunked, framing.limit_bytes) {
+            (true, _) => {
+                let mut keep = unit
+                    .as_ref()
+                    .map(|u| u.agent.config.merge_trailers.clone())
+                    .unwrap_or_default();
+                keep.extend(announced);
+                let decoder = ChunkDecoder::new(stream, keep, trailers);
+                Box::new(PoolReturnRead::new(unit, decoder, reusable))
+            }
+            (false, Some(len)) => {
+                let reader = LimitedRead::new(stream, len);
+                Box::new(PoolReturnRead::new(unit, reader, reusable))
+            }
+            (false, None) => Box::new(stream),
+        };
+        let body_reader: Box<dyn Read + Send> = Box::new(EndRead::new(body_reader, ended));
+
+        // Transfer codings are listed in the order they were applied, so undo
+        // them in reverse.
+        let reader = framing
+            .codings
+            .into_iter()
+            .rev()
+            .fold(body_reader, |reader, coding| coding.decode(reader, framed));
+
+        // The content coding is part of the representation, and is undone
+        // after the transfer codings.
+        #[cfg(feature = "gzip")]
+        let reader = match content_coding {
+            Some(coding) => coding.decode(reader, framed),
+            None if sniff => Box::new(SniffRead::new(reader, framed)),
+            None => reader,
+        };
+
+        reader
+    }
+
+    /// The content coding that is undone when reading the body: `"gzip"` or
+    /// `"deflate"`, or `None` if the body is read as it was received.
+    ///
+    /// The `Content-Encoding` header is left in place when the body is
+    /// decoded, and this tells whether that happens, for instance to measure
+    /// bandwidth saved by compression. It's always `None` without the `gzip`
+    /// feature, and for requests made with
+    /// [`Request::no_decompress`](crate::Request::no_decompress).
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/")
+    ///     .set("Accept-Encoding", "gzip")
+    ///     .call()?;
+    ///
+    /// if let Some(coding) = resp.content_encoding_used() {
+    ///     println!("body was sent with {}", coding);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn content_encoding_used(&self) -> Option<&str> {
+        #[cfg(feature = "gzip")]
+        let coding = self.content_coding().map(TransferCoding::name);
+        #[cfg(not(feature = "gzip"))]
+        let coding = None;
+        coding
+    }
+
+    /// The `Content-Encoding` to decode when reading the body, if any.
+    #[cfg(feature = "gzip")]
+    fn content_coding(&self) -> Option<TransferCoding> {
+        let coding = match self.header("content-encoding")?.trim() {
+            c if c.eq_ignore_ascii_case("gzip") || c.eq_ignore_ascii_case("x-gzip") => {
+                TransferCoding::Gzip
+            }
+            c if c.eq_ignore_ascii_case("deflate") => TransferCoding::Deflate,
+            _ => return None,
+        };
+        let filter = self
+            .unit
+            .as_ref()
+            .and_then(|u| u.agent.config.should_decompress.as_ref());
+        match filter {
+            Some(filter) if !filter.allows(self.content_type()) => None,
+            _ => Some(coding),
+        }
+    }
+
+    /// A copy of the status line, headers and url of this response, without
+    /// the body.
+    ///
+    /// The copy can be kept, for instance for logging, after the response is
+    /// consumed by reading its body.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/").call()?;
+    /// let meta = resp.metadata();
+    ///
+    /// let body = resp.into_string()?;
+    /// println!("{} {} bytes", meta.status(), body.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn metadata(&self) -> ResponseHead {
+        ResponseHead {
+            url: self.url.clone(),
+            status_line: self.status_line.clone(),
+            index: self.index,
+            status: self.status,
+            headers: self.headers.clone(),
+            trailers: self.trailers.clone(),
+        }
+    }
+
+    /// Whether to look for a content coding in the body itself, see
+    /// [`AgentBuilder::sniff_encoding`](crate::AgentBuilder::sniff_encoding).
+    #[cfg(feature = "gzip")]
+    fn sniffs_encoding(&self) -> bool {
+        let config = match &self.unit {
+            Some(unit) => &unit.agent.config,
+            None => return false,
+        };
+        if !config.sniff_encoding || self.has("content-encoding") {
+            return false;
+        }
+        match &config.should_decompress {
+            Some(filter) => filter.allows(self.content_type()),
+            None => true,
+        }
+    }
+
+    /// Split this response into its head (status and headers) and its body.
+    ///
+    /// The two parts are owned and can be handled independently, for instance
+    /// by a proxy that inspects the head before deciding where to stream the
+    /// body. The [BodyReader] reads the body like
+    /// [`into_reader()`](#method.into_reader).
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// let (head, mut body) = resp.split();
+    /// assert_eq!(head.status(), 200);
+    ///
+    /// let mut bytes = vec![];
+    /// body.read_to_end(&mut bytes)?;
+    /// assert_eq!(bytes.len(), 100);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn split(self) -> (ResponseHead, BodyReader) {
+        let head = self.metadata();
+        let ended = Arc::new(AtomicBool::new(false));
+        let reusable = Arc::new(AtomicBool::new(false));
+        let clock = self.unit.as_ref().map(|u| u.clock()).unwrap_or_default();
+        let call_deadline = CallDeadline::default();
+        let reader =
+            self.into_tracked_reader(ended.clone(), reusable.clone(), call_deadline.clone());
+        let body = BodyReader {
+            reader: io::BufReader::new(reader),
+            eof: false,
+            ended,
+            reusable,
+            clock,
+            call_timeout: None,
+            call_deadline,
+        };
+        (head, body)
+    }
+
+    /// Turn this response into a `impl Read` of the body, that also writes every
+    /// byte read to `sink`.
+    ///
+    /// This lets you stream a response to its consumer while persisting a copy
+    /// of it, for instance to a cache on disk, in a single pass. The body is
+    /// decoded the same way as for [`into_reader()`](#method.into_reader).
+    /// An error writing to the sink is returned as an error reading.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// let mut copy = vec![];
+    /// let mut bytes = vec![];
+    /// resp.into_reader_tee(&mut copy)
+    ///     .read_to_end(&mut bytes)?;
+    ///
+    /// assert_eq!(bytes, copy);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_reader_tee<W: Write>(self, sink: W) -> impl Read {
+        TeeRead {
+            reader: self.into_reader(),
+            sink,
+        }
+    }
+
+    /// Turn this response into a `impl Read` of the body, that reads no faster
+    /// than the rate of `limit`.
+    ///
+    /// The rate is looked up on every read, so it can be changed through a
+    /// clone of the handle while the body is being read, for instance to slow
+    /// down when a downstream buffer fills up. A rate of 0 pauses reading
+    /// until the rate is raised again.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// use ureq::RateLimitHandle;
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// let limit = RateLimitHandle::new(1024 * 1024);
+    /// let mut reader = resp.into_reader_rate_limited(&limit);
+    ///
+    /// let mut bytes = vec![];
+    /// reader.read_to_end(&mut bytes)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_reader_rate_limited(self, limit: &RateLimitHandle) -> impl Read + Send {
+        RateLimitRead {
+            reader: self.into_reader(),
+            limit: limit.clone(),
+            rate: 0,
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    /// Turn this response into a `impl Read` of the body, that checks the body
+    /// against an expected checksum.
+    ///
+    /// The digest is computed as the body is read. Once the body is read to the
+    /// end, a mismatch is reported as an error of kind `InvalidData` instead of
+    /// the final `Ok(0)`. This lets you verify a download in a single streaming
+    /// pass, but note that the mismatching data has already been handed out by
+    /// then.
+    ///
+    /// Requires feature `ureq = { version = "*", features = ["checksum"] }`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// use ureq::Checksum;
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// let expected = Checksum::Sha256(
+    ///     "cd00e292c5970d3c5e2f0ffa5171e555bc46bfc4faddfb4a418b6840b86e79a3".into());
+    /// let mut bytes = vec![];
+    /// resp.into_reader_checksum(expected)
+    ///     .read_to_end(&mut bytes)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "checksum")]
+    pub fn into_reader_checksum(self, expected: Checksum) -> impl Read + Send {
+        ChecksumRead {
+            reader: self.into_reader(),
+            hasher: Sha256::new(),
+            expected,
+            done: false,
+        }
+    }
+
+    /// Read the body and check it against the `Content-MD5` header.
+    ///
+    /// Some storage APIs send the base64 encoded MD5 digest of the body in a
+    /// `Content-MD5` header (see [RFC 1864](https://tools.ietf.org/html/rfc1864)).
+    /// The digest is of the body as sent, so this is meant for bodies without
+    /// a content coding. Returns the body if the digests match, and an error
+    /// of kind `InvalidData` if they don't or the header is missing.
+    ///
+    /// Requires feature `ureq = { version = "*", features = ["checksum"] }`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Content-MD5: XrY7u+Ae7tCTyyK7j1rNww==\r\n\
+    ///     Content-Length: 11\r\n\
+    ///     \r\n\
+    ///     hello world";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    ///
+    /// let body = resp.verify_content_md5().unwrap();
+    /// assert_eq!(body, b"hello world");
+    /// ```
+    #[cfg(feature = "checksum")]
+    pub fn verify_content_md5(self) -> io::Result<Vec<u8>> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let expected = self
+            .header("content-md5")
+            .ok_or_else(|| invalid("no Content-MD5 header"))?;
+        let expected =
+            base64::decode(expected.trim()).map_err(|_| invalid("Content-MD5 is not base64"))?;
+
+        let mut body = vec![];
+        self.into_reader().read_to_end(&mut body)?;
+        if Md5::digest(&body)[..] != expected[..] {
+            return Err(invalid("body doesn't match Content-MD5"));
+        }
+        Ok(body)
+    }
+
+    /// Turn a `206 Partial Content` response into the byte ranges it holds.
+    ///
+    /// When a request asks for several ranges, like `Range: bytes=0-99,200-299`,
+    /// the server sends them as a `multipart/byteranges` body. The parts are
+    /// read from the body one at a time, each into memory. A response with a
+    /// single range and a `Content-Range` header is one part.
+    ///
+    /// Any other response yields a single `InvalidData` error.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let s = "HTTP/1.1 206 Partial Content\r\n\
+    ///     Content-Type: multipart/byteranges; boundary=SEP\r\n\
+    ///     \r\n\
+    ///     --SEP\r\n\
+    ///     Content-Range: bytes 0-4/20\r\n\
+    ///     \r\n\
+    ///     hello\r\n\
+    ///     --SEP\r\n\
+    ///     Content-Range: bytes 15-19/20\r\n\
+    ///     \r\n\
+    ///     world\r\n\
+    ///     --SEP--\r\n";
+    /// let resp = s.parse::<ureq::Response>()?;
+    ///
+    /// for part in resp.into_byte_ranges() {
+    ///     let part = part?;
+    ///     println!("{}-{}: {} bytes", part.start(), part.end(), part.body().len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_byte_ranges(self) -> impl Iterator<Item = io::Result<ByteRangePart>> + Send {
+        let boundary = self
+            .header("content-type")
+            .filter(|_| {
+                self.content_type()
+                    .eq_ignore_ascii_case("multipart/byteranges")
+            })
+            .and_then(|ct| media_type_param(ct, "boundary"));
+        if let Some(boundary) = boundary {
+            let reader = io::BufReader::new(self.into_reader());
+            return Box::new(ByteRanges::new(reader, &boundary))
+                as Box<dyn Iterator<Item = io::Result<ByteRangePart>> + Send>;
+        }
+
+        let range = self.header("content-range").and_then(parse_content_range);
+        let content_type = self.header("content-type").map(String::from);
+        Box::new(std::iter::once_with(move || {
+            let (start, end, complete_length) = range.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "not a byte range response")
+            })?;
+            let mut body = vec![];
+            self.into_reader().read_to_end(&mut body)?;
+            Ok(ByteRangePart::new(
+                content_type,
+                start,
+                end,
+                complete_length,
+                body,
+            ))
+        }))
+    }
+
+    /// Turn this response into an iterator over records of `record_size`
+    /// bytes each, for bodies made of fixed size records.
+    ///
+    /// The records are read from the body one at a time. If the body ends in
+    /// a partial record, it's the last item, shorter than the others, unless
+    /// [`Records::partial_is_error`] makes it an error instead.
+    ///
+    /// Panics if `record_size` is 0.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let s = "HTTP/1.1 200 OK\r\n\r\naaaabbbbcc";
+    /// let resp = s.parse::<ureq::Response>()?;
+    ///
+    /// let records: Vec<Vec<u8>> = resp.into_records(4).collect::<Result<_, _>>()?;
+    /// assert_eq!(records, vec![b"aaaa".to_vec(), b"bbbb".to_vec(), b"cc".to_vec()]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_records(self, record_size: usize) -> Records {
+        assert!(record_size > 0, "record_size must be greater than 0");
+        Records {
+            reader: self.into_boxed_reader(),
+            record_size,
+            partial_is_error: false,
+            done: false,
+        }
+    }
+
+    /// Read the body up to and including the first `delim` byte, but no more
+    /// than `max` bytes.
+    ///
+    /// This is handy for reading the first record of a line or delimiter
+    /// framed body. Whatever is read is returned, so the result doesn't end in
+    /// `delim` if the limit or the end of the body was hit first. The rest of
+    /// the body is discarded, and the connection isn't reused.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     \r\n\
+    ///     first line\nsecond line\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    ///
+    /// let line = resp.read_until(b'\n', 1024).unwrap();
+    /// assert_eq!(line, b"first line\n");
+    /// ```
+    pub fn read_until(self, delim: u8, max: usize) -> io::Result<Vec<u8>> {
+        let mut buf = vec![];
+        io::BufReader::new(self.into_reader())
+            .take(max as u64)
+            .read_until(delim, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Compare status, headers and body with another response, skipping the
+    /// headers named in `ignore_headers`.
+    ///
+    /// Meant for golden-file style tests, where headers like `Date` differ
+    /// from run to run. Header names are compared ignoring case, and the
+    /// remaining headers must appear in the same order. Both bodies are read
+    /// in full, and a body that fails to read never compares equal.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// let a = "HTTP/1.1 200 OK\r\n\
+    ///     Date: Mon, 01 Feb 2021 10:00:00 GMT\r\n\
+    ///     Content-Length: 5\r\n\
+    ///     \r\n\
+    ///     hello";
+    /// let b = "HTTP/1.1 200 OK\r\n\
+    ///     Date: Tue, 02 Feb 2021 12:00:00 GMT\r\n\
+    ///     Content-Length: 5\r\n\
+    ///     \r\n\
+    ///     hello";
+    /// let a = a.parse::<ureq::Response>().unwrap();
+    /// let b = b.parse::<ureq::Response>().unwrap();
+    ///
+    /// assert!(a.eq_ignoring(b, &["date"]));
+    /// ```
+    pub fn eq_ignoring(self, other: Response, ignore_headers: &[&str]) -> bool {
+        let kept = |resp: &Response| -> Vec<(String, String)> {
+            resp.headers
+                .iter()
+                .filter(|h| !ignore_headers.iter().any(|name| h.is_name(name)))
+                .map(|h| (h.name().to_ascii_lowercase(), h.value().to_string()))
+                .collect()
+        };
+        let same_head = self.status == other.status && kept(&self) == kept(&other);
+        if !same_head {
+            return false;
+        }
+        let mut body = vec![];
+        let mut other_body = vec![];
+        self.into_reader().read_to_end(&mut body).is_ok()
+            && other.into_reader().read_to_end(&mut other_body).is_ok()
+            && body == other_body
+    }
+
+    /// Copy the body into `writer`, reporting progress to `callback` at most
+    /// once every `interval`.
+    ///
+    /// For fast downloads this calls back far less often than once per
+    /// read. Returns the number of bytes copied, like [std::io::copy].
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// let mut bytes = vec![];
+    /// resp.copy_to_with_progress(&mut bytes, Duration::from_secs(1), |progress| {
+    ///     println!("{} bytes, {:.0} bytes/s", progress.bytes(), progress.bytes_per_second());
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn copy_to_with_progress<W: Write>(
+        self,
+        mut writer: W,
+        interval: Duration,
+        mut callback: impl FnMut(Progress),
+    ) -> io::Result<u64> {
+        let mut reader = self.into_reader();
+        let start = Instant::now();
+        let mut last_callback = start;
+        let mut bytes = 0;
+        let mut buf = [0; 8 * 1024];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => return Ok(bytes),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            writer.write_all(&buf[..n])?;
+            bytes += n as u64;
+
+            let now = Instant::now();
+            if now.duration_since(last_callback) >= interval {
+                last_callback = now;
+                callback(Progress {
+                    bytes,
+                    elapsed: now.duration_since(start),
+                });
+            }
+        }
+    }
+
+    /// Copy the body into `writer`, reading it `buf_size` bytes at a time.
+    ///
+    /// A large buffer means fewer reads and writes for high-throughput
+    /// downloads; a small one keeps memory use down. A `buf_size` of zero is
+    /// taken as one. Returns the number of bytes copied, like [std::io::copy].
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// let mut bytes = vec![];
+    /// let n = resp.copy_to_buffered(&mut bytes, 256 * 1024)?;
+    /// assert_eq!(n, 100);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn copy_to_buffered<W: Write>(self, mut writer: W, buf_size: usize) -> io::Result<u64> {
+        let mut reader = self.into_reader();
+        let mut bytes = 0;
+        let mut buf = vec![0; buf_size.max(1)];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => return Ok(bytes),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            writer.write_all(&buf[..n])?;
+            bytes += n as u64;
+        }
+    }
+
+    /// Turn this response into a String of the response body. By default uses `utf-8`,
+    /// but can work with charset, see below.
+    ///
+    /// This is potentially memory inefficient for large bodies since the
+    /// implementation first reads the reader to end into a `Vec<u8>` and then
+    /// attempts to decode it using the charset.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let text = ureq::get("http://httpbin.org/get/success")
+    ///     .call()?
+    ///     .into_string()?;
+    ///
+    /// assert!(text.contains("success"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Charset support
+    ///
+    /// If you enable feature `ureq = { version = "*", features = ["charset"] }`, into_string()
+    /// attempts to respect the character encoding of the `Content-Type` header. If there is no
+    /// Content-Type header, or the Content-Type header does not specify a charset, into_string()
+    /// uses `utf-8`.
+    ///
+    /// I.e. `Content-Length: text/plain; charset=iso-8859-1` would be decoded in latin-1.
+    ///
+    /// With the `charset` feature, a UTF-8, UTF-16LE or UTF-16BE byte order mark at the
+    /// start of the body takes precedence over the header charset, and is removed from
+    /// the returned string.
+    ///
+    pub fn into_string(self) -> io::Result<String> {
+        let (text, _) = self.into_string_checked()?;
+        Ok(text)
+    }
+
+    /// Like [`into_string()`](#method.into_string), but also tells whether the
+    /// body contained byte sequences that are malformed in its character encoding.
+    ///
+    /// Malformed sequences are replaced by U+FFFD REPLACEMENT CHARACTER in the
+    /// returned string either way; the `bool` is `true` if that happened at least
+    /// once, so callers that care about corrupted text can tell.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let (text, had_errors) = ureq::get("http://httpbin.org/get/success")
+    ///     .call()?
+    ///     .into_string_checked()?;
+    ///
+    /// assert!(!had_errors);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_string_checked(self) -> io::Result<(String, bool)> {
+        #[cfg(feature = "charset")]
+        {
+            let mut labels = vec![self.charset().to_string()];
+            if let Some(unit) = &self.unit {
+                labels.extend(unit.charset_fallback.iter().cloned());
+            }
+            self.decode_body(&labels)
+        }
+        #[cfg(not(feature = "charset"))]
+        {
+            let mut buf: Vec<u8> = vec![];
+            self.into_reader().read_to_end(&mut buf)?;
+            match String::from_utf8(buf) {
+                Ok(text) => Ok((text, false)),
+                Err(e) => Ok((String::from_utf8_lossy(e.as_bytes()).to_string(), true)),
+            }
+        }
+    }
+
+    /// Turn this response into a String of the response body, decoded with the
+    /// character encoding named by `label` instead of the one in the
+    /// `Content-Type` header.
+    ///
+    /// This is an escape hatch for servers that mislabel their content. The label
+    /// is looked up like a charset in a header, so `"latin1"`, `"iso-8859-1"` and
+    /// `"windows-1252"` all work. If the label is unknown, `utf-8` is used.
+    ///
+    /// Requires feature `ureq = { version = "*", features = ["charset"] }`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let text = ureq::get("http://httpbin.org/get/success")
+    ///     .call()?
+    ///     .into_string_with_encoding("iso-8859-1")?;
+    ///
+    /// assert!(text.contains("success"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "charset")]
+    pub fn into_string_with_encoding(self, label: &str) -> io::Result<String> {
+        let (text, _) = self.decode_body(&[label.to_string()])?;
+        Ok(text)
+    }
+
+    // Decode with the first of the labelled encodings that has no errors, or
+    // else the first known one. Unknown labels are skipped.
+    #[cfg(feature = "charset")]
+    fn decode_body(self, labels: &[String]) -> io::Result<(String, bool)> {
+        let mut encodings: Vec<_> = labels
+            .iter()
+            .filter_map(|label| Encoding::for_label(label.as_bytes()))
+            .collect();
+        if encodings.is_empty() {
+            encodings.extend(Encoding::for_label(DEFAULT_CHARACTER_SET.as_bytes()));
+        }
+        let mut buf: Vec<u8> = vec![];
+        self.into_reader().read_to_end(&mut buf)?;
+        // A byte order mark wins over the labels, and isn't part of the text.
+        if let Some((encoding, bom_len)) = Encoding::for_bom(&buf) {
+            encodings = vec![encoding];
+            buf.drain(..bom_len);
+        }
+        let mut first = None;
+        for encoding in encodings {
+            let (text, had_errors) = encoding.decode_without_bom_handling(&buf);
+            if !had_errors {
+                return Ok((text.into_owned(), false));
+            }
+            first.get_or_insert(text.into_owned());
+        }
+        Ok((first.unwrap_or_default(), true))
+    }
+
+    /// Read the body and decode it from base64.
+    ///
+    /// Some APIs, for instance for certificates and keys, send binary data
+    /// base64 encoded. Whitespace, including line breaks, is ignored. Both the
+    /// standard and the URL safe alphabet are accepted, with or without
+    /// padding. A body that isn't base64 is an error of kind
+    /// [`InvalidData`](std::io::ErrorKind::InvalidData).
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// let s = "HTTP/1.1 200 OK\r\n\r\naGVsbG8g\r\nd29ybGQ=\r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    ///
+    /// assert_eq!(resp.into_base64_bytes().unwrap(), b"hello world");
+    /// ```
+    pub fn into_base64_bytes(self) -> io::Result<Vec<u8>> {
+        let mut body = vec![];
+        self.into_reader().read_to_end(&mut body)?;
+        body.retain(|b| !b.is_ascii_whitespace());
+        let config = if body.iter().any(|b| *b == b'-' || *b == b'_') {
+            base64::URL_SAFE
+        } else {
+            base64::STANDARD
+        };
+        base64::decode_config(&body, config)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Read the body to the end, keeping only its last `n` bytes.
+    ///
+    /// This is for bodies that are too large to hold, like a log that's
+    /// still growing, when only the end is of interest. At most `n` bytes
+    /// are held in memory at any time, however long the body.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// let s = "HTTP/1.1 200 OK\r\n\r\nline 1\nline 2\nline 3\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    ///
+    /// assert_eq!(resp.tail(7).unwrap(), b"line 3\n");
+    /// ```
+    pub fn tail(self, n: usize) -> io::Result<Vec<u8>> {
+        let mut ring = VecDeque::with_capacity(n);
+        let mut reader = self.into_reader();
+        let mut buf = [0; 8 * 1024];
+        loop {
+            let len = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let keep = &buf[len.saturating_sub(n)..len];
+            let overflow = (ring.len() + keep.len()).saturating_sub(n);
+            ring.drain(..overflow);
+            ring.extend(keep);
+        }
+        Ok(ring.into())
+    }
+
+    /// Read the body of this response into a serde_json::Value, or any other type that
+    // implements the [serde::Deserialize] trait.
+    ///
+    /// You must use either a type annotation as shown below (`message: Message`), or the
+    /// [turbofish operator] (`::<Type>`) so Rust knows what type you are trying to read.
+    ///
+    /// [turbofish operator]: https://matematikaadit.github.io/posts/rust-turbofish.html
+    ///
+    /// Requires feature `ureq = { version = "*", features = ["json"] }`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// use serde::{Deserialize, de::DeserializeOwned};
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Message {
+    ///     hello: String,
+    /// }
+    ///
+    /// let message: Message =
+    ///     ureq::get("http://example.com/hello_world.json")
+    ///         .call()?
+    ///         .into_json()?;
+    ///
+    /// assert_eq!(message.hello, "world");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Or, if you don't want to define a struct to read your JSON into, you can
+    /// use the convenient `serde_json::Value` type to parse arbitrary or unknown
+    /// JSON.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// # ureq::is_test(true);
+    /// let json: serde_json::Value = ureq::get("http://example.com/hello_world.json")
+    ///     .call()?
+    ///     .into_json()?;
+    ///
+    /// assert_eq!(json["hello"], "world");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn into_json<T: DeserializeOwned>(self) -> io::Result<T> {
+        let reader = self.into_reader();
+        serde_json::from_reader(reader).map_err(json_error)
+    }
+
+    /// Read the body as JSON, or None if the body is empty.
+    ///
+    /// Many APIs answer a successful PUT or DELETE with `204 No Content`, or
+    /// a `200 OK` without a body, but send a JSON body in other cases. This
+    /// returns `Ok(None)` for an empty body, including one of only
+    /// whitespace, and otherwise parses it like
+    /// [`into_json()`](#method.into_json). Only a body that isn't valid JSON
+    /// is an error.
+    ///
+    /// Requires feature `ureq = { version = "*", features = ["json"] }`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// let s = "HTTP/1.1 204 No Content\r\n\r\n";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    ///
+    /// let json: Option<serde_json::Value> = resp.into_json_or_empty().unwrap();
+    /// assert_eq!(json, None);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn into_json_or_empty<T: DeserializeOwned>(self) -> io::Result<Option<T>> {
+        if self.has_empty_body() {
+            return Ok(None);
+        }
+        let mut body = vec![];
+        self.into_reader().read_to_end(&mut body)?;
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(None);
+        }
+        serde_json::from_slice(&body).map(Some).map_err(json_error)
+    }
+
+    /// Turn this response into a JSON deserializer over the body.
+    ///
+    /// This is a lower level alternative to [`into_json()`](#method.into_json)
+    /// for driving deserialization by hand, for instance with
+    /// [`into_iter()`](serde_json::Deserializer::into_iter) for a stream of
+    /// values, or with a custom visitor. The deserializer owns the body reader
+    /// and so the connection; it's returned to the pool once the body has been
+    /// read to the end. Unlike `into_json()`, errors are serde_json errors.
+    ///
+    /// Requires feature `ureq = { version = "*", features = ["json"] }`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # ureq::is_test(true);
+    /// let resp = ureq::get("http://example.com/hello_world.json")
+    ///     .call()?;
+    ///
+    /// for value in resp.json_deserializer().into_iter::<serde_json::Value>() {
+    ///     println!("{}", value?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn json_deserializer(
+        self,
+    ) -> serde_json::Deserializer<serde_json::de::IoRead<impl Read + Send>> {
+        serde_json::Deserializer::from_reader(self.into_reader())
+    }
+
+    /// Read the body as JSON and return the value at `pointer`.
+    ///
+    /// The pointer uses [JSON Pointer](https://tools.ietf.org/html/rfc6901)
+    /// syntax, like `/data/items/0/id`, and `""` is the whole document. If
+    /// nothing is at the pointer, an error of kind `InvalidData` is returned.
+    /// The whole body is parsed, but only the value at the pointer is kept.
+    ///
+    /// Requires feature `ureq = { version = "*", features = ["json"] }`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Content-Type: application/json\r\n\
+    ///     \r\n\
+    ///     {\"data\": {\"items\": [{\"id\": 17}]}}";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    ///
+    /// let id = resp.json_path("/data/items/0/id").unwrap();
+    /// assert_eq!(id, 17);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn json_path(self, pointer: &str) -> io::Result<serde_json::Value> {
+        let mut json: serde_json::Value = self.into_json()?;
+        json.pointer_mut(pointer).map(|v| v.take()).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("No JSON value at {:?}", pointer),
+            )
+        })
+    }
+
+    // Returns an iterator across the redirect history of this response,
+    // if any. The iterator starts with the response before this one.
+    // If this response was not redirected, the iterator is empty.
+    pub(crate) fn history(&self) -> Hist {
+        Hist::new(self.previous.as_deref())
+    }
+
+    /// Create a response from a Read trait impl.
+    ///
+    /// The reader must produce a full HTTP response: status line, headers and body.
+    /// Only the status line and headers are read here, the body is left in the
+    /// reader until [`into_reader()`](#method.into_reader) or similar is called.
+    ///
+    /// This is hopefully useful for unit tests, where the response can come from
+    /// a file or some other transport instead of a `&str`.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    ///
+    /// let text = "HTTP/1.1 401 Authorization Required\r\n\r\nPlease log in\n";
+    /// let read = Cursor::new(text.to_string().into_bytes());
+    /// let resp = ureq::Response::from_reader(read).unwrap();
+    ///
+    /// assert_eq!(resp.status(), 401);
+    /// ```
+    pub fn from_reader(reader: impl Read + Send + 'static) -> Result<Response, Error> {
+        Self::do_from_stream(Stream::from_read(reader), None)
+    }
+
+    pub(crate) fn do_from_stream(stream: Stream, unit: Option<Unit>) -> Result<Response, Error> {
+        //
+        // HTTP/1.1 200 OK\r\n
+        let mut stream = stream::DeadlineStream::new(
+            stream,
+            unit.as_ref().and_then(|u| u.deadline.clone()),
+            unit.as_ref().map(|u| u.clock()).unwrap_or_default(),
+        );
+        let max_status_line_length = unit
+            .as_ref()
+            .map(|u| u.agent.config.max_status_line_length)
+            .unwrap_or(DEFAULT_MAX_STATUS_LINE_LENGTH);
+
+        let time_to_first_byte = match unit.as_ref().and_then(|u| u.sent_at) {
+            Some(sent_at) => {
+                stream.fill_buf()?;
+                Some(sent_at.elapsed())
+            }
+            None => None,
+        };
+        let status_line = read_status_line(&mut stream, max_status_line_length)?;
+
+        let (index, status) = parse_status_line(status_line.as_str())?;
+
+        let mut headers: Vec<Header> = Vec::new();
+        loop {
+            let line = read_next_line(&mut stream)?;
+            if line.is_empty() {
+                break;
+            }
+            match line.as_str().parse::<Header>() {
+                Ok(header) => headers.push(header),
+                Err(e) => debug!("ignoring response header: {}", e),
+            }
+        }
+
+        let response = Response {
+            url: None,
+            status_line,
+            index,
+            status,
+            headers,
+            unit,
+            stream: stream.into(),
+            previous: None,
+            body: None,
+            extensions: Extensions::new(),
+            time_to_first_byte,
+            trailers: Trailers::default(),
+        };
+
+        // Reject a body we won't be able to read before handing out the response.
+        response.body_framing()?;
+
+        Ok(response)
+    }
+
+    pub(crate) fn do_from_request(
+        unit: Unit,
+        stream: Stream,
+        previous: Option<Arc<Response>>,
+    ) -> Result<Response, Error> {
+        let url = Some(unit.url.clone());
+        let extensions = unit.extensions.clone();
+        let mut resp = Response::do_from_stream(stream, Some(unit))?;
+        let time_to_first_byte = resp.time_to_first_byte;
+        // Skip interim responses like 100 Continue. 101 Switching Protocols
+        // is the final response for the request.
+        while (100..200).contains(&resp.status) && resp.status != 101 {
+            debug!("skipping interim response: {}", resp.status_line);
+            resp = Response::do_from_stream(resp.stream, resp.unit)?;
+        }
+        resp.time_to_first_byte = time_to_first_byte;
+        resp.previous = previous;
+        resp.url = url;
+        resp.extensions = extensions;
+        Ok(resp)
+    }
+
+    #[cfg(test)]
+    pub fn to_write_vec(self) -> Vec<u8> {
+        self.stream.to_write_vec()
+    }
+
+    #[cfg(test)]
+    pub fn set_url(&mut self, url: Url) {
+        self.url = Some(url);
+    }
+
+    #[cfg(test)]
+    pub fn set_previous(&mut self, previous: Arc<Response>) {
+        self.previous = Some(previous);
+    }
+}
+
+/// The status line and headers of a [Response], split off by
+/// [`Response::split`] or copied by [`Response::metadata`].
+#[derive(Clone)]
+pub struct ResponseHead {
+    url: Option<Url>,
+    status_line: String,
+    index: ResponseStatusIndex,
+    status: u16,
+    headers: Vec<Header>,
+    trailers: Trailers,
+}
+
+impl fmt::Debug for ResponseHead {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ResponseHead[status: {}, status_text: {}]",
+            self.status(),
+            self.status_text(),
+        )
+    }
+}
+
+impl ResponseHead {
+    /// The URL we ended up at. See [`Response::get_url`].
+    pub fn get_url(&self) -> &str {
+        self.url.as_ref().map(|s| &s[..]).unwrap_or("")
+    }
+
+    /// The URL we ended up at, as a parsed [Url]. See [`Response::url`].
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
+    /// The http version: `HTTP/1.1`
+    pub fn http_version(&self) -> &str {
+        &self.status_line.as_str()[0..self.index.http_version]
+    }
+
+    /// The status as a u16: `200`
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// The status text: `OK`
+    pub fn status_text(&self) -> &str {
+        self.status_line.as_str()[self.index.response_code + 1..].trim()
+    }
+
+    /// The header corresponding header value for the give name, if any.
+    ///
+    /// Trailers picked with
+    /// [`AgentBuilder::merge_trailers`](crate::AgentBuilder::merge_trailers)
+    /// are found here too, once the body has been read to the end.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        crate::header::get_header(&self.headers, name)
+            .or_else(|| crate::header::get_header(self.trailers(), name))
+    }
+
+    /// A list of the header names in this response.
+    /// Lowercased to be uniform.
+    pub fn headers_names(&self) -> Vec<String> {
+        self.headers
+            .iter()
+            .chain(self.trailers())
+            .map(|h| h.name().to_lowercase())
+            .collect()
+    }
+
+    /// Tells if the response has the named header.
+    pub fn has(&self, name: &str) -> bool {
+        self.header(name).is_some()
+    }
+
+    /// All headers corresponding values for the give name, or empty vector.
+    pub fn all(&self, name: &str) -> Vec<&str> {
+        let mut all = crate::header::get_all_headers(&self.headers, name);
+        all.extend(crate::header::get_all_headers(self.trailers(), name));
+        all
+    }
+
+    // The merged trailers, or none before the body has been read.
+    fn trailers(&self) -> &[Header] {
+        self.trailers.get().map(|t| &t[..]).unwrap_or(&[])
+    }
+}
+
+/// The body of a [Response], split off by [`Response::split`].
+///
+/// Reading it to the end returns the connection to the agent's pool, just
+/// like the reader from [`Response::into_reader`].
+///
+/// The body is buffered, and `BodyReader` implements [BufRead], so parsers
+/// can look for a delimiter with [`fill_buf`](BufRead::fill_buf) and
+/// [`consume`](BufRead::consume) without copying the bytes first.
+///
+/// ```
+/// use std::io::BufRead;
+/// # fn main() -> Result<(), ureq::Error> {
+/// let s = "HTTP/1.1 200 OK\r\n\
+///     Content-Length: 12\r\n\
+///     \r\n\
+///     key: value\r\n";
+/// let (_, mut body) = s.parse::<ureq::Response>()?.split();
+///
+/// let buf = body.fill_buf()?;
+/// let colon = buf.iter().position(|&c| c == b':').unwrap();
+/// assert_eq!(&buf[..colon], b"key");
+/// body.consume(colon + 1);
+/// # Ok(())
+/// # }
+/// ```
+pub struct BodyReader {
+    reader: io::BufReader<Box<dyn Read + Send>>,
+    // Whether a read returned 0 bytes.
+    eof: bool,
+    // Whether the body's framing reader got to its expected end.
+    ended: Arc<AtomicBool>,
+    // Whether the connection could go back to the pool after the body.
+    reusable: Arc<AtomicBool>,
+    clock: ArcClock,
+    call_timeout: Option<Duration>,
+    call_deadline: CallDeadline,
+}
+
+impl BodyReader {
+    /// Whether the body was read to its end, as expected from how it was sent.
+    ///
+    /// That is, all bytes of a `Content-Length` body, or all chunks of a chunked
+    /// body up to the last, empty, one. It's `false` until a read returns 0 bytes,
+    /// and stays `false` if the body ended early, which reading reports as an
+    /// error. A body delimited by the server closing the connection can't be told
+    /// apart from one that was cut short, and counts as finished at its end.
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// # fn main() -> Result<(), ureq::Error> {
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     Content-Length: 10\r\n\
+    ///     \r\n\
+    ///     cut";
+    /// let (_, mut body) = s.parse::<ureq::Response>()?.split();
+    ///
+    /// let mut buf = vec![];
+    /// assert!(body.read_to_end(&mut buf).is_err());
+    /// assert!(!body.finished_cleanly());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn finished_cleanly(&self) -> bool {
+        self.eof && self.ended.load(Ordering::Relaxed)
+    }
+
+    /// Whether the connection can be used for another request, now that the
+    /// body has been read.
+    ///
+    /// This is the check that decides if the connection goes back to the
+    /// agent's pool. It's `true` once the body was read to the end of its
+    /// `Content-Length` or last chunk without errors. It's `false` before
+    /// then, for bodies that end when the server closes the connection, and
+    /// after a read failed, such as for a body cut short or a bad chunk. The
+    /// pool may still not keep a reusable connection, for instance when it's
+    /// disabled.
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # ureq::is_test(true);
+    /// let (_, mut body) = ureq::get("http://example.com/").call()?.split();
+    /// let mut bytes = vec![];
+    /// body.read_to_end(&mut bytes)?;
+    /// if !body.can_reuse_connection() {
+    ///     println!("next request opens a new connection");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn can_reuse_connection(&self) -> bool {
+        self.reusable.load(Ordering::Relaxed)
+    }
+
+    /// Limit how long each call to `read()`, or to `fill_buf()`, may take.
+    ///
+    /// A call that doesn't get any data in `timeout` fails with
+    /// [`TimedOut`](std::io::ErrorKind::TimedOut), so a program that reads
+    /// from a UI thread, say, is never blocked for longer. This is on top of
+    /// the agent's timeouts: the overall timeout still applies, and the read
+    /// timeout is checked for each read from the socket.
+    ///
+    /// A read that times out in the middle of a chunk header or a compressed
+    /// block may leave the body in a state where it can't be read further.
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// use std::time::Duration;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # ureq::is_test(true);
+    /// let (_, mut body) = ureq::get("http://example.com/").call()?.split();
+    /// body.set_read_call_timeout(Duration::from_millis(50));
+    ///
+    /// let mut buf = [0; 1024];
+    /// match body.read(&mut buf) {
+    ///     Err(e) if e.kind() == std::io::ErrorKind::TimedOut => println!("nothing yet"),
+    ///     result => println!("read {} bytes", result?),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_read_call_timeout(&mut self, timeout: Duration) {
+        self.call_timeout = Some(timeout);
+    }
+
+    fn start_call(&self) {
+        let deadline = self.call_timeout.map(|t| self.clock.now() + t);
+        *self.call_deadline.lock().unwrap() = deadline;
+    }
+}
+
+impl fmt::Debug for BodyReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BodyReader")
+    }
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.start_call();
+        let n = self.reader.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.eof = true;
+        }
+        Ok(n)
+    }
+}
+
+impl BufRead for BodyReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.start_call();
+        let buf = self.reader.fill_buf()?;
+        if buf.is_empty() {
+            self.eof = true;
+        }
+        Ok(buf)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt)
+    }
+}
+
+/// parse a line like: HTTP/1.1 200 OK\r\n
+fn parse_status_line(line: &str) -> Result<(ResponseStatusIndex, u16), Error> {
+    //
+
+    if !line.is_ascii() {
+        return Err(BadStatus.msg("Status line not ASCII"));
+    }
+    // https://tools.ietf.org/html/rfc7230#section-3.1.2
+    //      reason-phrase  = *( HTAB / SP / VCHAR / obs-text )
+    // obs-text is already ruled out above, so any remaining control
+    // character other than HTAB is invalid.
+    if line.bytes().any(|b| b != b'\t' && (b < 0x20 || b == 0x7F)) {
+        return Err(BadStatus.msg("Status line contains control characters"));
+    }
+    // https://tools.ietf.org/html/rfc7230#section-3.1.2
+    //      status-line = HTTP-version SP status-code SP reason-phrase CRLF
+    let split: Vec<&str> = line.splitn(3, ' ').collect();
+    if split.len() != 3 {
+        return Err(BadStatus.msg("Wrong number of tokens in status line"));
+    }
+
+    // https://tools.ietf.org/html/rfc7230#appendix-B
+    //    HTTP-name = %x48.54.54.50 ; HTTP
+    //    HTTP-version = HTTP-name "/" DIGIT "." DIGIT
+    let http_version = split[0];
+    if !http_version.starts_with("HTTP/") {
+        return Err(BadStatus.msg("HTTP version did not start with HTTP/"));
+    }
+    if http_version.len() != 8 {
+        return Err(BadStatus.msg("HTTP version was wrong length"));
+    }
+    let version = http_version.as_bytes();
+    if !version[5].is_ascii_digit() || version[6] != b'.' || !version[7].is_ascii_digit() {
+        return Err(BadStatus.msg("HTTP version did not match format"));
+    }
+
+    let status_str: &str = split[1];
+    //      status-code    = 3DIGIT
+    if status_str.len() != 3 {
+        return Err(BadStatus.msg("Status code was wrong length"));
+    }
+    if !status_str.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(BadStatus.msg("Status code was not a number"));
+    }
+
+    let status: u16 = status_str.parse().map_err(|_| BadStatus.new())?;
+
+    // The first digit is the class of the response, 1xx to 5xx. We don't
+    // cap the upper end since some servers use codes like 999 in the wild,
+    // but there's no such thing as a 0xx response.
+    if status < 100 {
+        return Err(BadStatus.msg("Status code out of range"));
+    }
+
+    Ok((
+        ResponseStatusIndex {
+            http_version: http_version.len(),
+            response_code: http_version.len() + status_str.len(),
+        },
+        status,
+    ))
+}
+
+impl FromStr for Response {
+    type Err = Error;
+    /// Parse a response from a string.
+    ///
+    /// Example:
+    /// ```
+    /// let s = "HTTP/1.1 200 OK\r\n\
+    ///     X-Forwarded-For: 1.2.3.4\r\n\
+    ///     Content-Type: text/plain\r\n\
+    ///     \r\n\
+    ///     Hello World!!!";
+    /// let resp = s.parse::<ureq::Response>().unwrap();
+    /// assert!(resp.has("X-Forwarded-For"));
+    /// let body = resp.into_string().unwrap();
+    /// assert_eq!(body, "Hello World!!!");
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stream = Stream::from_vec(s.as_bytes().to_owned());
+        Self::do_from_stream(stream, None)
+    }
+}
+
+// Hist is an iterator over the history of a redirected response. It
+// yields the URLs that were requested in backwards order, from most recent
+// to least recent.
+pub(crate) struct Hist<'a> {
+    response: Option<&'a Response>,
+}
+
+impl<'a> Hist<'a> {
+    fn new(response: Option<&'a Response>) -> Hist<'a> {
+        Hist { response }
+    }
+}
+impl<'a> Iterator for Hist<'a> {
+    type Item = &'a Response;
+    fn next(&mut self) -> Option<&'a Response> {
+        let response = match self.response {
+            None => return None,
+            Some(r) => r,
+        };
+
+        self.response = response.previous.as_deref();
+        return Some(response);
+    }
+}
+
+// Read the status line, but never more than max_len bytes of it (plus CRLF), so
+// a server can't make us buffer an arbitrarily long reason phrase.
+fn read_status_line(reader: &mut impl BufRead, max_len: usize) -> Result<String, Error> {
+    let mut limited = reader.take(max_len as u64 + 2);
+    match read_next_line(&mut limited) {
+        Err(_) if limited.limit() == 0 => Err(BadStatus.msg("Status line too long")),
+        result => Ok(result?),
+    }
+}
+
+fn read_next_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut s = String::new();
+    if reader.read_line(&mut s)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionAborted,
+            "Unexpected EOF",
+        ));
+    }
+
+    if !s.ends_with("\r\n") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Header field didn't end with \\r: {}", s),
+        ));
+    }
+    s.pop();
+    s.pop();
+    Ok(s)
+}
+
+/// How the body of a response is delimited and encoded.
+struct BodyFraming {
+    chunked: bool,
+    // Transfer codings other than chunked, in the order they were applied.
+    codings: Vec<TransferCoding>,
+    // Number of bytes in the body if known up front. None means the body is
+    // read until the server closes the connection.
+    limit_bytes: Option<usize>,
+}
+
+/// A transfer coding, other than chunked, from a `Transfer-Encoding` header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TransferCoding {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "gzip")]
+    Deflate,
+}
+
+impl TransferCoding {
+    // If `framed`, the body has a known end, up to which the encoded bytes are
+    // read once decoding is done. Without the gzip feature there are no
+    // codings to decode besides chunked.
+    #[cfg_attr(not(feature = "gzip"), allow(unused_variables))]
+    fn decode(self, reader: Box<dyn Read + Send>, framed: bool) -> Box<dyn Read + Send> {
+        match self {
+            #[cfg(feature = "gzip")]
+            TransferCoding::Gzip => {
+                let gz = flate2::bufread::GzDecoder::new(io::BufReader::new(reader));
+                Box::new(DrainRead::new(Decoder::Gzip(gz), framed))
+            }
+            // "deflate" in HTTP means the zlib format.
+            // https://tools.ietf.org/html/rfc7230#section-4.2.2
+            #[cfg(feature = "gzip")]
+            TransferCoding::Deflate => {
+                let zlib = flate2::bufread::ZlibDecoder::new(io::BufReader::new(reader));
+                Box::new(DrainRead::new(Decoder::Deflate(zlib), framed))
+            }
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    fn name(self) -> &'static str {
+        match self {
+            TransferCoding::Gzip => "gzip",
+            TransferCoding::Deflate => "deflate",
+        }
+    }
+}
+
+/// Parse a `Transfer-Encoding` header into the codings that were applied,
+/// in order, and whether the final coding is chunked.
+///
+/// https://tools.ietf.org/html/rfc7230#section-3.3.1
+fn parse_transfer_encoding(value: &str) -> Result<(Vec<TransferCoding>, bool), Error> {
+    let mut names: Vec<&str> = value
+        .split(',')
+        .map(|c| c.trim())
+        .filter(|c| !c.is_empty())
+        .collect();
+    let chunked = names
+        .last()
+        .map(|c| c.eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false);
+    if chunked {
+        names.pop();
+    }
+
+    let codings = names
+        .into_iter()
+        .filter(|c| !c.eq_ignore_ascii_case("identity"))
+        .map(|coding| match coding.to_ascii_lowercase().as_str() {
+            "chunked" => Err(BadHeader.msg(&format!(
+                "chunked must be the last Transfer-Encoding: {}",
+                value
+            ))),
+            #[cfg(feature = "gzip")]
+            "gzip" | "x-gzip" => Ok(TransferCoding::Gzip),
+            #[cfg(feature = "gzip")]
+            "deflate" => Ok(TransferCoding::Deflate),
+            _ => Err(BadHeader.msg(&format!("unsupported Transfer-Encoding: {}", coding))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((codings, chunked))
+}
+
+/// Sets a flag when the wrapped `Read` reaches its end.
+///
+/// The framing readers return an error rather than 0 when the body is cut
+/// short, so reaching the end means the whole body was read.
+struct EndRead<R> {
+    reader: R,
+    ended: Arc<AtomicBool>,
+}
+
+impl<R: Read> EndRead<R> {
+    fn new(reader: R, ended: Arc<AtomicBool>) -> Self {
+        EndRead { reader, ended }
+    }
+}
+
+impl<R: Read> Read for EndRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.ended.store(true, Ordering::Relaxed);
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "gzip")]
+type Buffered = io::BufReader<Box<dyn Read + Send>>;
+
+#[cfg(feature = "gzip")]
+enum Decoder {
+    Gzip(flate2::bufread::GzDecoder<Buffered>),
+    Deflate(flate2::bufread::ZlibDecoder<Buffered>),
+}
+
+/// Reads what's left of the encoded body once the decoder is done with it.
+///
+/// Decoders stop right after their trailer, but the connection only goes back
+/// to the pool once the framing reader below them sees the end of the body.
+/// A body that ends when the server closes the connection isn't drained.
+#[cfg(feature = "gzip")]
+struct DrainRead {
+    decoder: Decoder,
+    framed: bool,
+}
+
+#[cfg(feature = "gzip")]
+impl DrainRead {
+    fn new(decoder: Decoder, framed: bool) -> Self {
+        DrainRead { decoder, framed }
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl Read for DrainRead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (n, encoded) = match &mut self.decoder {
+            Decoder::Gzip(d) => (d.read(buf)?, d.get_mut()),
+            Decoder::Deflate(d) => (d.read(buf)?, d.get_mut()),
+        };
+        if n == 0 && !buf.is_empty() && self.framed {
+            io::copy(encoded, &mut io::sink())?;
+        }
+        Ok(n)
+    }
+}
+
+// Turn a serde_json error into an io::Error.
+#[cfg(feature = "json")]
+fn json_error(e: serde_json::Error) -> io::Error {
+    use crate::stream::io_err_timeout;
+    use std::error::Error;
+
+    // This is to unify TimedOut io::Error in the API.
+    // We make a clone of the original error since serde_json::Error doesn't
+    // let us get the wrapped error instance back.
+    if let Some(ioe) = e.source().and_then(|s| s.downcast_ref::<io::Error>()) {
+        if ioe.kind() == io::ErrorKind::TimedOut {
+            return io_err_timeout(ioe.to_string());
+        }
+    }
+
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Failed to read JSON: {}", e),
+    )
+}
+
+/// Decodes a body without `Content-Encoding` if it starts with the signature
+/// of gzip or zlib data, and passes it through otherwise. The first bytes are
+/// checked on the first read.
+#[cfg(feature = "gzip")]
+enum SniffRead {
+    Pending {
+        reader: Box<dyn Read + Send>,
+        prefix: Vec<u8>,
+        framed: bool,
+    },
+    Sniffed(Box<dyn Read + Send>),
+}
+
+#[cfg(feature = "gzip")]
+impl SniffRead {
+    fn new(reader: Box<dyn Read + Send>, framed: bool) -> Self {
+        SniffRead::Pending {
+            reader,
+            prefix: vec![],
+            framed,
+        }
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl Read for SniffRead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let SniffRead::Pending {
+            reader,
+            prefix,
+            framed,
+        } = self
+        {
+            let mut byte = [0];
+            while prefix.len() < 2 && reader.read(&mut byte)? == 1 {
+                prefix.push(byte[0]);
+            }
+            let coding = sniff_coding(prefix);
+            let reader = mem::replace(reader, Box::new(io::empty()));
+            let body: Box<dyn Read + Send> = Box::new(Cursor::new(mem::take(prefix)).chain(reader));
+            let body = match coding {
+                Some(coding) => coding.decode(body, *framed),
+                None => body,
+            };
+            *self = SniffRead::Sniffed(body);
+        }
+        match self {
+            SniffRead::Sniffed(body) => body.read(buf),
+            SniffRead::Pending { .. } => unreachable!(),
+        }
+    }
+}
+
+/// The coding of compressed data, from its first two bytes.
+#[cfg(feature = "gzip")]
+fn sniff_coding(prefix: &[u8]) -> Option<TransferCoding> {
+    match prefix {
+        [0x1f, 0x8b] => Some(TransferCoding::Gzip),
+        // Deflate with a 32K window, at each of the four compression levels.
+        // https://tools.ietf.org/html/rfc1950#section-2.2
+        [0x78, 0x01] | [0x78, 0x5e] | [0x78, 0x9c] | [0x78, 0xda] => Some(TransferCoding::Deflate),
+        _ => None,
+    }
+}
+
+/// Limits a `Read` to a content size (as set by a "Content-Length" header).
+struct LimitedRead<R> {
+    reader: R,
+    limit: usize,
+    position: usize,
+}
+
+impl<R: Read> LimitedRead<R> {
+    fn new(reader: R, limit: usize) -> Self {
+        LimitedRead {
+            reader,
+            limit,
+            position: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for LimitedRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.limit - self.position;
+        if left == 0 {
+            return Ok(0);
+        }
+        let from = if left < buf.len() {
+            &mut buf[0..left]
+        } else {
+            buf
+        };
+        match self.reader.read(from) {
+            // https://tools.ietf.org/html/rfc7230#page-33
+            // If the sender closes the connection or
+            // the recipient times out before the indicated number of octets are
+            // received, the recipient MUST consider the message to be
+            // incomplete and close the connection.
+            Ok(0) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "response body closed before all bytes were read",
+            )),
+            Ok(amount) => {
+                self.position += amount;
+                Ok(amount)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[test]
+fn short_read() {
+    use std::io::Cursor;
+    let mut lr = LimitedRead::new(Cursor::new(vec![b'a'; 3]), 10);
+    let mut buf = vec![0; 1000];
+    let result = lr.read_to_end(&mut buf);
+    assert!(result.is_err());
+}
+
+impl<R: Read> From<LimitedRead<R>> for Stream
+where
+    Stream: From<R>,
+{
+    fn from(limited_read: LimitedRead<R>) -> Stream {
+        limited_read.reader.into()
+    }
+}
+
+/// Extract the charset from a "Content-Type" header.
+///
+/// "Content-Type: text/plain; charset=iso8859-1" -> "iso8859-1"
+///
+/// *Internal API*
+pub(crate) fn charset_from_content_type(header: Option<&str>) -> &str {
+    header
+        .and_then(|header| {
+            header.find(';').and_then(|semi| {
+                (&header[semi + 1..])
+                    .find('=')
+                    .map(|equal| (&header[semi + equal + 2..]).trim())
+            })
+        })
+        .unwrap_or(DEFAULT_CHARACTER_SET)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_type_without_charset() {
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Content-Type: application/json\r\n\
+                 \r\n\
+                 OK";
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!("application/json", resp.content_type());
+    }
+
+    #[test]
+    fn content_type_with_charset() {
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Content-Type: application/json; charset=iso-8859-4\r\n\
+                 \r\n\
+                 OK";
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!("application/json", resp.content_type());
+    }
+
+    #[test]
+    fn content_type_default() {
+        let s = "HTTP/1.1 200 OK\r\n\r\nOK";
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!("text/plain", resp.content_type());
+    }
+
+    #[test]
+    fn charset() {
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Content-Type: application/json; charset=iso-8859-4\r\n\
+                 \r\n\
+                 OK";
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!("iso-8859-4", resp.charset());
+    }
+
+    #[test]
+    fn charset_default() {
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Content-Type: application/json\r\n\
+                 \r\n\
+                 OK";
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!("utf-8", resp.charset());
+    }
+
+    #[test]
+    fn chunked_transfer() {
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Transfer-Encoding: Chunked\r\n\
+                 \r\n\
+                 3\r\n\
+                 hel\r\n\
+                 b\r\n\
+                 lo world!!!\r\n\
+                 0\r\n\
+                 \r\n";
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!("hello world!!!", resp.into_string().unwrap());
+    }
+
+    #[test]
+    fn transfer_encoding_chunked_must_be_last() {
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Transfer-Encoding: chunked, gzip\r\n\
+                 \r\n";
+        let err = s.parse::<Response>().unwrap_err();
+        assert_eq!(err.kind(), BadHeader);
     }
-}
 
-// Hist is an iterator over the history of a redirected response. It
-// yields the URLs that were requested in backwards order, from most recent
-// to least recent.
-pub(crate) struct Hist<'a> {
-    response: Option<&'a Response>,
-}
+    #[test]
+    fn transfer_encoding_unknown() {
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Transfer-Encoding: bogus, chunked\r\n\
+                 \r\n";
+        let err = s.parse::<Response>().unwrap_err();
+        assert_eq!(err.kind(), BadHeader);
+    }
 
-impl<'a> Hist<'a> {
-    fn new(response: Option<&'a Response>) -> Hist<'a> {
-        Hist { response }
+    #[test]
+    fn transfer_encoding_ignored_without_body() {
+        let s = "HTTP/1.1 304 Not Modified\r\n\
+                 Transfer-Encoding: bogus\r\n\
+                 \r\n";
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!(resp.into_string().unwrap(), "");
     }
-}
-impl<'a> Iterator for Hist<'a> {
-    type Item = &'a Response;
-    fn next(&mut self) -> Option<&'a Response> {
-        let response = match self.response {
-            None => return None,
-            Some(r) => r,
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn transfer_encoding_gzip_chunked() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(b"hello world!!!").unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let mut body = format!("{:x}\r\n", gzipped.len()).into_bytes();
+        body.extend_from_slice(&gzipped);
+        body.extend_from_slice(b"\r\n0\r\n\r\n");
+        let mut s = b"HTTP/1.1 200 OK\r\n\
+                      Transfer-Encoding: gzip, chunked\r\n\
+                      \r\n"
+            .to_vec();
+        s.extend_from_slice(&body);
+
+        let resp = Response::from_reader(io::Cursor::new(s)).unwrap();
+        assert_eq!(resp.into_string().unwrap(), "hello world!!!");
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn transfer_encoding_deflate_until_close() {
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(b"hello world!!!").unwrap();
+        let deflated = encoder.finish().unwrap();
+
+        // Content-Length is overridden by Transfer-Encoding.
+        let mut s = b"HTTP/1.1 200 OK\r\n\
+                      Transfer-Encoding: deflate\r\n\
+                      Content-Length: 2\r\n\
+                      \r\n"
+            .to_vec();
+        s.extend_from_slice(&deflated);
+
+        let resp = Response::from_reader(io::Cursor::new(s)).unwrap();
+        assert_eq!(resp.into_string().unwrap(), "hello world!!!");
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn content_encoding_gzip() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(b"hello world!!!").unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let mut s = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Encoding: gzip\r\n\
+             Content-Length: {}\r\n\
+             \r\n",
+            gzipped.len()
+        )
+        .into_bytes();
+        s.extend_from_slice(&gzipped);
+
+        let resp = Response::from_reader(io::Cursor::new(s)).unwrap();
+        assert_eq!(resp.into_string().unwrap(), "hello world!!!");
+    }
+
+    #[test]
+    #[cfg(not(feature = "gzip"))]
+    fn transfer_encoding_gzip_needs_feature() {
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Transfer-Encoding: gzip, chunked\r\n\
+                 \r\n";
+        let err = s.parse::<Response>().unwrap_err();
+        assert_eq!(err.kind(), BadHeader);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn parse_simple_json() {
+        let s = "HTTP/1.1 200 OK\r\n\
+             \r\n\
+             {\"hello\":\"world\"}";
+        let resp = s.parse::<Response>().unwrap();
+        let v: serde_json::Value = resp.into_json().unwrap();
+        let compare = "{\"hello\":\"world\"}"
+            .parse::<serde_json::Value>()
+            .unwrap();
+        assert_eq!(v, compare);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn json_path() {
+        let s = "HTTP/1.1 200 OK\r\n\
+             \r\n\
+             {\"data\": {\"name\": \"x\", \"items\": [{\"id\": 1}, {\"id\": 2}]}, \"a/b\": true}";
+        let resp = || s.parse::<Response>().unwrap();
+        assert_eq!(resp().json_path("/data/name").unwrap(), "x");
+        assert_eq!(resp().json_path("/data/items/1/id").unwrap(), 2);
+        assert_eq!(
+            resp().json_path("/data/items/0").unwrap(),
+            serde_json::json!({"id": 1})
+        );
+        assert_eq!(resp().json_path("/a~1b").unwrap(), true);
+        assert!(resp().json_path("").unwrap().is_object());
+
+        for missing in &["/data/missing", "/data/items/2", "/data/name/x", "data"] {
+            let err = resp().json_path(missing).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn into_json_or_empty() {
+        use serde::Deserialize;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Error {
+            code: u32,
+        }
+
+        let parse = |s: &str| s.parse::<Response>().unwrap().into_json_or_empty::<Error>();
+        assert_eq!(parse("HTTP/1.1 204 No Content\r\n\r\n").unwrap(), None);
+        assert_eq!(
+            parse("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap(),
+            None
+        );
+        assert_eq!(parse("HTTP/1.1 200 OK\r\n\r\n \r\n").unwrap(), None);
+        assert_eq!(
+            parse("HTTP/1.1 409 Conflict\r\n\r\n{\"code\": 7}").unwrap(),
+            Some(Error { code: 7 })
+        );
+        let err = parse("HTTP/1.1 200 OK\r\n\r\n{\"code\":").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn from_parts() {
+        let body: Vec<u8> = (0..=255).chain((0..=255).rev()).collect();
+        let mut resp = Response::from_parts(
+            404,
+            &[("Content-Type", "application/octet-stream"), ("X-A", "1")],
+            body.clone(),
+        );
+        assert_eq!(resp.status(), 404);
+        assert_eq!(resp.status_text(), "");
+        assert_eq!(resp.headers_names(), vec!["content-type", "x-a"]);
+        assert_eq!(resp.body_mut().unwrap(), &body);
+
+        let mut read = vec![];
+        resp.into_reader().read_to_end(&mut read).unwrap();
+        assert_eq!(read, body);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_parts_invalid_header() {
+        Response::from_parts(200, &[("Bad Name", "x")], vec![]);
+    }
+
+    #[test]
+    fn into_records() {
+        let records = |body: &str, strict: bool| {
+            let resp = format!("HTTP/1.1 200 OK\r\n\r\n{}", body)
+                .parse::<Response>()
+                .unwrap();
+            let records = resp.into_records(3);
+            let records = if strict {
+                records.partial_is_error()
+            } else {
+                records
+            };
+            records.collect::<Vec<_>>()
+        };
+        let ok = |r: Vec<io::Result<Vec<u8>>>| -> Vec<Vec<u8>> {
+            r.into_iter().map(|r| r.unwrap()).collect()
         };
 
-        self.response = response.previous.as_deref();
-        return Some(response);
+        let exact = vec![b"abc".to_vec(), b"def".to_vec()];
+        assert_eq!(ok(records("abcdef", false)), exact);
+        assert_eq!(ok(records("abcdef", true)), exact);
+        assert!(records("", false).is_empty());
+
+        let partial = records("abcdefgh", false);
+        assert_eq!(ok(partial)[2], b"gh");
+        let mut partial = records("abcdefgh", true);
+        assert_eq!(partial.len(), 3);
+        let err = partial.pop().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(ok(partial), exact);
     }
-}
 
-fn read_next_line(reader: &mut impl BufRead) -> io::Result<String> {
-    let mut s = String::new();
-    if reader.read_line(&mut s)? == 0 {
-        return Err(io::Error::new(
-            io::ErrorKind::ConnectionAborted,
-            "Unexpected EOF",
-        ));
+    #[test]
+    fn tail() {
+        let body: String = (0..10_000).map(|i| format!("{}\n", i)).collect();
+        let tail = |n| {
+            format!("HTTP/1.1 200 OK\r\n\r\n{}", body)
+                .parse::<Response>()
+                .unwrap()
+                .tail(n)
+                .unwrap()
+        };
+        assert_eq!(tail(10), b"9998\n9999\n");
+        assert_eq!(tail(10_000), body[body.len() - 10_000..].as_bytes());
+        assert_eq!(tail(body.len() + 5), body.as_bytes());
+        assert!(tail(0).is_empty());
     }
 
-    if !s.ends_with("\r\n") {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            format!("Header field didn't end with \\r: {}", s),
-        ));
+    #[test]
+    fn into_base64_bytes() {
+        let decode = |body: &str| {
+            format!("HTTP/1.1 200 OK\r\n\r\n{}", body)
+                .parse::<Response>()
+                .unwrap()
+                .into_base64_bytes()
+        };
+        assert_eq!(decode("aGVsbG8gd29ybGQ=").unwrap(), b"hello world");
+        assert_eq!(decode("aGVs\r\nbG8g\nd29y bGQ=\n").unwrap(), b"hello world");
+        assert_eq!(decode("-_8=").unwrap(), vec![0xfb, 0xff]);
+        assert_eq!(decode("+/8=").unwrap(), vec![0xfb, 0xff]);
+        assert_eq!(decode("-_8").unwrap(), vec![0xfb, 0xff]);
+        assert_eq!(decode("").unwrap(), b"");
+
+        let err = decode("not base64!").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(decode("+_8=").is_err());
     }
-    s.pop();
-    s.pop();
-    Ok(s)
-}
 
-/// Limits a `Read` to a content size (as set by a "Content-Length" header).
-struct LimitedRead<R> {
-    reader: R,
-    limit: usize,
-    position: usize,
-}
+    #[test]
+    #[cfg(feature = "json")]
+    fn parse_deserialize_json() {
+        use serde::Deserialize;
 
-impl<R: Read> LimitedRead<R> {
-    fn new(reader: R, limit: usize) -> Self {
-        LimitedRead {
-            reader,
-            limit,
-            position: 0,
+        #[derive(Deserialize)]
+        struct Hello {
+            hello: String,
         }
+
+        let s = "HTTP/1.1 200 OK\r\n\
+             \r\n\
+             {\"hello\":\"world\"}";
+        let resp = s.parse::<Response>().unwrap();
+        let v: Hello = resp.into_json::<Hello>().unwrap();
+        assert_eq!(v.hello, "world");
     }
-}
 
-impl<R: Read> Read for LimitedRead<R> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let left = self.limit - self.position;
-        if left == 0 {
-            return Ok(0);
+    #[test]
+    fn parse_borked_header() {
+        let s = "HTTP/1.1 BORKED\r\n".to_string();
+        let err = s.parse::<Response>().unwrap_err();
+        assert_eq!(err.kind(), BadStatus);
+    }
+
+    #[test]
+    fn parse_status_line_adversarial() {
+        let cases = [
+            "HTTP/a.b 200 OK",
+            "HTTP/1x1 200 OK",
+            "HTTP/1.1 +20 OK",
+            "HTTP/1.1 -20 OK",
+            "HTTP/1.1 2 0 OK",
+            "HTTP/1.1 000 OK",
+            "HTTP/1.1 099 OK",
+            "HTTP/1.1 99999 OK",
+            "HTTP/1.1 200 O\x00K",
+            "HTTP/1.1 200 O\rK",
+            "HTTP/1.1 200 OK\x7F",
+            "HTTP/1.1\x00200 OK",
+            "HTTP/1.1 200",
+            "HTTP/1.1",
+            "",
+            " ",
+            "  ",
+            "HTTP/ 200 OK",
+            "HTTP/1.1 2é0 OK",
+        ];
+        for c in cases.iter() {
+            let result = parse_status_line(c);
+            assert!(
+                matches!(result, Err(ref e) if e.kind() == BadStatus),
+                "'{:?}': expected BadStatus, got {:?}",
+                c,
+                result
+            );
         }
-        let from = if left < buf.len() {
-            &mut buf[0..left]
-        } else {
-            buf
+    }
+
+    #[test]
+    fn parse_status_line_accepted() {
+        let (_, status) = parse_status_line("HTTP/1.1 200 OK").unwrap();
+        assert_eq!(status, 200);
+        let (_, status) = parse_status_line("HTTP/1.0 999 Request denied").unwrap();
+        assert_eq!(status, 999);
+        let (_, status) = parse_status_line("HTTP/1.1 404 \tNot\tFound").unwrap();
+        assert_eq!(status, 404);
+        let (_, status) = parse_status_line("HTTP/1.1 204 ").unwrap();
+        assert_eq!(status, 204);
+    }
+
+    #[test]
+    fn parse_status_line_fuzz() {
+        // A tiny xorshift PRNG, so the inputs are the same on every run.
+        let mut state: u32 = 0x9E37_79B9;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
         };
-        match self.reader.read(from) {
-            // https://tools.ietf.org/html/rfc7230#page-33
-            // If the sender closes the connection or
-            // the recipient times out before the indicated number of octets are
-            // received, the recipient MUST consider the message to be
-            // incomplete and close the connection.
-            Ok(0) => Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "response body closed before all bytes were read",
-            )),
-            Ok(amount) => {
-                self.position += amount;
-                Ok(amount)
+        let alphabet = b"HTP/1.0 259OK\t\r\n\x00\x7Fa";
+        let seed = b"HTTP/1.1 200 OK";
+        for _ in 0..10_000 {
+            let mut line: Vec<u8> = seed.to_vec();
+            for _ in 0..(next() % 4) {
+                let pos = next() as usize % (line.len() + 1);
+                let b = alphabet[next() as usize % alphabet.len()];
+                match next() % 3 {
+                    0 if pos < line.len() => line[pos] = b,
+                    1 if pos < line.len() => {
+                        line.remove(pos);
+                    }
+                    _ => line.insert(pos, b),
+                }
             }
-            Err(e) => Err(e),
+            let line = String::from_utf8(line).unwrap();
+            match parse_status_line(&line) {
+                Ok((index, status)) => {
+                    assert!(status >= 100, "{:?}", line);
+                    assert!(index.response_code < line.len(), "{:?}", line);
+                }
+                Err(e) => assert_eq!(e.kind(), BadStatus, "{:?}", line),
+            }
+            // Whole responses must also fail cleanly.
+            let _ = Response::from_reader(io::Cursor::new(line.into_bytes()));
         }
     }
-}
 
-#[test]
-fn short_read() {
-    use std::io::Cursor;
-    let mut lr = LimitedRead::new(Cursor::new(vec![b'a'; 3]), 10);
-    let mut buf = vec![0; 1000];
-    let result = lr.read_to_end(&mut buf);
-    assert!(result.is_err());
-}
+    #[test]
+    fn read_next_line_edge_cases() {
+        // An empty line is the header terminator.
+        let mut r = io::Cursor::new(b"\r\n".to_vec());
+        assert_eq!(read_next_line(&mut r).unwrap(), "");
+
+        // A lone \r is not a line ending.
+        let mut r = io::Cursor::new(b"\r".to_vec());
+        let err = read_next_line(&mut r).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let mut r = io::Cursor::new(b"\rfoo: bar\r\n".to_vec());
+        assert_eq!(read_next_line(&mut r).unwrap(), "\rfoo: bar");
+
+        let mut r = io::Cursor::new(b"".to_vec());
+        let err = read_next_line(&mut r).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+
+        // A response with a lone \r where the header terminator should be.
+        let err =
+            Response::from_reader(io::Cursor::new(b"HTTP/1.1 200 OK\r\n\r".to_vec())).unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::Io);
+    }
+
+    #[test]
+    fn status_line_too_long() {
+        let mut s = "HTTP/1.1 200 ".to_string();
+        s.push_str(&"a".repeat(4 * 1024 * 1024));
+        s.push_str("\r\n\r\n");
+        let err = s.parse::<Response>().unwrap_err();
+        assert_eq!(err.kind(), BadStatus);
+    }
+
+    #[test]
+    fn status_line_at_limit() {
+        let mut line = "HTTP/1.1 200 ".to_string();
+        line.push_str(&"a".repeat(DEFAULT_MAX_STATUS_LINE_LENGTH - line.len()));
+        let s = format!("{}\r\n\r\n", line);
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!(resp.status(), 200);
+
+        let s = format!("{}a\r\n\r\n", line);
+        let err = s.parse::<Response>().unwrap_err();
+        assert_eq!(err.kind(), BadStatus);
+    }
+
+    #[test]
+    fn from_reader_cursor() {
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Content-Length: 5\r\n\
+                 \r\n\
+                 hello";
+        let resp = Response::from_reader(io::Cursor::new(s.as_bytes().to_vec())).unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.header("content-length"), Some("5"));
+        assert_eq!(resp.into_string().unwrap(), "hello");
+    }
+
+    #[test]
+    fn from_reader_slow() {
+        // Yields a single byte per read() call.
+        struct Slow(io::Cursor<Vec<u8>>);
+        impl Read for Slow {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let len = buf.len().min(1);
+                self.0.read(&mut buf[..len])
+            }
+        }
+
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Transfer-Encoding: chunked\r\n\
+                 \r\n\
+                 3\r\n\
+                 hel\r\n\
+                 2\r\n\
+                 lo\r\n\
+                 0\r\n\
+                 \r\n";
+        let resp = Response::from_reader(Slow(io::Cursor::new(s.as_bytes().to_vec()))).unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.into_string().unwrap(), "hello");
+    }
+
+    #[test]
+    fn into_string_checked_invalid() {
+        let bytes = b"HTTP/1.1 200 OK\r\n\
+                      Content-Type: text/plain; charset=utf-8\r\n\
+                      \r\n\
+                      caf\xe9 \xff";
+        let resp = Response::from_reader(Cursor::new(bytes.to_vec())).unwrap();
+        let (text, had_errors) = resp.into_string_checked().unwrap();
+        assert_eq!(text, "caf\u{fffd} \u{fffd}");
+        assert!(had_errors);
+    }
+
+    #[test]
+    fn into_string_checked_valid() {
+        let resp = Response::new(200, "OK", "caf\u{e9}").unwrap();
+        let (text, had_errors) = resp.into_string_checked().unwrap();
+        assert_eq!(text, "caf\u{e9}\n");
+        assert!(!had_errors);
+    }
+
+    #[test]
+    #[cfg(feature = "charset")]
+    fn into_string_checked_latin1() {
+        // Every byte is valid in latin-1 (windows-1252 per the WHATWG encoding
+        // standard), so the same bytes that are invalid utf-8 decode cleanly.
+        let bytes = b"HTTP/1.1 200 OK\r\n\
+                      Content-Type: text/plain; charset=iso-8859-1\r\n\
+                      \r\n\
+                      caf\xe9 \xff";
+        let resp = Response::from_reader(Cursor::new(bytes.to_vec())).unwrap();
+        let (text, had_errors) = resp.into_string_checked().unwrap();
+        assert_eq!(text, "caf\u{e9} \u{ff}");
+        assert!(!had_errors);
+    }
+
+    #[test]
+    #[cfg(feature = "charset")]
+    fn into_string_with_encoding() {
+        // Latin-1 bytes, labelled as utf-8 by the server.
+        let bytes = b"HTTP/1.1 200 OK\r\n\
+                      Content-Type: text/plain; charset=utf-8\r\n\
+                      \r\n\
+                      K\xf6ln \xe0 la carte";
+        let resp = Response::from_reader(Cursor::new(bytes.to_vec())).unwrap();
+        let text = resp.into_string_with_encoding("latin1").unwrap();
+        assert_eq!(text, "K\u{f6}ln \u{e0} la carte");
+
+        let resp = Response::new(200, "OK", "K\u{f6}ln").unwrap();
+        let text = resp.into_string_with_encoding("no-such-charset").unwrap();
+        assert_eq!(text, "K\u{f6}ln\n");
+    }
+
+    #[test]
+    #[cfg(feature = "charset")]
+    fn into_string_bom() {
+        let with_body = |body: &[u8]| {
+            let mut bytes = b"HTTP/1.1 200 OK\r\n\
+                              Content-Type: text/plain; charset=iso-8859-1\r\n\
+                              \r\n"
+                .to_vec();
+            bytes.extend_from_slice(body);
+            Response::from_reader(Cursor::new(bytes)).unwrap()
+        };
+
+        let resp = with_body(b"\xff\xfeK\x00\xf6\x00l\x00n\x00");
+        assert_eq!(resp.into_string().unwrap(), "K\u{f6}ln");
+
+        let resp = with_body(b"\xfe\xff\x00K\x00\xf6\x00l\x00n");
+        assert_eq!(resp.into_string().unwrap(), "K\u{f6}ln");
+
+        let resp = with_body(b"\xef\xbb\xbfK\xc3\xb6ln");
+        assert_eq!(resp.into_string().unwrap(), "K\u{f6}ln");
+
+        // The BOM also beats an explicitly requested encoding.
+        let resp = with_body(b"\xef\xbb\xbfK\xc3\xb6ln");
+        assert_eq!(
+            resp.into_string_with_encoding("latin1").unwrap(),
+            "K\u{f6}ln"
+        );
+    }
+
+    #[test]
+    fn body_reader_finished_cleanly() {
+        let read = |s: &str| {
+            let (_, mut body) = s.parse::<Response>().unwrap().split();
+            let mut buf = vec![];
+            let result = body.read_to_end(&mut buf);
+            (result.is_ok(), body.finished_cleanly())
+        };
+
+        let s = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        let (_, mut body) = s.parse::<Response>().unwrap().split();
+        let mut buf = [0; 5];
+        body.read_exact(&mut buf).unwrap();
+        assert!(!body.finished_cleanly());
+        assert_eq!(body.read(&mut buf).unwrap(), 0);
+        assert!(body.finished_cleanly());
 
-impl<R: Read> From<LimitedRead<R>> for Stream
-where
-    Stream: From<R>,
-{
-    fn from(limited_read: LimitedRead<R>) -> Stream {
-        limited_read.reader.into()
+        assert_eq!(read(s), (true, true));
+        assert_eq!(
+            read("HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello"),
+            (false, false)
+        );
+        assert_eq!(
+            read("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n"),
+            (true, true)
+        );
+        assert_eq!(
+            read("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n"),
+            (false, false)
+        );
     }
-}
 
-/// Extract the charset from a "Content-Type" header.
-///
-/// "Content-Type: text/plain; charset=iso8859-1" -> "iso8859-1"
-///
-/// *Internal API*
-pub(crate) fn charset_from_content_type(header: Option<&str>) -> &str {
-    header
-        .and_then(|header| {
-            header.find(';').and_then(|semi| {
-                (&header[semi + 1..])
-                    .find('=')
-                    .map(|equal| (&header[semi + equal + 2..]).trim())
-            })
-        })
-        .unwrap_or(DEFAULT_CHARACTER_SET)
-}
+    #[test]
+    fn content_type_predicates() {
+        let with_type = |content_type: &str| {
+            let s = format!("HTTP/1.1 200 OK\r\nContent-Type: {}\r\n\r\n", content_type);
+            let resp = s.parse::<Response>().unwrap();
+            (resp.is_json(), resp.is_html(), resp.is_text())
+        };
+        assert_eq!(with_type("application/json"), (true, false, false));
+        assert_eq!(with_type("application/vnd.api+json"), (true, false, false));
+        assert_eq!(
+            with_type("Application/JSON; charset=utf-8"),
+            (true, false, false)
+        );
+        assert_eq!(with_type("text/html"), (false, true, true));
+        assert_eq!(with_type("application/xhtml+xml"), (false, true, false));
+        assert_eq!(with_type("text/plain;charset=utf-8"), (false, false, true));
+        assert_eq!(with_type("application/jsonp"), (false, false, false));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let resp = "HTTP/1.1 200 OK\r\n\r\n".parse::<Response>().unwrap();
+        assert!(!resp.is_json() && !resp.is_html() && !resp.is_text());
+    }
 
     #[test]
-    fn content_type_without_charset() {
-        let s = "HTTP/1.1 200 OK\r\n\
-                 Content-Type: application/json\r\n\
+    fn metadata() {
+        let s = "HTTP/1.1 404 Not Found\r\n\
+                 Content-Type: text/plain\r\n\
+                 X-Multi: 1\r\n\
+                 X-Multi: 2\r\n\
                  \r\n\
-                 OK";
-        let resp = s.parse::<Response>().unwrap();
-        assert_eq!("application/json", resp.content_type());
+                 nope";
+        let mut resp = s.parse::<Response>().unwrap();
+        resp.set_url("https://example.com/missing".parse().unwrap());
+        let meta = resp.metadata();
+        assert_eq!(meta.status(), resp.status());
+        assert_eq!(meta.status_text(), resp.status_text());
+        assert_eq!(meta.http_version(), resp.http_version());
+        assert_eq!(meta.get_url(), resp.get_url());
+        assert_eq!(meta.headers_names(), resp.headers_names());
+        assert_eq!(meta.all("x-multi"), resp.all("x-multi"));
+
+        assert_eq!(resp.into_string().unwrap(), "nope");
+        assert_eq!(meta.clone().header("content-type"), Some("text/plain"));
     }
 
     #[test]
-    fn content_type_with_charset() {
-        let s = "HTTP/1.1 200 OK\r\n\
-                 Content-Type: application/json; charset=iso-8859-4\r\n\
-                 \r\n\
-                 OK";
+    fn request_url_components() {
+        let mut resp = Response::new(200, "OK", "").unwrap();
+        assert_eq!(resp.request_scheme(), None);
+        assert_eq!(resp.request_port(), None);
+
+        resp.set_url("https://user@example.com/a/b?q=1#frag".parse().unwrap());
+        assert_eq!(resp.request_scheme(), Some("https"));
+        assert_eq!(resp.request_host(), Some("example.com"));
+        assert_eq!(resp.request_port(), Some(443));
+        assert_eq!(resp.request_path(), Some("/a/b"));
+
+        resp.set_url("http://127.0.0.1:8080".parse().unwrap());
+        assert_eq!(resp.request_host(), Some("127.0.0.1"));
+        assert_eq!(resp.request_port(), Some(8080));
+        assert_eq!(resp.request_path(), Some("/"));
+    }
+
+    #[test]
+    fn www_authenticate() {
+        let s = "HTTP/1.1 401 Unauthorized\r\n\
+                 WWW-Authenticate: Basic realm=\"x\"\r\n\
+                 WWW-Authenticate: Digest realm=\"y\", nonce=\"abc\", qop=auth\r\n\
+                 \r\n";
         let resp = s.parse::<Response>().unwrap();
-        assert_eq!("application/json", resp.content_type());
+        let challenges = resp.www_authenticate();
+        assert_eq!(challenges.len(), 2);
+        assert_eq!(challenges[0].scheme(), "Basic");
+        assert_eq!(challenges[0].param("realm"), Some("x"));
+        assert_eq!(challenges[1].scheme(), "Digest");
+        assert_eq!(challenges[1].param("realm"), Some("y"));
+        assert_eq!(challenges[1].param("nonce"), Some("abc"));
+        assert_eq!(challenges[1].param("qop"), Some("auth"));
+
+        let resp = Response::new(200, "OK", "").unwrap();
+        assert!(resp.www_authenticate().is_empty());
     }
 
     #[test]
-    fn content_type_default() {
-        let s = "HTTP/1.1 200 OK\r\n\r\nOK";
+    #[cfg(feature = "checksum")]
+    fn into_reader_checksum() {
+        let hello = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+        let resp = "HTTP/1.1 200 OK\r\n\r\nhello world"
+            .parse::<Response>()
+            .unwrap();
+        let mut text = String::new();
+        resp.into_reader_checksum(Checksum::Sha256(hello.to_uppercase()))
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "hello world");
+
+        let resp = "HTTP/1.1 200 OK\r\n\r\nhello w0rld"
+            .parse::<Response>()
+            .unwrap();
+        let mut text = String::new();
+        let err = resp
+            .into_reader_checksum(Checksum::Sha256(hello.to_string()))
+            .read_to_string(&mut text)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(text, "hello w0rld");
+    }
+
+    #[test]
+    #[cfg(feature = "checksum")]
+    fn verify_content_md5() {
+        let resp = |md5: &str, body: &str| {
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-MD5: {}\r\nContent-Length: {}\r\n\r\n{}",
+                md5,
+                body.len(),
+                body
+            )
+            .parse::<Response>()
+            .unwrap()
+        };
+        let md5 = "XrY7u+Ae7tCTyyK7j1rNww==";
+
+        let body = resp(md5, "hello world").verify_content_md5().unwrap();
+        assert_eq!(body, b"hello world");
+
+        let err = resp(md5, "hello w0rld").verify_content_md5().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("doesn't match"), "{}", err);
+
+        let err = resp("not base64!", "hello world")
+            .verify_content_md5()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let resp = "HTTP/1.1 200 OK\r\n\r\nhello world"
+            .parse::<Response>()
+            .unwrap();
+        let err = resp.verify_content_md5().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn cors_preflight() {
+        let s = "HTTP/1.1 204 No Content\r\n\
+                 Access-Control-Allow-Origin: *\r\n\
+                 Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+                 Access-Control-Allow-Methods: DELETE\r\n\
+                 Access-Control-Allow-Headers: Content-Type,X-Api-Key, ,Authorization\r\n\
+                 Access-Control-Max-Age: 86400\r\n\
+                 \r\n";
         let resp = s.parse::<Response>().unwrap();
-        assert_eq!("text/plain", resp.content_type());
+        assert_eq!(resp.cors_allow_origin(), Some("*"));
+        assert_eq!(
+            resp.cors_allow_methods(),
+            vec!["GET", "POST", "OPTIONS", "DELETE"]
+        );
+        assert_eq!(
+            resp.cors_allow_headers(),
+            vec!["Content-Type", "X-Api-Key", "Authorization"]
+        );
+
+        let resp = Response::new(200, "OK", "").unwrap();
+        assert_eq!(resp.cors_allow_origin(), None);
+        assert!(resp.cors_allow_methods().is_empty());
+        assert!(resp.cors_allow_headers().is_empty());
     }
 
     #[test]
-    fn charset() {
+    fn vary() {
         let s = "HTTP/1.1 200 OK\r\n\
-                 Content-Type: application/json; charset=iso-8859-4\r\n\
-                 \r\n\
-                 OK";
+                 Vary: Accept-Encoding , User-Agent\r\n\
+                 Vary: accept-encoding,Cookie\r\n\
+                 \r\n";
         let resp = s.parse::<Response>().unwrap();
-        assert_eq!("iso-8859-4", resp.charset());
+        assert_eq!(resp.vary(), vec!["accept-encoding", "user-agent", "cookie"]);
+
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Vary: Accept\r\n\
+                 Vary: *\r\n\
+                 \r\n";
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!(resp.vary(), vec!["*"]);
+
+        let resp = Response::new(200, "OK", "").unwrap();
+        assert!(resp.vary().is_empty());
     }
 
     #[test]
-    fn charset_default() {
+    fn content_language() {
         let s = "HTTP/1.1 200 OK\r\n\
-                 Content-Type: application/json\r\n\
-                 \r\n\
-                 OK";
+                 Content-Language: en, fr\r\n\
+                 Content-Language: de-CH,, \r\n\
+                 \r\n";
         let resp = s.parse::<Response>().unwrap();
-        assert_eq!("utf-8", resp.charset());
+        assert_eq!(resp.content_language(), vec!["en", "fr", "de-CH"]);
+
+        let resp = Response::new(200, "OK", "").unwrap();
+        assert!(resp.content_language().is_empty());
     }
 
     #[test]
-    fn chunked_transfer() {
+    fn forwarded() {
         let s = "HTTP/1.1 200 OK\r\n\
-                 Transfer-Encoding: Chunked\r\n\
-                 \r\n\
-                 3\r\n\
-                 hel\r\n\
-                 b\r\n\
-                 lo world!!!\r\n\
-                 0\r\n\
+                 Forwarded: for=192.0.2.43, for=\"[2001:db8:cafe::17]\";proto=https\r\n\
+                 Forwarded: for=unknown;by=10.0.0.1\r\n\
+                 X-Forwarded-For: 203.0.113.195, 70.41.3.18\r\n\
+                 X-Forwarded-For: 150.172.238.178\r\n\
+                 X-Forwarded-Proto: https, http\r\n\
                  \r\n";
         let resp = s.parse::<Response>().unwrap();
-        assert_eq!("hello world!!!", resp.into_string().unwrap());
+        let f = resp.forwarded();
+        assert_eq!(f.len(), 3);
+        assert_eq!(f[1].for_node(), Some("[2001:db8:cafe::17]"));
+        assert_eq!(f[1].proto(), Some("https"));
+        assert_eq!(f[2].by_node(), Some("10.0.0.1"));
+        assert_eq!(
+            resp.x_forwarded_for(),
+            vec!["203.0.113.195", "70.41.3.18", "150.172.238.178"]
+        );
+        assert_eq!(resp.x_forwarded_proto(), Some("https"));
+
+        let resp = Response::new(200, "OK", "").unwrap();
+        assert!(resp.forwarded().is_empty());
+        assert!(resp.x_forwarded_for().is_empty());
+        assert_eq!(resp.x_forwarded_proto(), None);
     }
 
     #[test]
-    #[cfg(feature = "json")]
-    fn parse_simple_json() {
+    fn set_cookie_raw() {
         let s = "HTTP/1.1 200 OK\r\n\
-             \r\n\
-             {\"hello\":\"world\"}";
+                 Set-Cookie: session=abc; Domain=example.com; Path=/; Secure\r\n\
+                 Content-Type: text/plain\r\n\
+                 set-cookie: theme=\"dark\"; Expires=Wed, 21 Oct 2015 07:28:00 GMT\r\n\
+                 \r\n";
         let resp = s.parse::<Response>().unwrap();
-        let v: serde_json::Value = resp.into_json().unwrap();
-        let compare = "{\"hello\":\"world\"}"
-            .parse::<serde_json::Value>()
+        assert_eq!(
+            resp.set_cookie_raw(),
+            vec![
+                "session=abc; Domain=example.com; Path=/; Secure",
+                "theme=\"dark\"; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+            ]
+        );
+
+        let resp = Response::new(200, "OK", "").unwrap();
+        assert!(resp.set_cookie_raw().is_empty());
+    }
+
+    #[test]
+    fn copy_to_with_progress() {
+        // Hands out 10 bytes every 20ms, 200 bytes in total.
+        struct SlowRead(usize);
+        impl Read for SlowRead {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0 == 0 {
+                    return Ok(0);
+                }
+                std::thread::sleep(Duration::from_millis(20));
+                let n = self.0.min(10).min(buf.len());
+                buf[..n].copy_from_slice(&b"abcdefghij"[..n]);
+                self.0 -= n;
+                Ok(n)
+            }
+        }
+        let head = io::Cursor::new(b"HTTP/1.1 200 OK\r\nContent-Length: 200\r\n\r\n".to_vec());
+        let resp = Response::from_reader(head.chain(SlowRead(200))).unwrap();
+
+        let mut calls: Vec<Progress> = vec![];
+        let mut out = vec![];
+        let n = resp
+            .copy_to_with_progress(&mut out, Duration::from_millis(100), |p| calls.push(p))
             .unwrap();
-        assert_eq!(v, compare);
+        assert_eq!(n, 200);
+        assert_eq!(out.len(), 200);
+
+        // 400ms of reading at one callback per 100ms.
+        assert!(calls.len() >= 2 && calls.len() <= 4, "{:?}", calls);
+        for pair in calls.windows(2) {
+            assert!(pair[1].bytes() > pair[0].bytes());
+            assert!(pair[1].elapsed() - pair[0].elapsed() >= Duration::from_millis(100));
+        }
+        assert!(calls[0].bytes_per_second() > 0.0);
+    }
+
+    #[test]
+    fn copy_to_buffered() {
+        let body: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        let resp = || {
+            let mut s = b"HTTP/1.1 200 OK\r\nContent-Length: 100000\r\n\r\n".to_vec();
+            s.extend_from_slice(&body);
+            Response::from_reader(io::Cursor::new(s)).unwrap()
+        };
+
+        let mut tiny = vec![];
+        assert_eq!(resp().copy_to_buffered(&mut tiny, 7).unwrap(), 100_000);
+        let mut large = vec![];
+        assert_eq!(
+            resp().copy_to_buffered(&mut large, 1024 * 1024).unwrap(),
+            100_000
+        );
+        let mut zero = vec![];
+        assert_eq!(resp().copy_to_buffered(&mut zero, 0).unwrap(), 100_000);
+        assert_eq!(tiny, body);
+        assert_eq!(large, body);
+        assert_eq!(zero, body);
     }
 
     #[test]
     #[cfg(feature = "json")]
-    fn parse_deserialize_json() {
-        use serde::Deserialize;
+    fn json_deserializer() {
+        use serde::de::{Deserializer, IgnoredAny, MapAccess, Visitor};
 
-        #[derive(Deserialize)]
-        struct Hello {
-            hello: String,
+        // Collects the keys of a JSON object without deserializing the values.
+        struct Keys;
+        impl<'de> Visitor<'de> for Keys {
+            type Value = Vec<String>;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an object")
+            }
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Vec<String>, A::Error> {
+                let mut keys = vec![];
+                while let Some((key, _)) = map.next_entry::<String, IgnoredAny>()? {
+                    keys.push(key);
+                }
+                Ok(keys)
+            }
         }
 
         let s = "HTTP/1.1 200 OK\r\n\
-             \r\n\
-             {\"hello\":\"world\"}";
+                 Content-Type: application/json\r\n\
+                 \r\n\
+                 {\"hello\": \"world\", \"nested\": {\"a\": [1, 2]}, \"n\": 3}";
         let resp = s.parse::<Response>().unwrap();
-        let v: Hello = resp.into_json::<Hello>().unwrap();
-        assert_eq!(v.hello, "world");
+        let keys = resp.json_deserializer().deserialize_any(Keys).unwrap();
+        assert_eq!(keys, vec!["hello", "nested", "n"]);
     }
 
     #[test]
-    fn parse_borked_header() {
-        let s = "HTTP/1.1 BORKED\r\n".to_string();
-        let err = s.parse::<Response>().unwrap_err();
-        assert_eq!(err.kind(), BadStatus);
+    fn byte_ranges_multipart() {
+        let s = "HTTP/1.1 206 Partial Content\r\n\
+                 Content-Type: multipart/byteranges; boundary=THIS_STRING_SEPARATES\r\n\
+                 \r\n\
+                 preamble\r\n\
+                 --THIS_STRING_SEPARATES\r\n\
+                 Content-Type: text/plain\r\n\
+                 Content-Range: bytes 0-9/40\r\n\
+                 \r\n\
+                 0123456789\r\n\
+                 --THIS_STRING_SEPARATES\r\n\
+                 Content-Type: text/plain\r\n\
+                 Content-Range: bytes 30-39/40\r\n\
+                 \r\n\
+                 ab\r\n--THIS\r\n\
+                 --THIS_STRING_SEPARATES--\r\n";
+        let resp = s.parse::<Response>().unwrap();
+        let parts: Vec<_> = resp.into_byte_ranges().map(|p| p.unwrap()).collect();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].start(), 0);
+        assert_eq!(parts[0].end(), 9);
+        assert_eq!(parts[0].complete_length(), Some(40));
+        assert_eq!(parts[0].content_type(), Some("text/plain"));
+        assert_eq!(parts[0].body(), b"0123456789");
+        assert_eq!(parts[1].start(), 30);
+        // The body may contain CRLFs and things that look like the boundary.
+        assert_eq!(parts[1].body(), b"ab\r\n--THIS");
+    }
+
+    #[test]
+    fn byte_ranges_single_and_truncated() {
+        let s = "HTTP/1.1 206 Partial Content\r\n\
+                 Content-Range: bytes 5-9/*\r\n\
+                 Content-Length: 5\r\n\
+                 \r\n\
+                 hello";
+        let resp = s.parse::<Response>().unwrap();
+        let parts: Vec<_> = resp.into_byte_ranges().map(|p| p.unwrap()).collect();
+        assert_eq!(parts.len(), 1);
+        assert_eq!((parts[0].start(), parts[0].end()), (5, 9));
+        assert_eq!(parts[0].complete_length(), None);
+        assert_eq!(parts[0].body(), b"hello");
+
+        let s = "HTTP/1.1 206 Partial Content\r\n\
+                 Content-Type: multipart/byteranges; boundary=\"b\"\r\n\
+                 \r\n\
+                 --b\r\n\
+                 Content-Range: bytes 0-99/200\r\n\
+                 \r\n\
+                 short";
+        let resp = s.parse::<Response>().unwrap();
+        let results: Vec<_> = resp.into_byte_ranges().collect();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].as_ref().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let resp = Response::new(200, "OK", "whole").unwrap();
+        let results: Vec<_> = resp.into_byte_ranges().collect();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+
+    #[test]
+    fn read_until() {
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Content-Length: 12\r\n\
+                 \r\n\
+                 hello\nworld\n";
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!(resp.read_until(b'\n', 100).unwrap(), b"hello\n");
+
+        // The limit is hit before the delimiter.
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!(resp.read_until(b'\n', 3).unwrap(), b"hel");
+
+        // The body ends before the delimiter.
+        let resp = s.parse::<Response>().unwrap();
+        assert_eq!(resp.read_until(b'!', 100).unwrap(), b"hello\nworld\n");
+    }
+
+    #[test]
+    fn eq_ignoring() {
+        let resp = |date: &str, body: &str| {
+            format!(
+                "HTTP/1.1 200 OK\r\n\
+                 Content-Type: text/plain\r\n\
+                 Date: {}\r\n\
+                 Content-Length: {}\r\n\
+                 \r\n\
+                 {}",
+                date,
+                body.len(),
+                body
+            )
+            .parse::<Response>()
+            .unwrap()
+        };
+        let monday = "Mon, 01 Feb 2021 10:00:00 GMT";
+        let tuesday = "Tue, 02 Feb 2021 12:00:00 GMT";
+
+        assert!(resp(monday, "hello").eq_ignoring(resp(tuesday, "hello"), &["Date"]));
+        assert!(resp(monday, "hello").eq_ignoring(resp(monday, "hello"), &[]));
+        assert!(!resp(monday, "hello").eq_ignoring(resp(tuesday, "hello"), &[]));
+        assert!(!resp(monday, "hello").eq_ignoring(resp(monday, "howdy"), &["date"]));
+
+        let other = "HTTP/1.1 404 Not Found\r\n\
+                     Content-Type: text/plain\r\n\
+                     Content-Length: 5\r\n\
+                     \r\n\
+                     hello"
+            .parse::<Response>()
+            .unwrap();
+        assert!(!resp(monday, "hello").eq_ignoring(other, &["date"]));
+    }
+
+    #[test]
+    fn body_mut_read_twice() {
+        let s = "HTTP/1.1 200 OK\r\n\
+                 Transfer-Encoding: chunked\r\n\
+                 \r\n\
+                 5\r\nhello\r\n0\r\n\r\n";
+        let mut resp = s.parse::<Response>().unwrap();
+        assert_eq!(resp.body_mut().unwrap(), b"hello");
+        assert_eq!(resp.body_mut().unwrap(), b"hello");
+        resp.body_mut().unwrap().extend_from_slice(b" world");
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.into_string().unwrap(), "hello world");
     }
 
     #[test]
@@ -789,6 +3918,229 @@ mod tests {
     }
 }
 
+// TeeRead copies everything read from reader into sink.
+struct TeeRead<R, W> {
+    reader: R,
+    sink: W,
+}
+
+impl<R: Read, W: Write> Read for TeeRead<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amount = self.reader.read(buf)?;
+        if amount == 0 {
+            self.sink.flush()?;
+        } else {
+            self.sink.write_all(&buf[..amount])?;
+        }
+        Ok(amount)
+    }
+}
+
+/// An iterator over the fixed size records of a body, see
+/// [`Response::into_records`].
+pub struct Records {
+    reader: Box<dyn Read + Send>,
+    record_size: usize,
+    partial_is_error: bool,
+    done: bool,
+}
+
+impl Records {
+    /// Make a partial record at the end of the body an error of kind
+    /// [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof), rather than
+    /// a short last record.
+    pub fn partial_is_error(mut self) -> Self {
+        self.partial_is_error = true;
+        self
+    }
+}
+
+impl fmt::Debug for Records {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Records[{} bytes]", self.record_size)
+    }
+}
+
+impl Iterator for Records {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut record = vec![0; self.record_size];
+        let mut len = 0;
+        while len < record.len() {
+            match self.reader.read(&mut record[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        if len == record.len() {
+            return Some(Ok(record));
+        }
+        self.done = true;
+        if len == 0 {
+            None
+        } else if self.partial_is_error {
+            Some(Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "body ended in a partial record",
+            )))
+        } else {
+            record.truncate(len);
+            Some(Ok(record))
+        }
+    }
+}
+
+/// The rate a body is read at, shared by a reader and whoever controls it.
+///
+/// See [`Response::into_reader_rate_limited`]. Clones of a handle share
+/// the same rate.
+#[derive(Debug, Clone)]
+pub struct RateLimitHandle(Arc<AtomicU64>);
+
+impl RateLimitHandle {
+    /// A handle allowing `bytes_per_sec` bytes per second.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        RateLimitHandle(Arc::new(AtomicU64::new(bytes_per_sec)))
+    }
+
+    /// Change the allowed rate, in bytes per second. Readers using this handle
+    /// pick it up on their next read. 0 pauses them.
+    pub fn set_rate(&self, bytes_per_sec: u64) {
+        self.0.store(bytes_per_sec, Ordering::SeqCst);
+    }
+
+    /// The allowed rate, in bytes per second.
+    pub fn rate(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// How long a paused RateLimitRead waits before looking at the rate again.
+const RATE_LIMIT_PAUSE_POLL: Duration = Duration::from_millis(10);
+
+// RateLimitRead sleeps after each read until the bytes read since the rate
+// last changed are within the rate. Reads are capped to a tenth of a
+// second's worth of bytes, so that a change of rate is picked up soon.
+struct RateLimitRead<R> {
+    reader: R,
+    limit: RateLimitHandle,
+    rate: u64,
+    start: Instant,
+    bytes: u64,
+}
+
+impl<R: Read> Read for RateLimitRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut rate = self.limit.rate();
+        while rate == 0 {
+            std::thread::sleep(RATE_LIMIT_PAUSE_POLL);
+            rate = self.limit.rate();
+        }
+        if rate != self.rate {
+            self.rate = rate;
+            self.start = Instant::now();
+            self.bytes = 0;
+        }
+
+        let max = (rate / 10).max(1).min(buf.len() as u64) as usize;
+        let amount = self.reader.read(&mut buf[..max])?;
+        self.bytes += amount as u64;
+
+        let due = Duration::from_secs_f64(self.bytes as f64 / rate as f64);
+        let elapsed = self.start.elapsed();
+        if due > elapsed {
+            std::thread::sleep(due - elapsed);
+        }
+        Ok(amount)
+    }
+}
+
+/// How far [`Response::copy_to_with_progress`] has come.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    bytes: u64,
+    elapsed: Duration,
+}
+
+impl Progress {
+    /// Bytes of the body copied so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Time since the copy started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The average rate of the copy so far.
+    pub fn bytes_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.bytes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// An expected checksum of a response body.
+///
+/// See [`Response::into_reader_checksum`].
+///
+/// Requires feature `ureq = { version = "*", features = ["checksum"] }`
+#[cfg(feature = "checksum")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksum {
+    /// A SHA-256 digest as a hex string. Case is ignored.
+    Sha256(String),
+}
+
+// ChecksumRead hashes everything read from reader, and fails at the end
+// if the digest doesn't match.
+#[cfg(feature = "checksum")]
+struct ChecksumRead<R> {
+    reader: R,
+    hasher: Sha256,
+    expected: Checksum,
+    done: bool,
+}
+
+#[cfg(feature = "checksum")]
+impl<R: Read> Read for ChecksumRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amount = self.reader.read(buf)?;
+        if amount > 0 {
+            self.hasher.update(&buf[..amount]);
+        } else if !self.done {
+            self.done = true;
+            let actual: String = self
+                .hasher
+                .finalize_reset()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            let Checksum::Sha256(expected) = &self.expected;
+            if !actual.eq_ignore_ascii_case(expected.trim()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("SHA-256 mismatch: expected {}, got {}", expected, actual),
+                ));
+            }
+        }
+        Ok(amount)
+    }
+}
+
 // ErrorReader returns an error for every read.
 // The error is as close to a clone of the underlying
 // io::Error as we can get.
@@ -2,12 +2,12 @@ use log::debug;
 use std::io::{self, BufRead, BufReader, Read, Write};
 use std::net::SocketAddr;
 use std::net::TcpStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::time::Instant;
 use std::{fmt, io::Cursor};
 
-use chunked_transfer::Decoder as ChunkDecoder;
-
 #[cfg(feature = "tls")]
 use rustls::ClientSession;
 #[cfg(feature = "tls")]
@@ -15,6 +15,7 @@ use rustls::StreamOwned;
 #[cfg(feature = "socks-proxy")]
 use socks::{TargetAddr, ToTargetAddr};
 
+use crate::clock::{ArcClock, Clock};
 use crate::proxy::Proxy;
 use crate::{error::Error, proxy::Proto};
 
@@ -23,6 +24,24 @@ use crate::unit::Unit;
 
 pub(crate) struct Stream {
     inner: BufReader<Inner>,
+    // number of requests whose response was fully read on this connection.
+    requests_served: usize,
+    // when the connection was last handed back to the pool.
+    last_used: Instant,
+    // false if the request/response exchange on this connection was cut
+    // short, leaving it in a state where it can't carry another request.
+    reusable: bool,
+    // counters of the agent that opened the connection.
+    io_counters: Option<Arc<IoCounters>>,
+    // bytes given back with unread(), read before the connection's.
+    unread: Cursor<Vec<u8>>,
+}
+
+/// Bytes read and written on all connections of an agent.
+#[derive(Debug, Default)]
+pub(crate) struct IoCounters {
+    pub(crate) read: AtomicU64,
+    pub(crate) written: AtomicU64,
 }
 
 enum Inner {
@@ -40,11 +59,37 @@ enum Inner {
 pub(crate) struct DeadlineStream {
     stream: Stream,
     deadline: Option<Instant>,
+    clock: ArcClock,
+    // Deadline of the current read call on the body, if it has one.
+    call_deadline: CallDeadline,
+    // The agent's read timeout, which still bounds each read from the
+    // socket when only the call has a deadline.
+    timeout_read: Option<Duration>,
 }
 
+/// Deadline for one read call, shared between a body reader and the
+/// DeadlineStream under it.
+pub(crate) type CallDeadline = Arc<Mutex<Option<Instant>>>;
+
 impl DeadlineStream {
-    pub(crate) fn new(stream: Stream, deadline: Option<Instant>) -> Self {
-        DeadlineStream { stream, deadline }
+    pub(crate) fn new(stream: Stream, deadline: Option<Instant>, clock: ArcClock) -> Self {
+        DeadlineStream {
+            stream,
+            deadline,
+            clock,
+            call_deadline: CallDeadline::default(),
+            timeout_read: None,
+        }
+    }
+
+    pub(crate) fn with_call_deadline(
+        mut self,
+        call_deadline: CallDeadline,
+        timeout_read: Option<Duration>,
+    ) -> Self {
+        self.call_deadline = call_deadline;
+        self.timeout_read = timeout_read;
+        self
     }
 }
 
@@ -56,8 +101,18 @@ impl From<DeadlineStream> for Stream {
 
 impl BufRead for DeadlineStream {
     fn fill_buf(&mut self) -> io::Result<&[u8]> {
-        if let Some(deadline) = self.deadline {
-            let timeout = time_until_deadline(deadline)?;
+        let call_deadline = *self.call_deadline.lock().unwrap();
+        let deadline = match (self.deadline, call_deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        if let Some(deadline) = deadline {
+            let mut timeout = time_until_deadline(deadline, &*self.clock)?;
+            if self.deadline.is_none() {
+                if let Some(timeout_read) = self.timeout_read {
+                    timeout = timeout.min(timeout_read);
+                }
+            }
             if let Some(socket) = self.stream.socket() {
                 socket.set_read_timeout(Some(timeout))?;
                 socket.set_write_timeout(Some(timeout))?;
@@ -97,8 +152,8 @@ impl Read for DeadlineStream {
 
 // If the deadline is in the future, return the remaining time until
 // then. Otherwise return a TimedOut error.
-fn time_until_deadline(deadline: Instant) -> io::Result<Duration> {
-    let now = Instant::now();
+pub(crate) fn time_until_deadline(deadline: Instant, clock: &dyn Clock) -> io::Result<Duration> {
+    let now = clock.now();
     match deadline.checked_duration_since(now) {
         None => Err(io_err_timeout("timed out reading response".to_string())),
         Some(duration) => Ok(duration),
@@ -129,12 +184,47 @@ impl Stream {
     pub(crate) fn from_vec(v: Vec<u8>) -> Stream {
         Stream::logged_create(Stream {
             inner: BufReader::new(Inner::Test(Box::new(Cursor::new(v)), vec![])),
+            requests_served: 0,
+            last_used: Instant::now(),
+            reusable: true,
+            io_counters: None,
+            unread: Cursor::default(),
+        })
+    }
+
+    // An empty stream to stand in for this one once it's been taken away.
+    // For test streams, it keeps what was written, for to_write_vec().
+    pub(crate) fn emptied(&self) -> Stream {
+        let written = match self.inner.get_ref() {
+            Inner::Test(_, writer) => writer.clone(),
+            _ => vec![],
+        };
+        let mut stream = Stream::from_vec(vec![]);
+        if let Inner::Test(_, writer) = stream.inner.get_mut() {
+            *writer = written;
+        }
+        stream
+    }
+
+    pub(crate) fn from_read(r: impl Read + Send + 'static) -> Stream {
+        Stream::logged_create(Stream {
+            inner: BufReader::new(Inner::Test(Box::new(SyncRead(Mutex::new(r))), vec![])),
+            requests_served: 0,
+            last_used: Instant::now(),
+            reusable: true,
+            io_counters: None,
+            unread: Cursor::default(),
         })
     }
 
     fn from_tcp_stream(t: TcpStream) -> Stream {
         Stream::logged_create(Stream {
             inner: BufReader::new(Inner::Http(t)),
+            requests_served: 0,
+            last_used: Instant::now(),
+            reusable: true,
+            io_counters: None,
+            unread: Cursor::default(),
         })
     }
 
@@ -142,6 +232,11 @@ impl Stream {
     fn from_tls_stream(t: StreamOwned<ClientSession, TcpStream>) -> Stream {
         Stream::logged_create(Stream {
             inner: BufReader::new(Inner::Https(t)),
+            requests_served: 0,
+            last_used: Instant::now(),
+            reusable: true,
+            io_counters: None,
+            unread: Cursor::default(),
         })
     }
 
@@ -172,6 +267,9 @@ impl Stream {
         }
     }
     pub fn is_poolable(&self) -> bool {
+        if !self.reusable {
+            return false;
+        }
         match self.inner.get_ref() {
             Inner::Http(_) => true,
             #[cfg(feature = "tls")]
@@ -180,6 +278,28 @@ impl Stream {
         }
     }
 
+    // Record that one more response was fully read on this connection, and
+    // return how many have been so far.
+    pub(crate) fn count_request(&mut self) -> usize {
+        self.requests_served += 1;
+        self.requests_served
+    }
+
+    // Prevent the connection from going back into the pool.
+    pub(crate) fn disable_reuse(&mut self) {
+        self.reusable = false;
+    }
+
+    // Mark the connection as idle, starting now.
+    pub(crate) fn mark_idle(&mut self) {
+        self.last_used = Instant::now();
+    }
+
+    // How long the connection has been idle since it was last marked.
+    pub(crate) fn idle_time(&self) -> Duration {
+        self.last_used.elapsed()
+    }
+
     pub(crate) fn reset(&mut self) -> io::Result<()> {
         // When we are turning this back into a regular, non-deadline Stream,
         // remove any timeouts we set.
@@ -200,6 +320,28 @@ impl Stream {
         }
     }
 
+    // Put bytes that were read back in front of the stream, to be read again.
+    pub(crate) fn unread(&mut self, bytes: Vec<u8>) {
+        let mut rest = bytes;
+        self.unread.read_to_end(&mut rest).unwrap();
+        self.unread = Cursor::new(rest);
+    }
+
+    fn has_unread(&self) -> bool {
+        (self.unread.position() as usize) < self.unread.get_ref().len()
+    }
+
+    // Count the bytes read and written from now on in `counters`.
+    pub(crate) fn count_io(&mut self, counters: Arc<IoCounters>) {
+        self.io_counters = Some(counters);
+    }
+
+    fn count_read(&self, amount: usize) {
+        if let Some(counters) = &self.io_counters {
+            counters.read.fetch_add(amount as u64, Ordering::Relaxed);
+        }
+    }
+
     pub(crate) fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
         if let Some(socket) = self.socket() {
             socket.set_read_timeout(timeout)
@@ -219,7 +361,24 @@ impl Stream {
 
 impl Read for Stream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
+        if self.has_unread() {
+            // Already counted when first read.
+            return self.unread.read(buf);
+        }
+        let amount = self.inner.read(buf)?;
+        self.count_read(amount);
+        Ok(amount)
+    }
+}
+
+// SyncRead makes a Read that is only Send also Sync, so it can be held
+// in a Stream. We only ever go through get_mut(), so the lock is never
+// actually taken and can't be poisoned.
+struct SyncRead<R>(Mutex<R>);
+
+impl<R: Read> Read for SyncRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.get_mut().unwrap().read(buf)
     }
 }
 
@@ -236,21 +395,19 @@ impl Read for Inner {
 
 impl BufRead for Stream {
     fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.has_unread() {
+            return self.unread.fill_buf();
+        }
         self.inner.fill_buf()
     }
 
     fn consume(&mut self, amt: usize) {
-        self.inner.consume(amt)
-    }
-}
-
-impl<R: Read> From<ChunkDecoder<R>> for Stream
-where
-    R: Read,
-    Stream: From<R>,
-{
-    fn from(chunk_decoder: ChunkDecoder<R>) -> Stream {
-        chunk_decoder.into_inner().into()
+        if self.has_unread() {
+            self.unread.consume(amt);
+            return;
+        }
+        self.inner.consume(amt);
+        self.count_read(amt);
     }
 }
 
@@ -284,12 +441,16 @@ fn is_close_notify(e: &std::io::Error) -> bool {
 
 impl Write for Stream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        match self.inner.get_mut() {
+        let amount = match self.inner.get_mut() {
             Inner::Http(sock) => sock.write(buf),
             #[cfg(feature = "tls")]
             Inner::Https(stream) => stream.write(buf),
             Inner::Test(_, writer) => writer.write(buf),
+        }?;
+        if let Some(counters) = &self.io_counters {
+            counters.written.fetch_add(amount as u64, Ordering::Relaxed);
         }
+        Ok(amount)
     }
     fn flush(&mut self) -> io::Result<()> {
         match self.inner.get_mut() {
@@ -311,7 +472,7 @@ pub(crate) fn connect_http(unit: &Unit, hostname: &str) -> Result<Stream, Error>
     //
     let port = unit.url.port().unwrap_or(80);
 
-    connect_host(unit, hostname, port).map(Stream::from_tcp_stream)
+    connect_host(unit, hostname, port, connect_deadline(unit)).map(Stream::from_tcp_stream)
 }
 
 #[cfg(all(feature = "tls", feature = "native-certs"))]
@@ -327,17 +488,17 @@ fn configure_certs(config: &mut rustls::ClientConfig) {
         .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
 }
 
+// The TLS config for agents that don't set one.
 #[cfg(feature = "tls")]
-pub(crate) fn connect_https(unit: &Unit, hostname: &str) -> Result<Stream, Error> {
-    use once_cell::sync::Lazy;
-    use std::sync::Arc;
-
-    static TLS_CONF: Lazy<Arc<rustls::ClientConfig>> = Lazy::new(|| {
+pub(crate) static TLS_CONF: once_cell::sync::Lazy<Arc<rustls::ClientConfig>> =
+    once_cell::sync::Lazy::new(|| {
         let mut config = rustls::ClientConfig::new();
         configure_certs(&mut config);
         Arc::new(config)
     });
 
+#[cfg(feature = "tls")]
+pub(crate) fn connect_https(unit: &Unit, hostname: &str) -> Result<Stream, Error> {
     let port = unit.url.port().unwrap_or(443);
 
     let sni = webpki::DNSNameRef::try_from_ascii_str(hostname)
@@ -349,22 +510,84 @@ pub(crate) fn connect_https(unit: &Unit, hostname: &str) -> Result<Stream, Error
         .as_ref()
         .map(|c| &c.0)
         .unwrap_or(&*TLS_CONF);
-    let sess = rustls::ClientSession::new(&tls_conf, sni);
-
-    let sock = connect_host(unit, hostname, port)?;
+    let mut sess = rustls::ClientSession::new(&tls_conf, sni);
+
+    let deadline = connect_deadline(unit);
+    let mut sock = connect_host(unit, hostname, port, deadline)?;
+    tls_handshake(
+        unit,
+        hostname,
+        &tls_conf.versions,
+        deadline,
+        &mut sess,
+        &mut sock,
+    )?;
 
     let stream = rustls::StreamOwned::new(sess, sock);
 
     Ok(Stream::from_tls_stream(stream))
 }
 
-pub(crate) fn connect_host(unit: &Unit, hostname: &str, port: u16) -> Result<TcpStream, Error> {
-    let connect_deadline: Option<Instant> =
-        if let Some(timeout_connect) = unit.agent.config.timeout_connect {
-            Instant::now().checked_add(timeout_connect)
-        } else {
-            unit.deadline
-        };
+// Complete the TLS handshake before handing out the stream, so that it's
+// bounded by the connect timeout rather than the read and write timeouts.
+#[cfg(feature = "tls")]
+fn tls_handshake(
+    unit: &Unit,
+    hostname: &str,
+    versions: &[rustls::ProtocolVersion],
+    connect_deadline: Option<Instant>,
+    sess: &mut ClientSession,
+    sock: &mut TcpStream,
+) -> Result<(), Error> {
+    use rustls::Session;
+
+    let read_timeout = sock.read_timeout()?;
+    let write_timeout = sock.write_timeout()?;
+    if let Some(deadline) = connect_deadline {
+        let timeout = time_until_deadline(deadline, &*unit.clock()).map_err(connect_timed_out)?;
+        sock.set_read_timeout(Some(timeout))?;
+        sock.set_write_timeout(Some(timeout))?;
+    }
+    while sess.is_handshaking() {
+        sess.complete_io(sock).map_err(|e| match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                connect_timed_out(io_err_timeout("timed out during TLS handshake".to_string()))
+            }
+            _ if e.get_ref().map(|e| e.is::<rustls::TLSError>()) == Some(true) => {
+                let version = match sess.get_protocol_version() {
+                    Some(v) => format!("negotiated {:?}", v),
+                    None => format!("attempted {:?}", versions),
+                };
+                let msg = format!("TLS handshake with {} failed, {}", hostname, version);
+                ErrorKind::Tls.msg(&msg).src(e)
+            }
+            _ => e.into(),
+        })?;
+    }
+    sock.set_read_timeout(read_timeout)?;
+    sock.set_write_timeout(write_timeout)?;
+    Ok(())
+}
+
+// The connect timeout takes precedence over the overall deadline.
+fn connect_deadline(unit: &Unit) -> Option<Instant> {
+    if let Some(timeout_connect) = unit.agent.config.timeout_connect {
+        unit.clock().now().checked_add(timeout_connect)
+    } else {
+        unit.deadline
+    }
+}
+
+fn connect_timed_out(e: io::Error) -> Error {
+    ErrorKind::ConnectionFailed.msg("Connect timed out").src(e)
+}
+
+pub(crate) fn connect_host(
+    unit: &Unit,
+    hostname: &str,
+    port: u16,
+    connect_deadline: Option<Instant>,
+) -> Result<TcpStream, Error> {
     let proxy: Option<Proxy> = unit.agent.config.proxy.clone();
     let netloc = match proxy {
         Some(ref proxy) => format!("{}:{}", proxy.server, proxy.port),
@@ -393,7 +616,9 @@ pub(crate) fn connect_host(unit: &Unit, hostname: &str, port: u16) -> Result<Tcp
     for sock_addr in sock_addrs {
         // ensure connect timeout or overall timeout aren't yet hit.
         let timeout = match connect_deadline {
-            Some(deadline) => Some(time_until_deadline(deadline)?),
+            Some(deadline) => {
+                Some(time_until_deadline(deadline, &*unit.clock()).map_err(connect_timed_out)?)
+            }
             None => None,
         };
 
@@ -425,19 +650,22 @@ pub(crate) fn connect_host(unit: &Unit, hostname: &str, port: u16) -> Result<Tcp
     let mut stream = if let Some(stream) = any_stream {
         stream
     } else if let Some(e) = any_err {
+        if e.kind() == io::ErrorKind::TimedOut {
+            return Err(connect_timed_out(e));
+        }
         return Err(ErrorKind::ConnectionFailed.msg("Connect error").src(e));
     } else {
         panic!("shouldn't happen: failed to connect to all IPs, but no error");
     };
 
     if let Some(deadline) = unit.deadline {
-        stream.set_read_timeout(Some(time_until_deadline(deadline)?))?;
+        stream.set_read_timeout(Some(time_until_deadline(deadline, &*unit.clock())?))?;
     } else {
         stream.set_read_timeout(unit.agent.config.timeout_read)?;
     }
 
     if let Some(deadline) = unit.deadline {
-        stream.set_write_timeout(Some(time_until_deadline(deadline)?))?;
+        stream.set_write_timeout(Some(time_until_deadline(deadline, &*unit.clock())?))?;
     } else {
         stream.set_write_timeout(unit.agent.config.timeout_write)?;
     }
@@ -556,7 +784,7 @@ fn connect_socks5(
         let (lock, cvar) = &*master_signal;
         let done = lock.lock().unwrap();
 
-        let timeout_connect = time_until_deadline(deadline)?;
+        let timeout_connect = time_until_deadline(deadline, &*unit.clock())?;
         let done_result = cvar.wait_timeout(done, timeout_connect).unwrap();
         let done = done_result.0;
         if *done {
@@ -629,6 +857,6 @@ pub(crate) fn connect_test(unit: &Unit) -> Result<Stream, Error> {
 #[cfg(not(feature = "tls"))]
 pub(crate) fn connect_https(unit: &Unit, _hostname: &str) -> Result<Stream, Error> {
     Err(ErrorKind::UnknownScheme
-        .msg("URL has 'https:' scheme but ureq was build without HTTP support")
+        .msg("URL has 'https:' scheme but ureq was built without TLS support")
         .url(unit.url.clone()))
 }
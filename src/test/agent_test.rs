@@ -44,6 +44,170 @@ fn connection_reuse() {
     assert_eq!(resp.status(), 200);
 }
 
+// Handler that serves requests on one connection until the client closes
+// it, answering each with the number of requests seen on that connection.
+fn count_requests_handler(mut stream: TcpStream) -> io::Result<()> {
+    let mut count = 0;
+    loop {
+        // read_request leaves the stream in non-blocking mode.
+        stream.set_nonblocking(false)?;
+        let headers = read_request(&stream);
+        if headers.path().is_empty() {
+            return Ok(());
+        }
+        count += 1;
+        let body = count.to_string();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+    }
+}
+
+#[test]
+fn max_requests_per_connection() {
+    let testserver = TestServer::new(count_requests_handler);
+    let url = format!("http://localhost:{}", testserver.port);
+    let agent = builder().max_requests_per_connection(2).build();
+
+    let counts: Vec<String> = (0..5)
+        .map(|_| agent.get(&url).call().unwrap().into_string().unwrap())
+        .collect();
+    assert_eq!(counts, vec!["1", "2", "1", "2", "1"]);
+    assert_eq!(agent.state.pool.len(), 1);
+}
+
+#[test]
+fn call_status() {
+    let testserver = TestServer::new(count_requests_handler);
+    let url = format!("http://localhost:{}", testserver.port);
+    let agent = Agent::new();
+
+    assert_eq!(agent.get(&url).call_status().unwrap(), 200);
+    assert_eq!(agent.state.pool.len(), 1);
+    // The body was read, so the next request reuses the connection.
+    let resp = agent.get(&url).call().unwrap();
+    assert_eq!(resp.into_string().unwrap(), "2");
+
+    // Error statuses are returned, not turned into errors.
+    crate::test::set_handler("/call_status_503", |_| {
+        crate::test::make_response(503, "Service Unavailable", vec![], vec![])
+    });
+    assert_eq!(
+        get("test://host/call_status_503").call_status().unwrap(),
+        503
+    );
+}
+
+// Handler that answers with a body that's complete, cut short or badly
+// chunked, depending on the path.
+fn framing_handler(mut stream: TcpStream) -> io::Result<()> {
+    let headers = read_request(&stream);
+    let resp: &[u8] = match headers.path() {
+        "/clean" => b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello",
+        "/short" => b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\ncut",
+        _ => b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nhello\r\n0\r\n\r\n",
+    };
+    stream.write_all(resp)?;
+    Ok(())
+}
+
+#[test]
+fn can_reuse_connection() {
+    let testserver = TestServer::new(framing_handler);
+    let split = |agent: &Agent, path: &str| {
+        let url = format!("http://localhost:{}{}", testserver.port, path);
+        agent.get(&url).call().unwrap().split().1
+    };
+
+    let agent = Agent::new();
+    let mut body = split(&agent, "/clean");
+    assert!(!body.can_reuse_connection());
+    let mut buf = vec![];
+    body.read_to_end(&mut buf).unwrap();
+    assert!(body.can_reuse_connection());
+    assert_eq!(agent.state.pool.len(), 1);
+
+    for path in &["/short", "/bad_chunk"] {
+        let agent = Agent::new();
+        let mut body = split(&agent, path);
+        assert!(body.read_to_end(&mut buf).is_err());
+        assert!(!body.can_reuse_connection());
+        // Reading on doesn't make it reusable.
+        let _ = body.read_to_end(&mut buf);
+        assert!(!body.can_reuse_connection());
+        assert_eq!(agent.state.pool.len(), 0);
+    }
+}
+
+#[test]
+fn no_pool() {
+    let testserver = TestServer::new(count_requests_handler);
+    let url = format!("http://localhost:{}", testserver.port);
+    let agent = builder().no_pool().build();
+
+    for _ in 0..2 {
+        let resp = agent.get(&url).call().unwrap();
+        // each request is the first on a fresh connection.
+        assert_eq!(resp.into_string().unwrap(), "1");
+        assert_eq!(agent.state.pool.len(), 0);
+    }
+}
+
+// Handler that answers the first request on a connection, then closes the
+// connection when the next request arrives, like a server whose idle timeout
+// raced with the client.
+fn close_on_second_request_handler(mut stream: TcpStream) -> io::Result<()> {
+    read_request(&stream);
+    stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")?;
+    stream.set_nonblocking(false)?;
+    read_request(&stream);
+    Ok(())
+}
+
+#[test]
+fn idempotent_post_retried() {
+    let testserver = TestServer::new(close_on_second_request_handler);
+    let url = format!("http://localhost:{}", testserver.port);
+    let agent = Agent::new();
+
+    // Pool a connection, which is then closed by the POST below.
+    agent.get(&url).call().unwrap().into_string().unwrap();
+    let resp = agent.post(&url).idempotent(true).call().unwrap();
+    assert_eq!(resp.into_string().unwrap(), "ok");
+
+    // Without the flag a POST isn't retried.
+    agent.get(&url).call().unwrap().into_string().unwrap();
+    let err = agent.post(&url).call().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Io);
+
+    // And a GET that opts out isn't either.
+    agent.get(&url).call().unwrap().into_string().unwrap();
+    let err = agent.get(&url).idempotent(false).call().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Io);
+}
+
+#[test]
+fn http_version_10_not_pooled() {
+    let testserver = TestServer::new(count_requests_handler);
+    let url = format!("http://localhost:{}", testserver.port);
+    let agent = builder().build();
+
+    let resp = agent
+        .get(&url)
+        .http_version(HttpVersion::Http10)
+        .call()
+        .unwrap();
+    assert_eq!(resp.into_string().unwrap(), "1");
+    assert_eq!(agent.state.pool.len(), 0);
+
+    let resp = agent.get(&url).call().unwrap();
+    assert_eq!(resp.into_string().unwrap(), "1");
+    assert_eq!(agent.state.pool.len(), 1);
+}
+
 #[test]
 fn custom_resolver() {
     use std::io::Read;
@@ -71,6 +235,86 @@ fn custom_resolver() {
     assert_eq!(&server.join().unwrap(), b"GET / HTTP/1.1\r\n");
 }
 
+#[test]
+fn url_rewrite() {
+    use crate::test;
+
+    test::set_handler("/url_rewrite1", |unit| {
+        assert_eq!(unit.url.query(), Some("mirror=1"));
+        test::make_response(302, "Found", vec!["Location: /url_rewrite2"], vec![])
+    });
+    test::set_handler("/url_rewrite2", |unit| {
+        assert_eq!(unit.url.query(), Some("mirror=1"));
+        test::make_response(200, "OK", vec![], vec![])
+    });
+    let agent = builder()
+        .url_rewrite(|url| {
+            let mut url = url.clone();
+            url.set_query(Some("mirror=1"));
+            url
+        })
+        .build();
+    let resp = agent.get("test://host/url_rewrite1").call().unwrap();
+    assert_eq!(resp.get_url(), "test://host/url_rewrite2?mirror=1");
+}
+
+#[test]
+#[cfg(feature = "tls")]
+fn url_rewrite_to_https() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let local_addr = listener.local_addr().unwrap();
+
+    let server = std::thread::spawn(move || {
+        let (mut client, _) = listener.accept().unwrap();
+        let mut buf = vec![0u8; 1];
+        client.read_exact(&mut buf).unwrap();
+        buf
+    });
+
+    builder()
+        .resolver(move |_: &str| Ok(vec![local_addr]))
+        .url_rewrite(|url| {
+            let mut url = url.clone();
+            url.set_scheme("https").unwrap();
+            url
+        })
+        .build()
+        .get("http://cool.server/")
+        .call()
+        .ok();
+
+    // 0x16 starts a TLS handshake record, like the ClientHello.
+    assert_eq!(server.join().unwrap(), [0x16]);
+}
+
+#[test]
+#[cfg(feature = "tls")]
+fn upgrade_to_https() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let local_addr = listener.local_addr().unwrap();
+
+    let server = std::thread::spawn(move || {
+        let (mut client, _) = listener.accept().unwrap();
+        let mut buf = vec![0u8; 1];
+        client.read_exact(&mut buf).unwrap();
+        buf
+    });
+
+    builder()
+        .resolver(move |_: &str| Ok(vec![local_addr]))
+        .upgrade_to_https(true)
+        .build()
+        .get("http://cool.server/")
+        .call()
+        .ok();
+
+    assert_eq!(server.join().unwrap(), [0x16]);
+}
+
 #[cfg(feature = "cookies")]
 #[cfg(test)]
 fn cookie_and_redirect(mut stream: TcpStream) -> io::Result<()> {
@@ -132,6 +376,80 @@ fn test_cookies_on_redirect() -> Result<(), Error> {
     Ok(())
 }
 
+#[cfg(feature = "cookies")]
+#[test]
+fn cookie_limits() {
+    fn set_cookies(names: &[&str], value: &str) -> Result<crate::stream::Stream, Error> {
+        let headers: Vec<String> = names
+            .iter()
+            .map(|n| format!("Set-Cookie: {}={}", n, value))
+            .collect();
+        let headers: Vec<&str> = headers.iter().map(|h| h.as_str()).collect();
+        test::make_response(200, "OK", headers, vec![])
+    }
+    test::set_handler("/cookie_limits1", |_| {
+        set_cookies(&["a", "b", "c", "d", "e", "f", "g", "h"], "1")
+    });
+    test::set_handler("/cookie_limits2", |_| {
+        set_cookies(&["a", "x", "y", "z"], "2")
+    });
+    let agent = builder()
+        .max_cookies_per_response(5)
+        .max_cookies_total(7)
+        .build();
+
+    agent.get("test://host/cookie_limits1").call().unwrap();
+    assert_eq!(agent.cookie_store().iter_any().count(), 5);
+
+    // The update to "a" is stored even though the store is full after "y".
+    agent.get("test://host/cookie_limits2").call().unwrap();
+    let store = agent.cookie_store();
+    let mut cookies: Vec<String> = store.iter_any().map(|c| c.to_string()).collect();
+    cookies.sort();
+    assert_eq!(
+        cookies,
+        vec!["a=2", "b=1", "c=1", "d=1", "e=1", "x=2", "y=2"]
+    );
+}
+
+#[cfg(feature = "cookies")]
+#[test]
+fn cookie_limit_ignores_expired() {
+    test::set_handler("/cookie_limit_expired1", |_| {
+        test::make_response(
+            200,
+            "OK",
+            vec!["Set-Cookie: a=1", "Set-Cookie: b=1"],
+            vec![],
+        )
+    });
+    test::set_handler("/cookie_limit_expired2", |_| {
+        test::make_response(200, "OK", vec!["Set-Cookie: a=; Max-Age=0"], vec![])
+    });
+    test::set_handler("/cookie_limit_expired3", |_| {
+        test::make_response(200, "OK", vec!["Set-Cookie: c=1"], vec![])
+    });
+    let agent = builder().max_cookies_total(2).build();
+
+    agent
+        .get("test://host/cookie_limit_expired1")
+        .call()
+        .unwrap();
+    agent
+        .get("test://host/cookie_limit_expired2")
+        .call()
+        .unwrap();
+    // "a" has expired, which leaves room for "c".
+    agent
+        .get("test://host/cookie_limit_expired3")
+        .call()
+        .unwrap();
+    let store = agent.cookie_store();
+    let mut cookies: Vec<String> = store.iter_unexpired().map(|c| c.to_string()).collect();
+    cookies.sort();
+    assert_eq!(cookies, vec!["b=1", "c=1"]);
+}
+
 #[test]
 fn dirty_streams_not_returned() -> Result<(), Error> {
     let testserver = TestServer::new(|mut stream: TcpStream| -> io::Result<()> {
@@ -167,3 +485,367 @@ fn dirty_streams_not_returned() -> Result<(), Error> {
     let _resp_to_succeed = agent.get(&url).call()?;
     Ok(())
 }
+
+#[cfg(feature = "gzip")]
+fn gzip_content_length_handler(mut stream: TcpStream) -> io::Result<()> {
+    use flate2::{write::GzEncoder, Compression};
+
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(b"hello world, hello world, hello world")?;
+    let gzipped = encoder.finish()?;
+
+    read_request(&stream);
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+        gzipped.len()
+    )?;
+    stream.write_all(&gzipped)?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn gzip_content_length_reuse() {
+    let testserver = TestServer::new(gzip_content_length_handler);
+    let url = format!("http://localhost:{}", testserver.port);
+    let agent = Agent::new();
+
+    // The Content-Length is the compressed size, so the connection is done
+    // with once the compressed bytes are read, and goes back to the pool.
+    let resp = agent.get(&url).call().unwrap();
+    assert_eq!(
+        resp.into_string().unwrap(),
+        "hello world, hello world, hello world"
+    );
+    assert_eq!(agent.state.pool.len(), 1);
+}
+
+#[test]
+fn preconnect() {
+    let testserver = TestServer::new(idle_timeout_handler);
+    let url = format!("http://localhost:{}", testserver.port);
+    let agent = Agent::new();
+
+    agent.preconnect(&url).unwrap();
+    assert_eq!(agent.state.pool.len(), 1);
+
+    let resp = agent.get(&url).call().unwrap();
+    assert!(resp.connection_reused());
+    assert_eq!(resp.into_string().unwrap(), "response");
+    assert_eq!(agent.state.pool.len(), 1);
+
+    let err = agent.preconnect("not a url").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidUrl);
+}
+
+#[test]
+fn connect_only() {
+    let testserver = TestServer::new(idle_timeout_handler);
+    let url = format!("http://localhost:{}/", testserver.port);
+    let agent = Agent::new();
+
+    let conn = agent.connect_only(&url).unwrap();
+    assert_eq!(conn.url().as_str(), url);
+    assert_eq!(conn.remote_addr().unwrap().port(), testserver.port);
+    assert!(conn.connect_time() < Duration::from_secs(5));
+    assert_eq!(agent.state.pool.len(), 1);
+
+    let resp = agent.get(&url).call().unwrap();
+    assert!(resp.connection_reused());
+    assert_eq!(resp.into_string().unwrap(), "response");
+}
+
+#[test]
+fn shutdown() {
+    let testserver = TestServer::new(idle_timeout_handler);
+    let url = format!("http://localhost:{}", testserver.port);
+    let agent = Agent::new();
+
+    agent.get(&url).call().unwrap().into_string().unwrap();
+    assert_eq!(agent.state.pool.len(), 1);
+
+    agent.clone().shutdown();
+    assert_eq!(agent.state.pool.len(), 0);
+
+    // Requests still work, on connections that aren't pooled.
+    let resp = agent.get(&url).call().unwrap();
+    assert!(!resp.connection_reused());
+    assert_eq!(resp.into_string().unwrap(), "response");
+    assert_eq!(agent.state.pool.len(), 0);
+    agent.preconnect(&url).unwrap();
+    assert_eq!(agent.state.pool.len(), 0);
+}
+
+#[test]
+fn host_config() {
+    use crate::test;
+
+    for path in &["/host_config_a", "/host_config_b", "/host_config_override"] {
+        test::set_handler(path, |_unit| test::make_response(200, "OK", vec![], vec![]));
+    }
+    let agent = builder()
+        .accept_language("en")
+        .host_config("a.test", |host| {
+            host.set("X-Api-Key", "a-key").set("Accept-Language", "fr");
+        })
+        .host_config("B.TEST", |host| {
+            host.set("X-Api-Key", "b-key");
+        })
+        .host_config("b.test", |host| {
+            host.set("X-Other", "1");
+        })
+        .build();
+
+    let written = |resp: Response| String::from_utf8_lossy(&resp.to_write_vec()).to_string();
+
+    let s = written(agent.get("test://a.test/host_config_a").call().unwrap());
+    assert!(s.contains("\r\nX-Api-Key: a-key\r\n"));
+    assert!(s.contains("\r\nAccept-Language: fr\r\n"));
+    assert!(!s.contains("Accept-Language: en"));
+
+    let s = written(agent.get("test://b.test/host_config_b").call().unwrap());
+    assert!(s.contains("\r\nX-Api-Key: b-key\r\n"));
+    assert!(s.contains("\r\nX-Other: 1\r\n"));
+    assert!(s.contains("\r\nAccept-Language: en\r\n"));
+
+    // The request's own headers win.
+    let s = written(
+        agent
+            .get("test://a.test/host_config_override")
+            .set("X-Api-Key", "mine")
+            .call()
+            .unwrap(),
+    );
+    assert!(s.contains("\r\nX-Api-Key: mine\r\n"));
+    assert!(!s.contains("a-key"));
+}
+
+#[test]
+fn clone_for_failover() {
+    let testserver = TestServer::new(idle_timeout_handler);
+    let addr: std::net::SocketAddr = format!("127.0.0.1:{}", testserver.port).parse().unwrap();
+
+    let down = builder()
+        .resolver(|_: &str| Err(io::Error::new(io::ErrorKind::Other, "region down")))
+        .build();
+    let up = builder().resolver(move |_: &str| Ok(vec![addr])).build();
+
+    let req = down
+        .get("http://service.test/failover")
+        .query("attempt", "1")
+        .set("X-Request-Id", "abc");
+    let err = req.clone().call().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Dns);
+
+    let resp = req.clone_for(&up).call().unwrap();
+    assert_eq!(resp.get_url(), "http://service.test/failover?attempt=1");
+    assert!(resp
+        .request_headers()
+        .contains(&("X-Request-Id".to_string(), "abc".to_string())));
+    assert_eq!(resp.into_string().unwrap(), "response");
+}
+
+#[test]
+fn io_stats() {
+    use crate::test;
+
+    test::set_handler("/io_stats_get", |_unit| {
+        test::make_response(200, "OK", vec![], vec![b'a'; 1000])
+    });
+    test::set_handler("/io_stats_post", |_unit| {
+        test::make_response(200, "OK", vec![], vec![b'b'; 500])
+    });
+    let agent = builder().build();
+    assert_eq!(agent.io_stats(), IoStats::default());
+
+    agent
+        .get("test://host/io_stats_get")
+        .call()
+        .unwrap()
+        .into_string()
+        .unwrap();
+    let first = agent.io_stats();
+    assert!(first.bytes_read() >= 1000, "{:?}", first);
+    assert!(first.bytes_written() > 0, "{:?}", first);
+
+    agent
+        .post("test://host/io_stats_post")
+        .send_bytes(&[b'c'; 300])
+        .unwrap()
+        .into_string()
+        .unwrap();
+    let second = agent.clone().io_stats();
+    assert!(second.bytes_read() >= first.bytes_read() + 500);
+    assert!(second.bytes_written() >= first.bytes_written() + 300);
+}
+
+// A self-signed certificate for localhost, and its key.
+#[cfg(feature = "tls")]
+const LOCALHOST_CERT: &str = "MIIBljCCATugAwIBAgIUKVaEJXzqACrBlQQnE57PoLjHJxEwCgYIKoZIzj0EAwIwFDESMBAGA1UEAwwJbG9jYWxob3N0MCAXDTI2MTAxNTA1MjQyNFoYDzIxMjYwOTIxMDUyNDI0WjAUMRIwEAYDVQQDDAlsb2NhbGhvc3QwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAASocSB4Ht5LCR0Z+SDHyFIvcmy1L/cb7G3Qi49A0KNr3cKAd38tRGOr1jDdnJBC5Sg2mUXUBB8kEdtc8jrPr/mdo2kwZzAdBgNVHQ4EFgQUHYS4rDEWnPeBIHz9BRGbQvkZxuYwHwYDVR0jBBgwFoAUHYS4rDEWnPeBIHz9BRGbQvkZxuYwDwYDVR0TAQH/BAUwAwEB/zAUBgNVHREEDTALgglsb2NhbGhvc3QwCgYIKoZIzj0EAwIDSQAwRgIhAO4+0WWHP0CYQ9Zz+U6Y4TWavQGGeoZDb1+0YenTwqwUAiEA5vqf+2XzSx9l/t1SKXEAmrOqTFtQW86jr/p6TgaAqcw=";
+#[cfg(feature = "tls")]
+const LOCALHOST_KEY: &str = "MIGHAgEAMBMGByqGSM49AgEGCCqGSM49AwEHBG0wawIBAQQgZLNHnrl3/4m8xNbodjRLgLdLo6hAwsgXbRkWm1bQBzKhRANCAASocSB4Ht5LCR0Z+SDHyFIvcmy1L/cb7G3Qi49A0KNr3cKAd38tRGOr1jDdnJBC5Sg2mUXUBB8kEdtc8jrPr/md";
+
+// Serves `count` connections over TLS with the localhost certificate,
+// answering one request on each.
+#[cfg(feature = "tls")]
+fn tls_server(count: usize) -> u16 {
+    use rustls::{Certificate, NoClientAuth, PrivateKey, ServerConfig, ServerSession, Stream};
+    use std::net::TcpListener;
+
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    let cert = Certificate(base64::decode(LOCALHOST_CERT).unwrap());
+    let key = PrivateKey(base64::decode(LOCALHOST_KEY).unwrap());
+    config.set_single_cert(vec![cert], key).unwrap();
+    let config = std::sync::Arc::new(config);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for sock in listener.incoming().take(count) {
+            let mut sock = sock.unwrap();
+            let mut sess = ServerSession::new(&config);
+            let mut tls = Stream::new(&mut sess, &mut sock);
+            let mut buf = [0; 1024];
+            if tls.read(&mut buf).is_ok() {
+                tls.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                    .ok();
+            }
+        }
+    });
+    port
+}
+
+#[test]
+#[cfg(feature = "tls")]
+fn verify_callback() {
+    use std::sync::{Arc, Mutex};
+
+    let port = tls_server(3);
+    let url = format!("https://localhost:{}/", port);
+
+    // The self-signed certificate isn't trusted normally.
+    let err = get(&url).call().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Tls);
+    assert!(err
+        .to_string()
+        .contains("TLS handshake with localhost failed"));
+
+    let seen = Arc::new(Mutex::new(vec![]));
+    let seen2 = seen.clone();
+    let agent = builder()
+        .verify_callback(move |cert| {
+            seen2.lock().unwrap().push((
+                cert.hostname().to_string(),
+                cert.is_trusted(),
+                cert.chain().len(),
+            ));
+            false
+        })
+        .build();
+    assert!(agent.get(&url).call().is_err());
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![("localhost".to_string(), false, 1)]
+    );
+
+    let expected = base64::decode(LOCALHOST_CERT).unwrap();
+    let agent = builder()
+        .verify_callback(move |cert| cert.end_entity() == &expected[..])
+        .build();
+    let resp = agent.get(&url).call().unwrap();
+    assert_eq!(resp.into_string().unwrap(), "ok");
+}
+
+#[test]
+fn request_quota() {
+    use crate::test;
+
+    for path in &["/request_quota1", "/request_quota2"] {
+        test::set_handler(path, |_unit| test::make_response(200, "OK", vec![], vec![]));
+    }
+    let agent = builder().request_quota(2).build();
+    agent.get("test://host/request_quota1").call().unwrap();
+    agent
+        .clone()
+        .get("test://host/request_quota2")
+        .call()
+        .unwrap();
+
+    let err = agent.get("test://host/request_quota3").call().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::QuotaExceeded);
+    assert_eq!(
+        err.to_string(),
+        "test://host/request_quota3: Request quota exceeded: request quota of 2 exhausted"
+    );
+}
+
+// The Idempotency-Key headers of the requests seen by idempotency_key_handler.
+static IDEMPOTENCY_KEYS: once_cell::sync::Lazy<std::sync::Mutex<Vec<String>>> =
+    once_cell::sync::Lazy::new(Default::default);
+
+// Like close_on_second_request_handler, recording idempotency keys.
+fn idempotency_key_handler(mut stream: TcpStream) -> io::Result<()> {
+    let record = |headers: crate::testserver::TestHeaders| {
+        let keys = headers.headers().iter().filter_map(|h| {
+            h.strip_prefix("Idempotency-Key: ")
+                .map(|key| key.to_string())
+        });
+        IDEMPOTENCY_KEYS.lock().unwrap().extend(keys);
+    };
+    record(read_request(&stream));
+    stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")?;
+    stream.set_nonblocking(false)?;
+    record(read_request(&stream));
+    Ok(())
+}
+
+#[test]
+fn idempotency_key_retried() {
+    let testserver = TestServer::new(idempotency_key_handler);
+    let url = format!("http://localhost:{}", testserver.port);
+    let agent = builder().idempotency_keys(true).build();
+    let take_keys = || std::mem::take(&mut *IDEMPOTENCY_KEYS.lock().unwrap());
+
+    // The POST is sent on a pooled connection that the server then closes,
+    // and is sent again on a new one.
+    agent.get(&url).call().unwrap().into_string().unwrap();
+    take_keys();
+    let resp = agent.post(&url).with_idempotency_key("abc").call().unwrap();
+    assert_eq!(resp.into_string().unwrap(), "ok");
+    assert_eq!(take_keys(), vec!["abc", "abc"]);
+
+    // A generated key is the same on the retry.
+    agent.get(&url).call().unwrap().into_string().unwrap();
+    take_keys();
+    let resp = agent.post(&url).call().unwrap();
+    assert_eq!(resp.into_string().unwrap(), "ok");
+    let keys = take_keys();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[0].len(), 36);
+    assert_eq!(keys[0], keys[1]);
+
+    // GETs don't get one.
+    agent.get(&url).call().unwrap().into_string().unwrap();
+    assert!(take_keys().is_empty());
+}
+
+#[test]
+fn prepared_request() {
+    let testserver = TestServer::new(count_requests_handler);
+    let url = format!("http://localhost:{}/poll", testserver.port);
+    let agent = Agent::new();
+    let prepared = agent
+        .get(&url)
+        .query("job", "42")
+        .set("X-Poll", "1")
+        .build();
+    assert_eq!(prepared.url(), format!("{}?job=42", url));
+
+    let first = prepared.call().unwrap();
+    assert_eq!(first.get_url(), format!("{}?job=42", url));
+    assert_eq!(first.into_string().unwrap(), "1");
+    let second = prepared.clone().call().unwrap();
+    assert_eq!(second.into_string().unwrap(), "2");
+}
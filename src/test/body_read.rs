@@ -1,5 +1,6 @@
 use crate::test;
-use std::io::Read;
+use std::io::{self, BufRead, Read, Write};
+use std::time::{Duration, Instant};
 
 use super::super::*;
 
@@ -10,18 +11,19 @@ fn transfer_encoding_bogus() {
             200,
             "OK",
             vec![
-                "transfer-encoding: bogus", // whatever it says here, we should chunk
+                "transfer-encoding: bogus", // we can't decode this, so it's an error
             ],
             "3\r\nhel\r\nb\r\nlo world!!!\r\n0\r\n\r\n"
                 .to_string()
                 .into_bytes(),
         )
     });
-    let resp = get("test://host/transfer_encoding_bogus").call().unwrap();
-    let mut reader = resp.into_reader();
-    let mut text = String::new();
-    reader.read_to_string(&mut text).unwrap();
-    assert_eq!(text, "hello world!!!");
+    let result = get("test://host/transfer_encoding_bogus").call();
+    assert!(
+        matches!(result, Err(ref e) if e.kind() == ErrorKind::BadHeader),
+        "expected Err(BadHeader), got {:?}",
+        result
+    );
 }
 
 #[test]
@@ -82,3 +84,580 @@ fn no_reader_on_head() {
     reader.read_to_string(&mut text).unwrap();
     assert_eq!(text, "");
 }
+
+#[test]
+fn empty_body_on_head() {
+    test::set_handler("/empty_body_on_head", |_unit| {
+        test::make_response(200, "OK", vec!["Content-Length: 4"], vec![])
+    });
+    let resp = head("test://host/empty_body_on_head").call().unwrap();
+    assert!(resp.has_empty_body());
+}
+
+#[test]
+fn empty_body_on_204_and_304() {
+    for status in &[204, 304] {
+        let path = format!("/empty_body_on_{}", status);
+        let status = *status;
+        test::set_handler(&path, move |_unit| {
+            test::make_response(status, "Empty", vec!["Transfer-Encoding: chunked"], vec![])
+        });
+        let resp = get(&format!("test://host{}", path)).call().unwrap();
+        assert!(resp.has_empty_body());
+        assert_eq!(resp.into_string().unwrap(), "");
+    }
+}
+
+#[test]
+fn empty_body_on_zero_content_length() {
+    test::set_handler("/empty_body_on_zero_content_length", |_unit| {
+        test::make_response(200, "OK", vec!["Content-Length: 0"], vec![])
+    });
+    let resp = get("test://host/empty_body_on_zero_content_length")
+        .call()
+        .unwrap();
+    assert!(resp.has_empty_body());
+}
+
+#[test]
+fn non_empty_body() {
+    test::set_handler("/non_empty_body", |_unit| {
+        test::make_response(200, "OK", vec!["Content-Length: 4"], b"abcd".to_vec())
+    });
+    let resp = get("test://host/non_empty_body").call().unwrap();
+    assert!(!resp.has_empty_body());
+
+    // Zero length chunked is still chunked; we can't know without reading.
+    test::set_handler("/non_empty_body_chunked", |_unit| {
+        test::make_response(
+            200,
+            "OK",
+            vec!["Content-Length: 0", "Transfer-Encoding: chunked"],
+            b"0\r\n\r\n".to_vec(),
+        )
+    });
+    let resp = get("test://host/non_empty_body_chunked").call().unwrap();
+    assert!(!resp.has_empty_body());
+}
+
+#[test]
+fn reader_tee() {
+    let body: Vec<u8> = (0..20_000).map(|i| (i % 251) as u8).collect();
+    let body_clone = body.clone();
+    test::set_handler("/reader_tee", move |_unit| {
+        test::make_response(
+            200,
+            "OK",
+            vec!["Transfer-Encoding: chunked"],
+            format!("{:x}\r\n", body_clone.len())
+                .into_bytes()
+                .into_iter()
+                .chain(body_clone.iter().cloned())
+                .chain(b"\r\n0\r\n\r\n".iter().cloned())
+                .collect(),
+        )
+    });
+    let resp = get("test://host/reader_tee").call().unwrap();
+    let mut sink = vec![];
+    let mut read = vec![];
+    resp.into_reader_tee(&mut sink)
+        .read_to_end(&mut read)
+        .unwrap();
+    assert_eq!(read, body);
+    assert_eq!(sink, body);
+}
+
+#[test]
+fn reader_tee_sink_error() {
+    struct FailingSink;
+    impl Write for FailingSink {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    test::set_handler("/reader_tee_sink_error", |_unit| {
+        test::make_response(200, "OK", vec!["Content-Length: 4"], b"abcd".to_vec())
+    });
+    let resp = get("test://host/reader_tee_sink_error").call().unwrap();
+    let mut text = String::new();
+    let err = resp
+        .into_reader_tee(FailingSink)
+        .read_to_string(&mut text)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "disk full");
+}
+
+#[test]
+fn split_head_and_body() {
+    test::set_handler("/split_head_and_body", |_unit| {
+        test::make_response(
+            200,
+            "OK",
+            vec![
+                "Content-Type: text/plain",
+                "Content-Length: 5",
+                "X-Foo: 1",
+                "X-Foo: 2",
+            ],
+            b"hello trailing garbage".to_vec(),
+        )
+    });
+    let resp = get("test://host/split_head_and_body").call().unwrap();
+    let (head, mut body) = resp.split();
+
+    // The body can be moved elsewhere independently of the head.
+    let handle = std::thread::spawn(move || {
+        let mut text = String::new();
+        body.read_to_string(&mut text).unwrap();
+        text
+    });
+
+    assert_eq!(head.status(), 200);
+    assert_eq!(head.status_text(), "OK");
+    assert_eq!(head.http_version(), "HTTP/1.1");
+    assert_eq!(head.header("content-type"), Some("text/plain"));
+    assert_eq!(head.all("x-foo"), vec!["1", "2"]);
+    assert_eq!(head.get_url(), "test://host/split_head_and_body");
+    assert_eq!(handle.join().unwrap(), "hello");
+}
+
+#[test]
+fn merge_trailers() {
+    test::set_handler("/merge_trailers", |_unit| {
+        test::make_response(
+            200,
+            "OK",
+            vec!["Transfer-Encoding: chunked", "Trailer: grpc-status"],
+            b"5\r\nhello\r\n0\r\ngrpc-status: 0\r\nx-other: 1\r\n\r\n".to_vec(),
+        )
+    });
+    let agent = builder().merge_trailers(&["grpc-status"]).build();
+    let resp = agent.get("test://host/merge_trailers").call().unwrap();
+    let (head, mut body) = resp.split();
+
+    // Trailers come after the body, so they're not there yet.
+    assert_eq!(head.header("grpc-status"), None);
+
+    let mut text = String::new();
+    body.read_to_string(&mut text).unwrap();
+    assert_eq!(text, "hello");
+    assert_eq!(head.header("grpc-status"), Some("0"));
+    assert_eq!(head.all("grpc-status"), vec!["0"]);
+    assert!(head.headers_names().contains(&"grpc-status".to_string()));
+    assert_eq!(head.header("x-other"), None);
+}
+
+#[test]
+fn te_trailers() {
+    for path in &["/te_trailers", "/te_trailers_not_asked"] {
+        test::set_handler(path, |unit| {
+            let te =
+                unit.header("te") == Some("trailers") && unit.header("connection") == Some("TE");
+            test::make_response(
+                200,
+                "OK",
+                vec![
+                    "Transfer-Encoding: chunked",
+                    "Trailer: grpc-status, grpc-message",
+                ],
+                format!(
+                    "5\r\nhello\r\n0\r\ngrpc-status: {}\r\ngrpc-message: ok\r\nx-other: 1\r\n\r\n",
+                    if te { 0 } else { 1 }
+                )
+                .into_bytes(),
+            )
+        });
+    }
+    let resp = get("test://host/te_trailers").te_trailers().call().unwrap();
+
+    let (head, mut body) = resp.split();
+    let mut text = String::new();
+    body.read_to_string(&mut text).unwrap();
+    assert_eq!(text, "hello");
+    assert_eq!(head.header("grpc-status"), Some("0"));
+    assert_eq!(head.header("grpc-message"), Some("ok"));
+    assert_eq!(head.header("x-other"), None);
+
+    // Without TE, only the trailers named by merge_trailers are kept.
+    let resp = get("test://host/te_trailers_not_asked").call().unwrap();
+    let (head, mut body) = resp.split();
+    body.read_to_string(&mut text).unwrap();
+    assert_eq!(head.header("grpc-status"), None);
+}
+
+#[test]
+fn body_reader_fill_buf() {
+    test::set_handler("/body_reader_fill_buf", |_unit| {
+        test::make_response(
+            200,
+            "OK",
+            vec!["Transfer-Encoding: chunked"],
+            b"6\r\nfoo;ba\r\n4\r\nr;ba\r\n1\r\nz\r\n0\r\n\r\n".to_vec(),
+        )
+    });
+    let resp = get("test://host/body_reader_fill_buf").call().unwrap();
+    let (_, mut body) = resp.split();
+
+    // Take one `;` separated field at a time, straight from the buffer.
+    let mut fields = vec![];
+    let mut field = vec![];
+    loop {
+        let buf = body.fill_buf().unwrap();
+        if buf.is_empty() {
+            fields.push(field);
+            break;
+        }
+        match buf.iter().position(|&c| c == b';') {
+            Some(i) => {
+                field.extend_from_slice(&buf[..i]);
+                fields.push(std::mem::take(&mut field));
+                body.consume(i + 1);
+            }
+            None => {
+                let len = buf.len();
+                field.extend_from_slice(buf);
+                body.consume(len);
+            }
+        }
+    }
+    assert_eq!(
+        fields,
+        vec![b"foo".to_vec(), b"bar".to_vec(), b"baz".to_vec()]
+    );
+}
+
+#[test]
+fn max_error_body_size() {
+    test::set_handler("/max_error_body_size_large", |_unit| {
+        test::make_response(
+            500,
+            "Internal Server Error",
+            vec!["Content-Length: 100000"],
+            vec![b'x'; 100_000],
+        )
+    });
+    let agent = builder().max_error_body_size(1024).build();
+    let resp = match agent.get("test://host/max_error_body_size_large").call() {
+        Err(Error::Status(500, resp)) => resp,
+        other => panic!("expected 500 error, got {:?}", other),
+    };
+    assert_eq!(resp.into_string().unwrap(), "x".repeat(1024));
+
+    test::set_handler("/max_error_body_size_small", |_unit| {
+        test::make_response(404, "Not Found", vec![], b"no such thing".to_vec())
+    });
+    let resp = match agent.get("test://host/max_error_body_size_small").call() {
+        Err(Error::Status(404, resp)) => resp,
+        other => panic!("expected 404 error, got {:?}", other),
+    };
+    // The buffered response still knows its request.
+    let sent = resp.request_headers();
+    assert!(sent.iter().any(|(name, _)| name == "User-Agent"));
+    assert!(String::from_utf8(resp.to_write_vec())
+        .unwrap()
+        .starts_with("GET /max_error_body_size_small HTTP/1.1\r\n"));
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn should_decompress() {
+    use flate2::{write::GzEncoder, Compression};
+
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(b"hello world!!!").unwrap();
+    let gzipped = encoder.finish().unwrap();
+
+    let body = gzipped.clone();
+    test::set_handler("/should_decompress_text", move |_unit| {
+        test::make_response(
+            200,
+            "OK",
+            vec!["Content-Type: text/plain", "Content-Encoding: gzip"],
+            body.clone(),
+        )
+    });
+    for path in &["/should_decompress_zip", "/should_decompress_default"] {
+        let body = gzipped.clone();
+        test::set_handler(path, move |_unit| {
+            test::make_response(
+                200,
+                "OK",
+                vec!["Content-Type: application/zip", "Content-Encoding: gzip"],
+                body.clone(),
+            )
+        });
+    }
+
+    let agent = builder()
+        .should_decompress(|content_type| content_type != "application/zip")
+        .build();
+    let resp = agent
+        .get("test://host/should_decompress_text")
+        .call()
+        .unwrap();
+    assert_eq!(resp.into_string().unwrap(), "hello world!!!");
+
+    let resp = agent
+        .get("test://host/should_decompress_zip")
+        .call()
+        .unwrap();
+    let mut bytes = vec![];
+    resp.into_reader().read_to_end(&mut bytes).unwrap();
+    assert_eq!(bytes, gzipped);
+
+    // Without a filter everything is decompressed.
+    let resp = get("test://host/should_decompress_default").call().unwrap();
+    assert_eq!(resp.into_string().unwrap(), "hello world!!!");
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn decompress_content_types() {
+    use flate2::{write::GzEncoder, Compression};
+
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(b"hello world!!!").unwrap();
+    let gzipped = encoder.finish().unwrap();
+
+    for (path, content_type) in &[
+        ("/decompress_types_text", "text/plain; charset=utf-8"),
+        ("/decompress_types_json", "application/json"),
+        ("/decompress_types_binary", "application/octet-stream"),
+    ] {
+        let body = gzipped.clone();
+        let content_type = format!("Content-Type: {}", content_type);
+        test::set_handler(path, move |_unit| {
+            test::make_response(
+                200,
+                "OK",
+                vec![&content_type, "Content-Encoding: gzip"],
+                body.clone(),
+            )
+        });
+    }
+
+    let agent = builder()
+        .decompress_content_types(&["text/*", "Application/JSON"])
+        .build();
+    for path in &["/decompress_types_text", "/decompress_types_json"] {
+        let resp = agent.get(&format!("test://host{}", path)).call().unwrap();
+        assert_eq!(resp.into_string().unwrap(), "hello world!!!");
+    }
+
+    let resp = agent
+        .get("test://host/decompress_types_binary")
+        .call()
+        .unwrap();
+    assert_eq!(resp.content_encoding_used(), None);
+    let mut bytes = vec![];
+    resp.into_reader().read_to_end(&mut bytes).unwrap();
+    assert_eq!(bytes, gzipped);
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn content_encoding_used() {
+    use flate2::{write::GzEncoder, Compression};
+
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(b"hello world!!!").unwrap();
+    let gzipped = encoder.finish().unwrap();
+
+    for path in &["/content_encoding_used", "/content_encoding_used_raw"] {
+        let body = gzipped.clone();
+        test::set_handler(path, move |_unit| {
+            test::make_response(200, "OK", vec!["Content-Encoding: x-gzip"], body.clone())
+        });
+    }
+    test::set_handler("/content_encoding_used_plain", |_unit| {
+        test::make_response(200, "OK", vec![], b"hello".to_vec())
+    });
+
+    let resp = get("test://host/content_encoding_used").call().unwrap();
+    assert_eq!(resp.content_encoding_used(), Some("gzip"));
+    assert_eq!(resp.into_string().unwrap(), "hello world!!!");
+
+    let resp = get("test://host/content_encoding_used_raw")
+        .no_decompress()
+        .call()
+        .unwrap();
+    assert_eq!(resp.content_encoding_used(), None);
+
+    let resp = get("test://host/content_encoding_used_plain")
+        .call()
+        .unwrap();
+    assert_eq!(resp.content_encoding_used(), None);
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn no_decompress() {
+    use flate2::{write::GzEncoder, Compression};
+
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(b"hello world!!!").unwrap();
+    let gzipped = encoder.finish().unwrap();
+
+    for path in &["/no_decompress", "/no_decompress_other"] {
+        let body = gzipped.clone();
+        test::set_handler(path, move |_unit| {
+            test::make_response(
+                200,
+                "OK",
+                vec!["Content-Type: text/plain", "Content-Encoding: gzip"],
+                body.clone(),
+            )
+        });
+    }
+
+    let agent = agent();
+    let resp = agent
+        .get("test://host/no_decompress")
+        .no_decompress()
+        .call()
+        .unwrap();
+    assert_eq!(resp.header("content-encoding"), Some("gzip"));
+    let mut bytes = vec![];
+    resp.into_reader().read_to_end(&mut bytes).unwrap();
+    assert_eq!(bytes, gzipped);
+
+    // Other requests from the agent are still decompressed.
+    let resp = agent.get("test://host/no_decompress_other").call().unwrap();
+    assert_eq!(resp.into_string().unwrap(), "hello world!!!");
+}
+
+#[test]
+#[cfg(feature = "charset")]
+fn charset_fallback() {
+    for path in &["/charset_fallback", "/charset_fallback_none"] {
+        test::set_handler(path, |_unit| {
+            test::make_response(
+                200,
+                "OK",
+                vec!["Content-Type: text/plain; charset=no-such-charset"],
+                b"K\xf6ln".to_vec(),
+            )
+        });
+    }
+
+    let (text, had_errors) = get("test://host/charset_fallback")
+        .charset_fallback(&["utf-8", "windows-1252"])
+        .call()
+        .unwrap()
+        .into_string_checked()
+        .unwrap();
+    assert_eq!(text, "K\u{f6}ln");
+    assert!(!had_errors);
+
+    // The fallback carries over to the redirected request.
+    test::set_handler("/charset_fallback_redirect", |_unit| {
+        test::make_response(
+            302,
+            "Found",
+            vec!["Location: /charset_fallback_target"],
+            vec![],
+        )
+    });
+    test::set_handler("/charset_fallback_target", |_unit| {
+        test::make_response(
+            200,
+            "OK",
+            vec!["Content-Type: text/plain; charset=no-such-charset"],
+            b"K\xf6ln".to_vec(),
+        )
+    });
+    let (text, had_errors) = get("test://host/charset_fallback_redirect")
+        .charset_fallback(&["utf-8", "windows-1252"])
+        .call()
+        .unwrap()
+        .into_string_checked()
+        .unwrap();
+    assert_eq!(text, "K\u{f6}ln");
+    assert!(!had_errors);
+
+    // Without a fallback, the unknown charset means utf-8.
+    let (text, had_errors) = get("test://host/charset_fallback_none")
+        .call()
+        .unwrap()
+        .into_string_checked()
+        .unwrap();
+    assert_eq!(text, "K\u{fffd}ln");
+    assert!(had_errors);
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn sniff_encoding() {
+    use flate2::{
+        write::{GzEncoder, ZlibEncoder},
+        Compression,
+    };
+
+    let mut gz = GzEncoder::new(vec![], Compression::default());
+    gz.write_all(b"hello gzip").unwrap();
+    let gzipped = gz.finish().unwrap();
+    let mut zlib = ZlibEncoder::new(vec![], Compression::best());
+    zlib.write_all(b"hello zlib").unwrap();
+    let zlibbed = zlib.finish().unwrap();
+
+    let bodies = vec![
+        ("/sniff_encoding_gzip", gzipped.clone()),
+        ("/sniff_encoding_zlib", zlibbed),
+        ("/sniff_encoding_plain", b"hello plain".to_vec()),
+        ("/sniff_encoding_short", b"h".to_vec()),
+        ("/sniff_encoding_off", gzipped.clone()),
+    ];
+    for (path, body) in bodies {
+        test::set_handler(path, move |_unit| {
+            test::make_response(200, "OK", vec![], body.clone())
+        });
+    }
+
+    let agent = builder().sniff_encoding(true).build();
+    let read = |path: &str| {
+        let url = format!("test://host{}", path);
+        let mut bytes = vec![];
+        let resp = agent.get(&url).call().unwrap();
+        resp.into_reader().read_to_end(&mut bytes).unwrap();
+        bytes
+    };
+    assert_eq!(read("/sniff_encoding_gzip"), b"hello gzip");
+    assert_eq!(read("/sniff_encoding_zlib"), b"hello zlib");
+    assert_eq!(read("/sniff_encoding_plain"), b"hello plain");
+    assert_eq!(read("/sniff_encoding_short"), b"h");
+
+    // It's off by default.
+    let resp = get("test://host/sniff_encoding_off").call().unwrap();
+    let mut bytes = vec![];
+    resp.into_reader().read_to_end(&mut bytes).unwrap();
+    assert_eq!(bytes, gzipped);
+}
+
+#[test]
+fn rate_limit_handle() {
+    test::set_handler("/rate_limit_handle", |_unit| {
+        test::make_response(200, "OK", vec![], vec![b'a'; 2000])
+    });
+    let limit = RateLimitHandle::new(100_000);
+    let resp = get("test://host/rate_limit_handle").call().unwrap();
+    let mut reader = resp.into_reader_rate_limited(&limit);
+    let mut buf = [0; 1000];
+
+    let start = Instant::now();
+    reader.read_exact(&mut buf).unwrap();
+    let fast = start.elapsed();
+
+    limit.set_rate(5_000);
+    let start = Instant::now();
+    reader.read_exact(&mut buf).unwrap();
+    let slow = start.elapsed();
+
+    // 1000 bytes take 10ms at the first rate, and 200ms at the second.
+    assert!(slow >= Duration::from_millis(180), "{:?}", slow);
+    assert!(fast < slow, "{:?} {:?}", fast, slow);
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+}
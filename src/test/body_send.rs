@@ -119,3 +119,407 @@ fn content_type_not_overriden_on_json() {
     let s = String::from_utf8_lossy(&vec);
     assert!(s.contains("\r\ncontent-type: text/plain\r\n"));
 }
+
+#[test]
+fn expect_continue_early_final_response() {
+    test::set_handler("/expect_continue_early_final_response", |_unit| {
+        test::make_response(401, "Unauthorized", vec!["Content-Length: 0"], vec![])
+    });
+    let resp = match put("test://host/expect_continue_early_final_response")
+        .set("Expect", "100-continue")
+        .send_string("Hello World!!!")
+    {
+        Err(Error::Status(401, resp)) => resp,
+        other => panic!("expected 401 error, got {:?}", other),
+    };
+    let vec = resp.to_write_vec();
+    let s = String::from_utf8_lossy(&vec);
+    assert!(s.contains("\r\nExpect: 100-continue\r\n"));
+    assert!(s.ends_with("\r\n\r\n"), "body was sent: {:?}", s);
+}
+
+#[test]
+fn expect_continue_interim_response() {
+    test::set_handler("/expect_continue_interim_response", |_unit| {
+        Ok(crate::stream::Stream::from_vec(
+            b"HTTP/1.1 100 Continue\r\n\r\n\
+              HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"
+                .to_vec(),
+        ))
+    });
+    let resp = put("test://host/expect_continue_interim_response")
+        .set("Expect", "100-continue")
+        .send_string("Hello World!!!")
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let vec = resp.to_write_vec();
+    let s = String::from_utf8_lossy(&vec);
+    assert!(s.ends_with("\r\n\r\nHello World!!!"));
+}
+
+#[test]
+fn expect_continue_interim_response_in_pieces() {
+    use std::io;
+
+    // Hands out a few bytes per read, like a response split across packets.
+    struct Trickle(io::Cursor<Vec<u8>>);
+    impl io::Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let max = buf.len().min(4);
+            self.0.read(&mut buf[..max])
+        }
+    }
+
+    test::set_handler("/expect_continue_interim_response_in_pieces", |_unit| {
+        Ok(crate::stream::Stream::from_read(Trickle(io::Cursor::new(
+            b"HTTP/1.1 100 Continue\r\n\r\n\
+              HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"
+                .to_vec(),
+        ))))
+    });
+    let resp = put("test://host/expect_continue_interim_response_in_pieces")
+        .set("Expect", "100-continue")
+        .send_string("Hello World!!!")
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let vec = resp.to_write_vec();
+    let s = String::from_utf8_lossy(&vec);
+    assert!(
+        s.ends_with("\r\n\r\nHello World!!!"),
+        "body not sent: {:?}",
+        s
+    );
+}
+
+// Handler that never sends 100 Continue, but echoes the request body
+// once it arrives.
+fn echo_body_without_continue(stream: std::net::TcpStream) -> std::io::Result<()> {
+    use std::io::{BufRead, BufReader, Read, Write};
+    let mut reader = BufReader::new(&stream);
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if line.to_ascii_lowercase().starts_with("content-length:") {
+            content_length = line[15..].trim().parse().unwrap();
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(&body)
+}
+
+#[test]
+fn expect_continue_timeout_then_send() {
+    use std::time::{Duration, Instant};
+    let testserver = crate::testserver::TestServer::new(echo_body_without_continue);
+    let url = format!("http://localhost:{}/", testserver.port);
+    let agent = builder()
+        .continue_timeout(Duration::from_millis(100))
+        .build();
+    let start = Instant::now();
+    let resp = agent
+        .put(&url)
+        .set("Expect", "100-continue")
+        .send_string("Hello World!!!")
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(resp.into_string().unwrap(), "Hello World!!!");
+}
+
+#[test]
+fn send_file() {
+    test::set_handler("/send_file", |_unit| {
+        test::make_response(200, "OK", vec![], vec![])
+    });
+    let contents: Vec<u8> = (0..=255).cycle().take(70_000).collect();
+    let path = std::env::temp_dir().join(format!("ureq-send-file-{}", std::process::id()));
+    std::fs::write(&path, &contents).unwrap();
+
+    let resp = put("test://host/send_file")
+        .guess_content_type("upload.json")
+        .send_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    let vec = resp.unwrap().to_write_vec();
+    let s = String::from_utf8_lossy(&vec);
+    assert!(s.contains("\r\nContent-Length: 70000\r\n"));
+    assert!(!s.contains("Transfer-Encoding"));
+    assert!(s.contains("\r\nContent-Type: application/json\r\n"));
+    assert!(vec.ends_with(&contents));
+
+    let err = put("test://host/send_file").send_file(&path).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Io);
+}
+
+#[test]
+fn send_sized() {
+    use std::io::Read;
+
+    for path in &["/send_sized_slice", "/send_sized_vec", "/send_sized_file"] {
+        test::set_handler(path, |_unit| test::make_response(200, "OK", vec![], vec![]));
+    }
+    let contents: Vec<u8> = (0..=255).cycle().take(1000).collect();
+    let check = |resp: Response, body: &[u8]| {
+        let vec = resp.to_write_vec();
+        let s = String::from_utf8_lossy(&vec);
+        assert!(s.contains(&format!("\r\nContent-Length: {}\r\n", body.len())));
+        assert!(!s.contains("Transfer-Encoding"));
+        assert!(vec.ends_with(body));
+    };
+
+    let resp = post("test://host/send_sized_slice")
+        .send_sized(&contents[..])
+        .unwrap();
+    check(resp, &contents);
+
+    let resp = post("test://host/send_sized_vec")
+        .send_sized(contents.clone())
+        .unwrap();
+    check(resp, &contents);
+
+    // A file is sent from its current position.
+    let path = std::env::temp_dir().join(format!("ureq-send-sized-{}", std::process::id()));
+    std::fs::write(&path, &contents).unwrap();
+    let mut file = std::fs::File::open(&path).unwrap();
+    let mut skipped = [0; 100];
+    file.read_exact(&mut skipped).unwrap();
+    let resp = post("test://host/send_sized_file").send_sized(file);
+    std::fs::remove_file(&path).unwrap();
+    check(resp.unwrap(), &contents[100..]);
+}
+
+#[test]
+fn send_with_len() {
+    for path in &[
+        "/send_with_len",
+        "/send_with_len_short",
+        "/send_with_len_long",
+    ] {
+        test::set_handler(path, |_unit| test::make_response(200, "OK", vec![], vec![]));
+    }
+    let resp = post("test://host/send_with_len")
+        .send_with_len(&b"hello world"[..], 11)
+        .unwrap();
+    let vec = resp.to_write_vec();
+    let s = String::from_utf8_lossy(&vec);
+    assert!(s.contains("\r\nContent-Length: 11\r\n"));
+    assert!(!s.contains("Transfer-Encoding"));
+    assert!(s.ends_with("\r\n\r\nhello world"));
+
+    let err = post("test://host/send_with_len_short")
+        .send_with_len(&b"hello"[..], 11)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Io);
+
+    let err = post("test://host/send_with_len_long")
+        .send_with_len(&b"hello world!!!"[..], 11)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Io);
+}
+
+#[test]
+fn send_source_error_mid_body() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    struct BrokenRead;
+    impl Read for BrokenRead {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "broken"))
+        }
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://localhost:{}/",
+        listener.local_addr().unwrap().port()
+    );
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut received = vec![];
+        // Only returns once the client closes the connection.
+        stream.read_to_end(&mut received).unwrap();
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n");
+        received
+    });
+
+    let agent = builder().build();
+    let source = (&[b'a'; 20_000][..]).chain(BrokenRead);
+    let err = agent.post(&url).send(source).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Io);
+    assert!(err.to_string().contains("broken"));
+
+    // The connection was closed, not pooled, and the body never terminated.
+    let received = server.join().unwrap();
+    let s = String::from_utf8_lossy(&received);
+    assert!(s.contains("\r\nTransfer-Encoding: chunked\r\n"));
+    assert!(s.ends_with("aaa\r\n"));
+    assert!(!s.contains("\r\n0\r\n\r\n"));
+}
+
+#[test]
+fn call_with_body() {
+    for path in &[
+        "/call_with_body_str",
+        "/call_with_body_vec",
+        "/call_with_body_reader",
+        "/call_with_body_form",
+    ] {
+        test::set_handler(path, |_unit| test::make_response(200, "OK", vec![], vec![]));
+    }
+    let written = |resp: Result<Response, Error>| {
+        let vec = resp.unwrap().to_write_vec();
+        String::from_utf8_lossy(&vec).to_string()
+    };
+
+    let s = written(post("test://host/call_with_body_str").call_with_body("hello"));
+    assert!(s.contains("\r\nContent-Length: 5\r\n"));
+    assert!(s.ends_with("\r\n\r\nhello"));
+
+    let s = written(post("test://host/call_with_body_vec").call_with_body(b"bytes".to_vec()));
+    assert!(s.contains("\r\nContent-Length: 5\r\n"));
+    assert!(s.ends_with("\r\n\r\nbytes"));
+
+    let s = written(
+        post("test://host/call_with_body_reader").call_with_body(Body::reader(&b"stream"[..])),
+    );
+    assert!(s.contains("\r\nTransfer-Encoding: chunked\r\n"));
+    assert!(s.ends_with("\r\n\r\n6\r\nstream\r\n0\r\n\r\n"));
+
+    let s = written(
+        post("test://host/call_with_body_form").call_with_body(Body::form(&[("a", "b c")])),
+    );
+    assert!(s.contains("\r\nContent-Type: application/x-www-form-urlencoded\r\n"));
+    assert!(s.contains("\r\nContent-Length: 5\r\n"));
+    assert!(s.ends_with("\r\n\r\na=b+c"));
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn call_with_body_json() {
+    test::set_handler("/call_with_body_json", |_unit| {
+        test::make_response(200, "OK", vec![], vec![])
+    });
+    let resp = post("test://host/call_with_body_json")
+        .call_with_body(json!({"hello": "world"}))
+        .unwrap();
+    let vec = resp.to_write_vec();
+    let s = String::from_utf8_lossy(&vec);
+    assert!(s.contains("\r\nContent-Type: application/json\r\n"));
+    assert!(s.ends_with("\r\n\r\n{\"hello\":\"world\"}"));
+}
+
+#[test]
+fn send_chunked_with_trailers() {
+    use std::cell::Cell;
+    use std::io::{self, Read};
+
+    // Counts the bytes read, like a checksum computed during the upload.
+    struct Counting<'a>(&'a [u8], &'a Cell<usize>);
+    impl Read for Counting<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.read(buf)?;
+            self.1.set(self.1.get() + n);
+            Ok(n)
+        }
+    }
+
+    test::set_handler("/send_chunked_with_trailers", |_unit| {
+        test::make_response(200, "OK", vec![], vec![])
+    });
+    let count = Cell::new(0);
+    let resp = post("test://host/send_chunked_with_trailers")
+        .set("Trailer", "X-Length")
+        .send_chunked_with_trailers(Counting(b"hello world", &count), || {
+            vec![
+                Header::new("X-Length", &count.get().to_string()),
+                Header::new("X-Done", "yes"),
+            ]
+        })
+        .unwrap();
+    let s = String::from_utf8(resp.to_write_vec()).unwrap();
+    assert!(s.contains("\r\nTransfer-Encoding: chunked\r\n"));
+    assert!(s.ends_with("\r\n\r\nb\r\nhello world\r\n0\r\nX-Length: 11\r\nX-Done: yes\r\n\r\n"));
+}
+
+#[test]
+fn send_with() {
+    use std::io;
+
+    for path in &[
+        "/send_with_chunked",
+        "/send_with_length",
+        "/send_with_error",
+    ] {
+        test::set_handler(path, |_unit| test::make_response(200, "OK", vec![], vec![]));
+    }
+    let resp = post("test://host/send_with_chunked")
+        .send_with(|body| {
+            body.write_all(b"hello")?;
+            body.write_all(b" ")?;
+            body.flush()?;
+            write!(body, "world")?;
+            body.flush()?;
+            body.write_all(b"!")
+        })
+        .unwrap();
+    let s = String::from_utf8(resp.to_write_vec()).unwrap();
+    assert!(s.contains("\r\nTransfer-Encoding: chunked\r\n"));
+    assert!(s.ends_with("\r\n\r\n6\r\nhello \r\n5\r\nworld\r\n1\r\n!\r\n0\r\n\r\n"));
+
+    let resp = post("test://host/send_with_length")
+        .set("Content-Length", "11")
+        .send_with(|body| {
+            body.write_all(b"hello ")?;
+            body.flush()?;
+            body.write_all(b"world")
+        })
+        .unwrap();
+    let s = String::from_utf8(resp.to_write_vec()).unwrap();
+    assert!(s.contains("\r\nContent-Length: 11\r\n"));
+    assert!(!s.contains("Transfer-Encoding"));
+    assert!(s.ends_with("\r\n\r\nhello world"));
+
+    let err = post("test://host/send_with_error")
+        .send_with(|_body| Err(io::Error::new(io::ErrorKind::Other, "no data")))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Io);
+    assert!(err.to_string().contains("no data"));
+}
+
+#[test]
+fn content_encoding() {
+    test::set_handler("/content_encoding", |_unit| {
+        test::make_response(200, "OK", vec![], vec![])
+    });
+    let body = [0x28, 0xb5, 0x2f, 0xfd, 0x00, 0xff, 0x0d, 0x0a];
+    let resp = post("test://host/content_encoding")
+        .content_encoding("zstd")
+        .send_bytes(&body)
+        .unwrap();
+    let vec = resp.to_write_vec();
+    let s = String::from_utf8_lossy(&vec);
+    assert!(s.contains("\r\nContent-Encoding: zstd\r\n"));
+    assert!(s.contains("\r\nContent-Length: 8\r\n"));
+    assert!(vec.ends_with(b"\r\n\r\n\x28\xb5\x2f\xfd\x00\xff\x0d\x0a"));
+
+    let err = post("test://host/content_encoding_bad")
+        .content_encoding("gzip; q=1")
+        .send_bytes(&body)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BadHeader);
+}
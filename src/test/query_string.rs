@@ -56,3 +56,33 @@ fn query_in_path_and_req() {
     let s = String::from_utf8_lossy(&vec);
     assert!(s.contains("GET /query_in_path_and_req?foo=bar&baz=1+2+3 HTTP/1.1"))
 }
+
+#[test]
+fn query_space_as_plus() {
+    for path in &["/query_space_plus", "/query_space_pct"] {
+        test::set_handler(path, |_unit| test::make_response(200, "OK", vec![], vec![]));
+    }
+    let resp = get("test://host/query_space_plus?a=b")
+        .query("q", "fish & chips")
+        .call()
+        .unwrap();
+    let s = String::from_utf8(resp.to_write_vec()).unwrap();
+    assert!(
+        s.contains("GET /query_space_plus?a=b&q=fish+%26+chips HTTP/1.1"),
+        "req: {}",
+        s
+    );
+
+    let resp = get("test://host/query_space_pct?a=b")
+        .query_space_as_plus(false)
+        .query("q", "fish & chips")
+        .query("x y", "1+1")
+        .call()
+        .unwrap();
+    let s = String::from_utf8(resp.to_write_vec()).unwrap();
+    assert!(
+        s.contains("GET /query_space_pct?a=b&q=fish%20%26%20chips&x%20y=1%2B1 HTTP/1.1"),
+        "req: {}",
+        s
+    );
+}
@@ -21,6 +21,89 @@ fn redirect_on() {
     assert_eq!(resp.header("x-foo").unwrap(), "bar");
 }
 
+#[test]
+fn redirect_url() {
+    test::set_handler("/redirect_url1", |_| {
+        test::make_response(
+            302,
+            "Go here",
+            vec!["Location: test://otherhost/redirect_url2?x=1"],
+            vec![],
+        )
+    });
+    test::set_handler("/redirect_url2", |_| {
+        test::make_response(200, "OK", vec![], vec![])
+    });
+    let resp = get("test://host/redirect_url1").call().unwrap();
+    let url = resp.url().unwrap();
+    assert_eq!(url.host_str(), Some("otherhost"));
+    assert_eq!(url.path(), "/redirect_url2");
+    assert_eq!(url.query(), Some("x=1"));
+    assert_eq!(resp.get_url(), "test://otherhost/redirect_url2?x=1");
+
+    let resp = Response::new(200, "OK", "").unwrap();
+    assert!(resp.url().is_none());
+}
+
+#[test]
+fn redirect_history_headers() {
+    test::set_handler("/redirect_history1", |_| {
+        test::make_response(
+            302,
+            "Go here",
+            vec!["Location: /redirect_history2", "Set-Cookie: hop=1"],
+            vec![],
+        )
+    });
+    test::set_handler("/redirect_history2", |_| {
+        test::make_response(
+            301,
+            "Moved",
+            vec!["Location: /redirect_history3", "Set-Cookie: hop=2"],
+            vec![],
+        )
+    });
+    test::set_handler("/redirect_history3", |_| {
+        test::make_response(200, "OK", vec![], vec![])
+    });
+    let resp = get("test://host/redirect_history1").call().unwrap();
+    assert_eq!(resp.get_url(), "test://host/redirect_history3");
+
+    let hops: Vec<_> = resp.redirect_history().collect();
+    assert_eq!(hops.len(), 2);
+    assert_eq!(hops[0].status(), 301);
+    assert_eq!(hops[0].get_url(), "test://host/redirect_history2");
+    assert_eq!(hops[0].header("set-cookie"), Some("hop=2"));
+    assert_eq!(hops[0].header("location"), Some("/redirect_history3"));
+    assert_eq!(hops[1].status(), 302);
+    assert_eq!(hops[1].get_url(), "test://host/redirect_history1");
+    assert_eq!(hops[1].header("set-cookie"), Some("hop=1"));
+
+    let resp = Response::new(200, "OK", "").unwrap();
+    assert_eq!(resp.redirect_history().count(), 0);
+}
+
+#[test]
+fn redirect_keeps_extensions() {
+    struct Marker;
+    test::set_handler("/redirect_keeps_extensions1", |_| {
+        test::make_response(
+            302,
+            "Go here",
+            vec!["Location: /redirect_keeps_extensions2"],
+            vec![],
+        )
+    });
+    test::set_handler("/redirect_keeps_extensions2", |unit| {
+        assert!(unit.extensions.get::<Marker>().is_some());
+        test::make_response(200, "OK", vec![], vec![])
+    });
+    let mut req = get("test://host/redirect_keeps_extensions1");
+    req.extensions_mut().insert(Marker);
+    let resp = req.call().unwrap();
+    assert!(resp.extensions().get::<Marker>().is_some());
+}
+
 #[test]
 fn redirect_many() {
     test::set_handler("/redirect_many1", |_| {
@@ -127,3 +210,36 @@ fn redirect_post() {
     assert!(resp.has("x-foo"));
     assert_eq!(resp.header("x-foo").unwrap(), "bar");
 }
+
+#[test]
+fn redirect_count() {
+    test::set_handler("/redirect_count1", |_| {
+        test::make_response(302, "Go here", vec!["Location: /redirect_count2"], vec![])
+    });
+    test::set_handler("/redirect_count2", |_| {
+        test::make_response(301, "Moved", vec!["Location: /redirect_count3"], vec![])
+    });
+    test::set_handler("/redirect_count3", |_| {
+        test::make_response(200, "OK", vec![], vec![])
+    });
+    let resp = get("test://host/redirect_count1").call().unwrap();
+    assert_eq!(resp.redirect_count(), 2);
+
+    let resp = Response::new(200, "OK", "").unwrap();
+    assert_eq!(resp.redirect_count(), 0);
+}
+
+#[test]
+fn no_redirects() {
+    test::set_handler("/no_redirects", |_| {
+        test::make_response(302, "Found", vec!["Location: /no_redirects2"], vec![])
+    });
+    let resp = get("test://host/no_redirects")
+        .no_redirects()
+        .call()
+        .unwrap();
+    assert_eq!(resp.status(), 302);
+    assert_eq!(resp.header("location"), Some("/no_redirects2"));
+    assert_eq!(resp.get_url(), "test://host/no_redirects");
+    assert_eq!(resp.redirect_count(), 0);
+}
@@ -4,7 +4,7 @@ use std::thread;
 use std::time::Duration;
 use std::{
     error::Error,
-    io::{self, Write},
+    io::{self, Read, Write},
 };
 
 use super::super::*;
@@ -69,6 +69,43 @@ fn read_timeout_during_body() {
     .expect("expected timeout but got something else");
 }
 
+#[test]
+fn no_timeout_during_body() {
+    let server = TestServer::new(|stream| dribble_body_respond(stream, &[b'a'; 5]));
+    let url = format!("http://localhost:{}/", server.port);
+    let agent = builder()
+        .timeout_read(Duration::from_millis(70))
+        .timeout(Duration::from_millis(200))
+        .build();
+    let body = agent
+        .get(&url)
+        .no_timeout()
+        .call()
+        .unwrap()
+        .into_string()
+        .unwrap();
+    assert_eq!(body, "a\na\na\na\na\n");
+}
+
+#[test]
+fn no_timeout_overrides_host_config() {
+    let server = TestServer::new(|stream| dribble_body_respond(stream, &[b'a'; 5]));
+    let url = format!("http://localhost:{}/", server.port);
+    let agent = builder()
+        .host_config("localhost", |host| {
+            host.timeout(Duration::from_millis(200));
+        })
+        .build();
+    let body = agent
+        .get(&url)
+        .no_timeout()
+        .call()
+        .unwrap()
+        .into_string()
+        .unwrap();
+    assert_eq!(body, "a\na\na\na\na\n");
+}
+
 // Send HTTP headers on the TcpStream at a rate of one header every 100
 // milliseconds, for a total of 30 headers.
 fn dribble_headers_respond(mut stream: TcpStream) -> io::Result<()> {
@@ -153,3 +190,146 @@ fn overall_timeout_reading_json() {
     }
     .expect("expected timeout but got something else");
 }
+
+#[test]
+fn time_to_first_byte() {
+    let server = TestServer::new(|mut stream| {
+        read_request(&stream);
+        stream.set_nonblocking(false)?;
+        thread::sleep(Duration::from_millis(200));
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")?;
+        Ok(())
+    });
+    let url = format!("http://localhost:{}/", server.port);
+
+    let agent = builder().timing(true).build();
+    let resp = agent.get(&url).call().unwrap();
+    let ttfb = resp.time_to_first_byte().unwrap();
+    assert!(ttfb >= Duration::from_millis(200), "{:?}", ttfb);
+    assert!(ttfb < Duration::from_secs(5), "{:?}", ttfb);
+
+    let resp = builder().build().get(&url).call().unwrap();
+    assert_eq!(resp.time_to_first_byte(), None);
+}
+
+#[test]
+fn mock_clock_past_deadline() {
+    use crate::test;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    let now = Arc::new(Mutex::new(Instant::now()));
+    let clock = now.clone();
+    let agent = builder()
+        .timeout(Duration::from_secs(10))
+        .clock(move || *clock.lock().unwrap())
+        .build();
+
+    // The server "takes" 9 and then 11 seconds to respond.
+    for (path, secs) in &[("/mock_clock_9s", 9), ("/mock_clock_11s", 11)] {
+        let now = now.clone();
+        test::set_handler(path, move |_unit| {
+            *now.lock().unwrap() += Duration::from_secs(*secs);
+            test::make_response(200, "OK", vec![], vec![])
+        });
+    }
+
+    agent.get("test://host/mock_clock_9s").call().unwrap();
+
+    let err = agent.get("test://host/mock_clock_11s").call().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Io);
+    let ioe: Option<&io::Error> = err.source().and_then(|s| s.downcast_ref());
+    assert_eq!(ioe.map(|e| e.kind()), Some(io::ErrorKind::TimedOut));
+}
+
+#[test]
+#[cfg(feature = "tls")]
+fn connect_timeout_during_tls_handshake() {
+    use std::net::TcpListener;
+
+    // Accepts the connection, but never answers the client hello.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let local_addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || listener.accept().map(|(stream, _)| stream));
+
+    let err = builder()
+        .resolver(move |_: &str| Ok(vec![local_addr]))
+        .timeout_connect(Duration::from_millis(100))
+        .build()
+        .get("https://cool.server/")
+        .call()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ConnectionFailed);
+    let ioe: Option<&io::Error> = err.source().and_then(|s| s.downcast_ref());
+    assert_eq!(ioe.map(|e| e.kind()), Some(io::ErrorKind::TimedOut));
+    drop(server.join());
+}
+
+#[test]
+fn default_timeout_for_top_level_functions() {
+    // Takes longer than the timeout to respond.
+    let server = TestServer::new(|stream| {
+        read_request(&stream);
+        thread::sleep(Duration::from_secs(2));
+        Ok(())
+    });
+    let url = format!("http://localhost:{}/", server.port);
+
+    // Goes through the same path as agent(), without touching the
+    // process-wide default that other tests depend on.
+    let agent = with_default_timeout(builder().build(), Some(Duration::from_millis(200)));
+    let err = agent.get(&url).call().unwrap_err();
+    let ioe: Option<&io::Error> = err.source().and_then(|s| s.downcast_ref());
+    assert_eq!(ioe.map(|e| e.kind()), Some(io::ErrorKind::TimedOut));
+}
+
+// Send the headers and the first two bytes of the body, then stall.
+fn stalled_body_respond(mut stream: TcpStream) -> io::Result<()> {
+    read_request(&stream);
+    stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nab")?;
+    stream.flush()?;
+    thread::sleep(Duration::from_secs(2));
+    stream.write_all(b"cd")?;
+    Ok(())
+}
+
+#[test]
+fn read_call_timeout() {
+    let server = TestServer::new(stalled_body_respond);
+    let url = format!("http://localhost:{}/", server.port);
+    let (_, mut body) = get(&url).call().unwrap().split();
+    body.set_read_call_timeout(Duration::from_millis(100));
+
+    let mut buf = [0; 4];
+    let mut n = 0;
+    while n < 2 {
+        n += body.read(&mut buf[n..]).unwrap();
+    }
+    assert_eq!(&buf[..2], b"ab");
+
+    let start = std::time::Instant::now();
+    let err = body.read(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn read_call_timeout_longer_than_timeout_read() {
+    let server = TestServer::new(stalled_body_respond);
+    let url = format!("http://localhost:{}/", server.port);
+    let agent = builder().timeout_read(Duration::from_millis(100)).build();
+    let (_, mut body) = agent.get(&url).call().unwrap().split();
+    body.set_read_call_timeout(Duration::from_secs(10));
+
+    let mut buf = [0; 4];
+    let mut n = 0;
+    while n < 2 {
+        n += body.read(&mut buf[n..]).unwrap();
+    }
+
+    // The shorter read timeout still applies.
+    let start = std::time::Instant::now();
+    let err = body.read(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
@@ -86,7 +86,6 @@ impl TestHeaders {
         }
     }
 
-    #[cfg(feature = "cookies")]
     pub fn headers(&self) -> &[String] {
         &self.0[1..]
     }
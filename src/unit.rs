@@ -1,7 +1,8 @@
 use std::time;
 use std::{
-    io::{self, Write},
-    sync::Arc,
+    io::{self, BufRead, Read, Write},
+    net::SocketAddr,
+    sync::{atomic::Ordering, Arc},
 };
 
 use log::{debug, info};
@@ -11,12 +12,15 @@ use url::Url;
 use cookie::Cookie;
 
 use crate::body::{self, BodySize, Payload, SizedReader};
+use crate::clock::ArcClock;
 use crate::error::{Error, ErrorKind};
+use crate::extensions::Extensions;
 use crate::header;
 use crate::header::{get_header, Header};
+use crate::request::HttpVersion;
 use crate::resolve::ArcResolver;
 use crate::response::Response;
-use crate::stream::{self, connect_test, Stream};
+use crate::stream::{self, connect_test, time_until_deadline, Stream};
 use crate::Agent;
 
 /// A Unit is fully-built Request, ready to execute.
@@ -30,6 +34,23 @@ pub(crate) struct Unit {
     is_chunked: bool,
     headers: Vec<Header>,
     pub deadline: Option<time::Instant>,
+    // When the request was sent, if the agent records timings.
+    pub sent_at: Option<time::Instant>,
+    pub extensions: Extensions,
+    pub http_version: HttpVersion,
+    // The headers as written to the wire, once the request is sent.
+    pub sent_headers: Vec<Header>,
+    // Overrides whether the method is idempotent, for retries.
+    pub idempotent: Option<bool>,
+    // Whether the request went out on a connection from the pool.
+    pub connection_reused: bool,
+    // Lowercase names of headers to send first, in this order.
+    pub header_order: Vec<String>,
+    // Return 3xx responses instead of following them, whatever the agent says.
+    pub no_redirects: bool,
+    // Encodings to try decoding a text body with, after the declared one.
+    #[cfg(feature = "charset")]
+    pub charset_fallback: Vec<String>,
 }
 
 impl Unit {
@@ -44,6 +65,25 @@ impl Unit {
     ) -> Self {
         //
 
+        let rewritten;
+        let url = match &agent.config.url_rewrite {
+            Some(rewrite) => {
+                rewritten = rewrite.rewrite(url);
+                &rewritten
+            }
+            None => url,
+        };
+
+        let upgraded;
+        let url = if url.scheme() == "http" && needs_https(agent, url) {
+            let mut url = url.clone();
+            url.set_scheme("https").unwrap();
+            upgraded = url;
+            &upgraded
+        } else {
+            url
+        };
+
         let (is_transfer_encoding_set, mut is_chunked) = get_header(&headers, "transfer-encoding")
             // if the user has set an encoding header, obey that.
             .map(|enc| {
@@ -101,10 +141,15 @@ impl Unit {
             .cloned()
             .collect();
 
-        let deadline = match agent.config.timeout {
+        let timeout = agent
+            .config
+            .host_config(url)
+            .and_then(|host| host.timeout)
+            .or(agent.config.timeout);
+        let deadline = match timeout {
             None => None,
             Some(timeout) => {
-                let now = time::Instant::now();
+                let now = agent.state.clock.now();
                 Some(now.checked_add(timeout).unwrap())
             }
         };
@@ -116,6 +161,16 @@ impl Unit {
             is_chunked,
             headers,
             deadline,
+            sent_at: None,
+            extensions: Extensions::new(),
+            http_version: HttpVersion::Http11,
+            sent_headers: vec![],
+            idempotent: None,
+            connection_reused: false,
+            header_order: vec![],
+            no_redirects: false,
+            #[cfg(feature = "charset")]
+            charset_fallback: vec![],
         }
     }
 
@@ -127,6 +182,10 @@ impl Unit {
         self.agent.state.resolver.clone()
     }
 
+    pub fn clock(&self) -> ArcClock {
+        self.agent.state.clock.clone()
+    }
+
     #[cfg(test)]
     pub fn header(&self, name: &str) -> Option<&str> {
         header::get_header(&self.headers, name)
@@ -135,7 +194,6 @@ impl Unit {
     pub fn has(&self, name: &str) -> bool {
         header::has_header(&self.headers, name)
     }
-    #[cfg(test)]
     pub fn all(&self, name: &str) -> Vec<&str> {
         header::get_all_headers(&self.headers, name)
     }
@@ -144,10 +202,11 @@ impl Unit {
     pub(crate) fn is_retryable(&self, body: &SizedReader) -> bool {
         // Per https://tools.ietf.org/html/rfc7231#section-8.1.3
         // these methods are idempotent.
-        let idempotent = match self.method.as_str() {
+        let by_method = match self.method.as_str() {
             "DELETE" | "GET" | "HEAD" | "OPTIONS" | "PUT" | "TRACE" => true,
             _ => false,
         };
+        let idempotent = self.idempotent.unwrap_or(by_method);
         // Unsized bodies aren't retryable because we can't rewind the reader.
         // Sized bodies are retryable only if they are zero-length because of
         // coincidences of the current implementation - the function responsible
@@ -161,11 +220,52 @@ impl Unit {
 
         idempotent && retryable_body
     }
+
+    // Returns true if the user asked to wait for 100 Continue before sending
+    // the provided body.
+    fn expects_continue(&self, body: &SizedReader) -> bool {
+        let has_body = match body.size {
+            BodySize::Empty | BodySize::Known(0) => false,
+            BodySize::Known(_) | BodySize::Unknown => true,
+        };
+        has_body
+            && get_header(&self.headers, "expect")
+                .map(|v| v.trim().eq_ignore_ascii_case("100-continue"))
+                .unwrap_or(false)
+    }
+}
+
+/// Wait for the server to answer the request headers, up to continue_timeout.
+/// Returns false if the server sent a final response, meaning we should not
+/// send the body. On `100 Continue` or when the wait times out, returns true.
+/// The interim response is left in the stream, and skipped when the response
+/// is read.
+fn await_continue(unit: &Unit, stream: &mut Stream) -> io::Result<bool> {
+    stream.set_read_timeout(Some(unit.agent.config.continue_timeout))?;
+    // The status line may arrive in pieces, so read up to its end. It's put
+    // back for the response to be read as usual.
+    let mut line = vec![];
+    let limit = unit.agent.config.max_status_line_length as u64 + 2;
+    let result = match (&mut *stream).take(limit).read_until(b'\n', &mut line) {
+        // "HTTP/1.1 1xx": the status code starts at offset 9.
+        Ok(_) => Ok(line.get(9) == Some(&b'1')),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => Ok(true),
+        Err(e) => Err(e),
+    };
+    stream.unread(line);
+    match unit.deadline {
+        Some(deadline) => {
+            stream.set_read_timeout(Some(time_until_deadline(deadline, &*unit.clock())?))?
+        }
+        None => stream.set_read_timeout(unit.agent.config.timeout_read)?,
+    }
+    result
 }
 
 /// Perform a connection. Used recursively for redirects.
 pub(crate) fn connect(
-    unit: Unit,
+    mut unit: Unit,
     use_pooled: bool,
     body: SizedReader,
     previous: Option<Arc<Response>>,
@@ -178,6 +278,9 @@ pub(crate) fn connect(
         .ok_or(ErrorKind::InvalidUrl.msg("no host in URL"))?;
     let url = &unit.url;
     let method = &unit.method;
+    check_quota(&unit)?;
+    #[cfg(feature = "inspect")]
+    let started = time::Instant::now();
     // open socket
     let (mut stream, is_recycled) = connect_socket(&unit, &host, use_pooled)?;
 
@@ -187,7 +290,18 @@ pub(crate) fn connect(
         info!("sending request {} {}", method, url);
     }
 
-    let send_result = send_prelude(&unit, &mut stream, previous.is_some());
+    // HTTP/1.0 connections aren't persistent unless negotiated with
+    // `Connection: keep-alive`, which we don't do.
+    if unit.http_version == HttpVersion::Http10 {
+        stream.disable_reuse();
+    }
+
+    unit.connection_reused = is_recycled;
+    if unit.agent.config.timing {
+        unit.sent_at = Some(time::Instant::now());
+    }
+    unit.sent_headers = request_headers(&unit, previous.is_some());
+    let send_result = send_prelude(&unit, &mut stream);
 
     if let Err(err) = send_result {
         if is_recycled {
@@ -202,8 +316,24 @@ pub(crate) fn connect(
     }
     let retryable = unit.is_retryable(&body);
 
-    // send the body (which can be empty now depending on redirects)
-    body::send_body(body, unit.is_chunked, &mut stream)?;
+    // send the body (which can be empty now depending on redirects), unless
+    // the server answered our Expect: 100-continue with a final response.
+    if unit.expects_continue(&body) && !await_continue(&unit, &mut stream)? {
+        debug!(
+            "server rejected body with early response {} {}",
+            method, url
+        );
+        // The server still expects the body we never sent, so the connection
+        // can't be used for another request.
+        stream.disable_reuse();
+    } else {
+        body::send_body(body, unit.is_chunked, &mut stream)?;
+    }
+    #[cfg(feature = "inspect")]
+    crate::inspect::emit(|| crate::inspect::InspectEvent::Request {
+        method: method.clone(),
+        url: url.to_string(),
+    });
 
     // start reading the response to process cookies and redirects.
     let result = Response::do_from_request(unit.clone(), stream, previous.clone());
@@ -227,15 +357,31 @@ pub(crate) fn connect(
         Err(e) => return Err(e),
         Ok(resp) => resp,
     };
+    #[cfg(feature = "inspect")]
+    crate::inspect::emit(|| crate::inspect::InspectEvent::Response {
+        method: method.clone(),
+        url: url.to_string(),
+        status: resp.status(),
+        elapsed: started.elapsed(),
+    });
 
     // squirrel away cookies
     #[cfg(feature = "cookies")]
     save_cookies(&unit, &resp);
 
+    if unit.agent.config.hsts {
+        save_hsts(&unit, &resp);
+    }
+
     // handle redirects
-    if (300..399).contains(&resp.status()) && unit.agent.config.redirects > 0 {
+    let redirects = if unit.no_redirects {
+        0
+    } else {
+        unit.agent.config.redirects
+    };
+    if (300..399).contains(&resp.status()) && redirects > 0 {
         if let Some(previous) = previous {
-            if previous.history().count() + 1 >= unit.agent.config.redirects as usize {
+            if previous.history().count() + 1 >= redirects as usize {
                 return Err(ErrorKind::TooManyRedirects.new());
             }
         }
@@ -261,8 +407,15 @@ pub(crate) fn connect(
                         _ => "GET".into(),
                     };
                     // recreate the unit to get a new hostname and cookies for the new host.
-                    let new_unit =
+                    let mut new_unit =
                         Unit::new(&unit.agent, &new_method, &new_url, &unit.headers, &empty);
+                    new_unit.extensions = unit.extensions.clone();
+                    new_unit.http_version = unit.http_version;
+                    new_unit.header_order = unit.header_order.clone();
+                    #[cfg(feature = "charset")]
+                    {
+                        new_unit.charset_fallback = unit.charset_fallback.clone();
+                    }
 
                     debug!("redirect {} {} -> {}", resp.status(), url, new_url);
                     return connect(new_unit, use_pooled, empty, Some(Arc::new(resp)));
@@ -302,6 +455,23 @@ fn extract_cookies(agent: &Agent, url: &Url) -> Option<Header> {
     }
 }
 
+// Count one more request against the agent's quota, or fail if it's used up.
+fn check_quota(unit: &Unit) -> Result<(), Error> {
+    if let Some(quota) = unit.agent.config.request_quota {
+        let sent = unit
+            .agent
+            .state
+            .requests_sent
+            .fetch_add(1, Ordering::SeqCst);
+        if sent >= quota {
+            return Err(
+                ErrorKind::QuotaExceeded.msg(&format!("request quota of {} exhausted", quota))
+            );
+        }
+    }
+    Ok(())
+}
+
 /// Connect the socket, either by using the pool or grab a new one.
 fn connect_socket(unit: &Unit, hostname: &str, use_pooled: bool) -> Result<(Stream, bool), Error> {
     match unit.url.scheme() {
@@ -328,29 +498,54 @@ fn connect_socket(unit: &Unit, hostname: &str, use_pooled: bool) -> Result<(Stre
         "test" => connect_test(&unit),
         scheme => Err(ErrorKind::UnknownScheme.msg(&format!("unknown scheme {}", scheme))),
     };
-    Ok((stream?, false))
+    let mut stream = stream?;
+    stream.count_io(unit.agent.state.io_counters.clone());
+    Ok((stream, false))
 }
 
-/// Send request line + headers (all up until the body).
-#[allow(clippy::write_with_newline)]
-fn send_prelude(unit: &Unit, stream: &mut Stream, redir: bool) -> io::Result<()> {
-    //
+/// Open a connection for the unit's url, and put it in the pool unused.
+/// Returns the address it's connected to, if it's a TCP connection.
+pub(crate) fn preconnect(unit: &Unit) -> Result<Option<SocketAddr>, Error> {
+    let host = unit
+        .url
+        .host_str()
+        .ok_or_else(|| ErrorKind::InvalidUrl.msg("no host in URL"))?;
+    let (mut stream, _) = connect_socket(unit, host, false)?;
+    // Like a stream returned after a response, without the request's timeouts.
+    stream.reset()?;
+    let remote_addr = stream.socket().and_then(|s| s.peer_addr().ok());
+    let proxy = unit.agent.config.proxy.clone();
+    unit.agent.state.pool.add_unused(&unit.url, proxy, stream);
+    Ok(remote_addr)
+}
 
-    // build into a buffer and send in one go.
-    let mut prelude: Vec<u8> = vec![];
+// Headers carrying credentials.
+pub(crate) fn is_sensitive(header: &Header) -> bool {
+    header.is_name("Authorization")
+        || header.is_name("Proxy-Authorization")
+        || header.is_name("Cookie")
+}
 
-    // request line
-    write!(
-        prelude,
-        "{} {}{}{} HTTP/1.1\r\n",
-        unit.method,
-        unit.url.path(),
-        if unit.url.query().is_some() { "?" } else { "" },
-        unit.url.query().unwrap_or_default(),
-    )?;
+/// The headers to send, including the ones ureq adds itself.
+fn request_headers(unit: &Unit, redir: bool) -> Vec<Header> {
+    let mut headers = vec![];
+
+    // Defaults for the host, unless set on the request. They're added here,
+    // rather than to the unit, so a redirect to another host doesn't get them.
+    let host_headers: Vec<&Header> = match unit.agent.config.host_config(&unit.url) {
+        Some(host) => host
+            .headers
+            .iter()
+            .filter(|h| !header::has_header(&unit.headers, h.name()))
+            .collect(),
+        None => vec![],
+    };
+    let is_set = |name: &str| {
+        header::has_header(&unit.headers, name) || host_headers.iter().any(|h| h.is_name(name))
+    };
 
     // host header if not set by user.
-    if !header::has_header(&unit.headers, "host") {
+    if !is_set("host") {
         let host = unit.url.host().unwrap();
         match unit.url.port() {
             Some(port) => {
@@ -360,32 +555,76 @@ fn send_prelude(unit: &Unit, stream: &mut Stream, redir: bool) -> io::Result<()>
                     _ => 0,
                 };
                 if scheme_default != 0 && scheme_default == port {
-                    write!(prelude, "Host: {}\r\n", host)?;
+                    headers.push(Header::new("Host", &host.to_string()));
                 } else {
-                    write!(prelude, "Host: {}:{}\r\n", host, port)?;
+                    headers.push(Header::new("Host", &format!("{}:{}", host, port)));
                 }
             }
             None => {
-                write!(prelude, "Host: {}\r\n", host)?;
+                headers.push(Header::new("Host", &host.to_string()));
             }
         }
     }
-    if !header::has_header(&unit.headers, "user-agent") {
-        write!(
-            prelude,
-            "User-Agent: ureq/{}\r\n",
-            env!("CARGO_PKG_VERSION")
-        )?;
+    if !is_set("user-agent") {
+        let agent = format!("ureq/{}", env!("CARGO_PKG_VERSION"));
+        headers.push(Header::new("User-Agent", &agent));
     }
-    if !header::has_header(&unit.headers, "accept") {
-        write!(prelude, "Accept: */*\r\n")?;
+    if !is_set("accept") {
+        headers.push(Header::new("Accept", "*/*"));
+    }
+    if !is_set("accept-language") {
+        if let Some(languages) = &unit.agent.config.accept_language {
+            headers.push(Header::new("Accept-Language", languages));
+        }
     }
 
     // other headers
-    for header in &unit.headers {
-        if !redir || !header.is_name("Authorization") {
-            write!(prelude, "{}: {}\r\n", header.name(), header.value())?;
+    for header in host_headers.into_iter().chain(&unit.headers) {
+        if redir && header.is_name("Authorization") {
+            continue;
         }
+        // https://tools.ietf.org/html/rfc7231#section-4.3.8
+        // A client MUST NOT generate header fields in a TRACE request
+        // containing sensitive data that might be disclosed by the response.
+        if unit.method.eq_ignore_ascii_case("TRACE") && is_sensitive(header) {
+            continue;
+        }
+        headers.push(header.clone());
+    }
+
+    // Stable, so headers not in the order keep theirs after the ones that are.
+    if !unit.header_order.is_empty() {
+        headers.sort_by_key(|h| {
+            let name = h.name().to_ascii_lowercase();
+            let pos = unit.header_order.iter().position(|n| *n == name);
+            pos.unwrap_or(unit.header_order.len())
+        });
+    }
+
+    headers
+}
+
+/// Send request line + headers (all up until the body).
+#[allow(clippy::write_with_newline)]
+fn send_prelude(unit: &Unit, stream: &mut Stream) -> io::Result<()> {
+    //
+
+    // build into a buffer and send in one go.
+    let mut prelude: Vec<u8> = vec![];
+
+    // request line
+    write!(
+        prelude,
+        "{} {}{}{} {}\r\n",
+        unit.method,
+        unit.url.path(),
+        if unit.url.query().is_some() { "?" } else { "" },
+        unit.url.query().unwrap_or_default(),
+        unit.http_version.as_str(),
+    )?;
+
+    for header in &unit.sent_headers {
+        write!(prelude, "{}: {}\r\n", header.name(), header.value())?;
     }
 
     // finish
@@ -398,6 +637,35 @@ fn send_prelude(unit: &Unit, stream: &mut Stream, redir: bool) -> io::Result<()>
     Ok(())
 }
 
+/// Whether an http request to `url` must be sent as https instead.
+fn needs_https(agent: &Agent, url: &Url) -> bool {
+    if agent.config.upgrade_to_https {
+        return true;
+    }
+    match url.domain() {
+        Some(host) if agent.config.hsts => {
+            let now = agent.state.clock.now();
+            agent.state.hsts.requires_https(host, now)
+        }
+        _ => false,
+    }
+}
+
+/// Investigate an https response for a "Strict-Transport-Security" header.
+fn save_hsts(unit: &Unit, resp: &Response) {
+    // https://tools.ietf.org/html/rfc6797#section-8.1
+    // The header is only meaningful over a secure connection, and IP
+    // addresses are never upgraded.
+    let host = match unit.url.domain() {
+        Some(host) if unit.url.scheme() == "https" => host,
+        _ => return,
+    };
+    if let Some(hsts) = resp.hsts() {
+        let now = unit.clock().now();
+        unit.agent.state.hsts.record(host, hsts, now);
+    }
+}
+
 /// Investigate a response for "Set-Cookie" headers.
 #[cfg(feature = "cookies")]
 fn save_cookies(unit: &Unit, resp: &Response) {
@@ -408,16 +676,26 @@ fn save_cookies(unit: &Unit, resp: &Response) {
     if headers.is_empty() {
         return;
     }
-    let cookies = headers.into_iter().flat_map(|header_value| {
+    let config = &unit.agent.config;
+    let max = config.max_cookies_per_response.unwrap_or(usize::MAX);
+    if headers.len() > max {
+        debug!(
+            "discarding {} of {} Set-Cookie headers",
+            headers.len() - max,
+            headers.len()
+        );
+    }
+    let cookies = headers.into_iter().take(max).flat_map(|header_value| {
         match Cookie::parse(header_value.to_string()) {
             Err(_) => None,
             Ok(c) => Some(c),
         }
     });
-    unit.agent
-        .state
-        .cookie_tin
-        .store_response_cookies(cookies, &unit.url.clone());
+    let tin = &unit.agent.state.cookie_tin;
+    match config.max_cookies_total {
+        Some(total) => tin.store_response_cookies_max(cookies, &unit.url, total),
+        None => tin.store_response_cookies(cookies, &unit.url.clone()),
+    }
 }
 
 #[cfg(test)]
@@ -451,3 +729,66 @@ mod tests {
         );
     }
 }
+
+#[cfg(test)]
+mod hsts_tests {
+    use super::*;
+
+    use crate::builder;
+
+    fn unit(agent: &Agent, url: &str) -> Unit {
+        let url: Url = url.parse().unwrap();
+        Unit::new(agent, "GET", &url, &vec![], &Payload::Empty.into_read())
+    }
+
+    #[test]
+    fn upgrade_after_hsts_header() {
+        let agent = builder().hsts(true).build();
+        assert_eq!(unit(&agent, "http://example.com/").url.scheme(), "http");
+
+        let resp: Response = "HTTP/1.1 200 OK\r\n\
+            Strict-Transport-Security: max-age=3600\r\n\r\n"
+            .parse()
+            .unwrap();
+
+        // Ignored when it arrives over plain http.
+        save_hsts(&unit(&agent, "http://example.com/"), &resp);
+        assert_eq!(unit(&agent, "http://example.com/").url.scheme(), "http");
+
+        save_hsts(&unit(&agent, "https://example.com/"), &resp);
+        let upgraded = unit(&agent, "http://example.com:8080/a?b");
+        assert_eq!(upgraded.url.as_str(), "https://example.com:8080/a?b");
+        assert_eq!(unit(&agent, "http://other.com/").url.scheme(), "http");
+        assert_eq!(unit(&agent, "http://www.example.com/").url.scheme(), "http");
+    }
+
+    #[test]
+    fn upgrade_subdomains_after_hsts_header() {
+        let agent = builder().hsts(true).build();
+        let resp: Response = "HTTP/1.1 200 OK\r\n\
+            Strict-Transport-Security: max-age=3600; includeSubDomains\r\n\r\n"
+            .parse()
+            .unwrap();
+        save_hsts(&unit(&agent, "https://example.com/"), &resp);
+        assert_eq!(
+            unit(&agent, "http://www.example.com/").url.scheme(),
+            "https"
+        );
+
+        // max-age=0 forgets the host again.
+        let resp: Response = "HTTP/1.1 200 OK\r\n\
+            Strict-Transport-Security: max-age=0\r\n\r\n"
+            .parse()
+            .unwrap();
+        save_hsts(&unit(&agent, "https://example.com/"), &resp);
+        assert_eq!(unit(&agent, "http://www.example.com/").url.scheme(), "http");
+    }
+
+    #[test]
+    fn upgrade_to_https_static() {
+        let agent = builder().upgrade_to_https(true).build();
+        let upgraded = unit(&agent, "http://example.com/path");
+        assert_eq!(upgraded.url.as_str(), "https://example.com/path");
+        assert_eq!(unit(&agent, "test://example.com/").url.scheme(), "test");
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use rustls::{
+    Certificate, ClientConfig, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError,
+    WebPKIVerifier,
+};
+
+/// The certificate chain presented by a server during the TLS handshake,
+/// handed to [`AgentBuilder::verify_callback`](crate::AgentBuilder::verify_callback).
+///
+/// Certificates are DER encoded, the server's own certificate first.
+#[derive(Debug)]
+pub struct CertificateInfo<'a> {
+    hostname: &'a str,
+    chain: &'a [Certificate],
+    trusted: bool,
+}
+
+impl<'a> CertificateInfo<'a> {
+    /// The host name the connection is for.
+    pub fn hostname(&self) -> &str {
+        self.hostname
+    }
+
+    /// The server's own certificate.
+    pub fn end_entity(&self) -> &[u8] {
+        self.chain.first().map(|c| &c.0[..]).unwrap_or(&[])
+    }
+
+    /// All certificates the server presented, its own first.
+    pub fn chain(&self) -> Vec<&[u8]> {
+        self.chain.iter().map(|c| &c.0[..]).collect()
+    }
+
+    /// Whether the chain passed the normal verification: it leads to one of
+    /// the trusted root certificates, is valid now, and is for the host name.
+    pub fn is_trusted(&self) -> bool {
+        self.trusted
+    }
+}
+
+/// Decides whether to trust a server certificate chain.
+#[derive(Clone)]
+pub(crate) struct VerifyCallback(pub(crate) Arc<dyn Fn(&CertificateInfo) -> bool + Send + Sync>);
+
+impl std::fmt::Debug for VerifyCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "VerifyCallback(...)")
+    }
+}
+
+// Runs the normal verification, then lets the callback decide.
+struct CallbackVerifier {
+    webpki: WebPKIVerifier,
+    callback: VerifyCallback,
+}
+
+impl ServerCertVerifier for CallbackVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        dns_name: webpki::DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let trusted = self
+            .webpki
+            .verify_server_cert(roots, presented_certs, dns_name, ocsp_response)
+            .is_ok();
+        let info = CertificateInfo {
+            hostname: dns_name.into(),
+            chain: presented_certs,
+            trusted,
+        };
+        if (self.callback.0)(&info) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(TLSError::General(
+                "certificate rejected by verify callback".to_string(),
+            ))
+        }
+    }
+}
+
+/// A copy of `config` that verifies server certificates with `callback`.
+pub(crate) fn with_callback(config: &ClientConfig, callback: &VerifyCallback) -> Arc<ClientConfig> {
+    let mut config = config.clone();
+    config
+        .dangerous()
+        .set_certificate_verifier(Arc::new(CallbackVerifier {
+            webpki: WebPKIVerifier::new(),
+            callback: callback.clone(),
+        }));
+    Arc::new(config)
+}